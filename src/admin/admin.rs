@@ -1,5 +1,6 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tuwunel_core::Result;
+use tuwunel_service::admin::{AdminRole, OutputFormat};
 
 use crate::{
 	appservice, appservice::AppserviceCommand, check, check::CheckCommand, context::Context,
@@ -10,6 +11,17 @@
 
 #[derive(Debug, Parser)]
 #[command(name = "tuwunel", version = tuwunel_core::version())]
+pub struct AdminArgs {
+	/// - Format of the response: `markdown` or `json`; defaults to the format
+	///   requested by the caller
+	#[arg(long, global = true)]
+	pub format: Option<OutputFormat>,
+
+	#[command(subcommand)]
+	pub command: AdminCommand,
+}
+
+#[derive(Debug, Subcommand)]
 pub enum AdminCommand {
 	#[command(subcommand)]
 	/// - Commands for managing appservices
@@ -48,6 +60,25 @@ pub enum AdminCommand {
 	Query(QueryCommand),
 }
 
+impl AdminCommand {
+	/// Roles besides superadmin permitted to run the command.
+	pub(crate) fn roles(&self) -> &'static [AdminRole] {
+		use AdminRole::{MediaAdmin, Moderator};
+
+		match self {
+			| Self::Users(command) => command.roles(),
+			| Self::Rooms(command) => command.roles(),
+			| Self::Federation(command) => command.roles(),
+			| Self::Media(_) => &[Moderator, MediaAdmin],
+			| Self::Appservices(_)
+			| Self::Server(_)
+			| Self::Check(_)
+			| Self::Debug(_)
+			| Self::Query(_) => &[],
+		}
+	}
+}
+
 #[tracing::instrument(skip_all, name = "command")]
 pub async fn process(command: AdminCommand, context: &Context<'_>) -> Result {
 	use AdminCommand::*;
@@ -1,20 +1,28 @@
-use std::{fmt, time::SystemTime};
+use std::{
+	fmt,
+	time::{Duration, SystemTime},
+};
 
 use futures::{
 	Future, FutureExt, TryFutureExt,
 	io::{AsyncWriteExt, BufWriter},
 	lock::Mutex,
 };
-use ruma::EventId;
+use ruma::{EventId, UserId};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
 use tuwunel_core::Result;
-use tuwunel_service::Services;
+use tuwunel_service::{Services, admin::OutputFormat};
 
 pub struct Context<'a> {
 	pub services: &'a Services,
 	pub body: &'a [&'a str],
+	pub sender: Option<&'a UserId>,
 	pub timer: SystemTime,
 	pub reply_id: Option<&'a EventId>,
 	pub output: Mutex<BufWriter<Vec<u8>>>,
+	pub format: OutputFormat,
+	pub data: std::sync::Mutex<Option<JsonValue>>,
 }
 
 impl Context<'_> {
@@ -42,4 +50,39 @@ pub fn write_str<'a>(
 				.await
 		})
 	}
+
+	/// Writes the typed result of a command along with its rendering. The data
+	/// is included in the response when the JSON format is requested.
+	pub fn write_data<'a, T: Serialize>(
+		&'a self,
+		data: &T,
+		rendered: &'a str,
+	) -> impl Future<Output = Result> + Send + 'a + use<'a, T> {
+		let result = self.set_data(data);
+		async move {
+			result?;
+			self.write_str(rendered).await
+		}
+	}
+
+	/// Writes the result of a query along with the time it took, the result
+	/// being the typed data of the response.
+	pub fn write_query<'a, T: fmt::Debug + Serialize>(
+		&'a self,
+		query_time: Duration,
+		result: &T,
+	) -> impl Future<Output = Result> + Send + 'a + use<'a, T> {
+		let rendered = self.set_data(result).map(|()| {
+			format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```")
+		});
+
+		async move { self.write_str(&rendered?).await }
+	}
+
+	fn set_data<T: Serialize>(&self, data: &T) -> Result {
+		let data = serde_json::to_value(data)?;
+		_ = self.data.lock().expect("locked").insert(data);
+
+		Ok(())
+	}
 }
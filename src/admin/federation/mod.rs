@@ -3,6 +3,7 @@
 use clap::Subcommand;
 use ruma::{OwnedRoomId, OwnedServerName, OwnedUserId};
 use tuwunel_core::Result;
+use tuwunel_service::admin::AdminRole;
 
 use crate::admin_command_dispatch;
 
@@ -40,3 +41,19 @@ pub enum FederationCommand {
 		user_id: OwnedUserId,
 	},
 }
+
+impl FederationCommand {
+	/// Roles besides superadmin permitted to run the command. Disabling or
+	/// enabling federation for a room is reserved for superadmins.
+	pub(crate) fn roles(&self) -> &'static [AdminRole] {
+		use AdminRole::Moderator;
+
+		match self {
+			| Self::IncomingFederation
+			| Self::FetchSupportWellKnown { .. }
+			| Self::RemoteUserInRooms { .. } => &[Moderator],
+
+			| Self::DisableRoom { .. } | Self::EnableRoom { .. } => &[],
+		}
+	}
+}
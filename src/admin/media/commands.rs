@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use ruma::{Mxc, OwnedEventId, OwnedMxcUri, OwnedServerName};
+use serde_json::json;
 use tuwunel_core::{
 	Err, Result, debug, debug_info, debug_warn, error, info, trace,
 	utils::time::parse_timepoint_ago, warn,
@@ -334,8 +335,17 @@ pub async fn delete_all_from_server(
 pub async fn get_file_info(&self, mxc: OwnedMxcUri) -> Result {
 	let mxc: Mxc<'_> = mxc.as_str().try_into()?;
 	let metadata = self.services.media.get_metadata(&mxc).await;
+	let data = metadata.as_ref().map(|metadata| {
+		json!({
+			"content_type": metadata.content_type,
+			"content_disposition": metadata
+				.content_disposition
+				.as_ref()
+				.map(ToString::to_string),
+		})
+	});
 
-	self.write_str(&format!("```\n{metadata:#?}\n```"))
+	self.write_data(&data, &format!("```\n{metadata:#?}\n```"))
 		.await
 }
 
@@ -12,9 +12,10 @@
 	EventId,
 	events::{
 		relation::InReplyTo,
-		room::message::{Relation::Reply, RoomMessageEventContent},
+		room::message::{MessageType, Relation::Reply, RoomMessageEventContent},
 	},
 };
+use serde_json::{Map as JsonMap, Value as JsonValue};
 use tracing::Level;
 use tracing_subscriber::{EnvFilter, filter::LevelFilter};
 use tuwunel_core::{
@@ -25,18 +26,28 @@
 		fmt::{markdown_table, markdown_table_head},
 	},
 	trace,
-	utils::string::{collect_stream, common_prefix},
+	utils::{
+		string::{collect_stream, common_prefix},
+		time::now_millis,
+	},
 	warn,
 };
 use tuwunel_service::{
 	Services,
-	admin::{CommandInput, CommandOutput, ProcessorFuture, ProcessorResult},
+	admin::{
+		AuditRecord, CommandInput, CommandOutput, JSON_MSGTYPE, OutputFormat, ProcessorFuture,
+		ProcessorResult,
+	},
 };
 
-use crate::{admin, admin::AdminCommand, context::Context};
+use crate::{
+	admin,
+	admin::{AdminArgs, AdminCommand},
+	context::Context,
+};
 
 #[must_use]
-pub fn complete(line: &str) -> String { complete_command(AdminCommand::command(), line) }
+pub fn complete(line: &str) -> String { complete_command(AdminArgs::command(), line) }
 
 #[must_use]
 pub fn dispatch(services: Arc<Services>, command: CommandInput) -> ProcessorFuture {
@@ -45,25 +56,59 @@ pub fn dispatch(services: Arc<Services>, command: CommandInput) -> ProcessorFutu
 
 #[tracing::instrument(skip_all, name = "admin")]
 async fn handle_command(services: Arc<Services>, command: CommandInput) -> ProcessorResult {
-	AssertUnwindSafe(Box::pin(process_command(services, &command)))
+	let ts = now_millis();
+	let timer = SystemTime::now();
+	let result = AssertUnwindSafe(Box::pin(process_command(services.clone(), &command)))
 		.catch_unwind()
 		.await
 		.map_err(Error::from_panic)
-		.unwrap_or_else(|error| handle_panic(&error, &command))
+		.unwrap_or_else(|error| handle_panic(&error, &command));
+
+	services.admin.audit(&AuditRecord {
+		ts,
+		sender: command.sender.clone(),
+		origin: command.origin,
+		command: command.command.trim().to_owned(),
+		duration: timer
+			.elapsed()
+			.unwrap_or_default()
+			.as_millis()
+			.try_into()
+			.unwrap_or(u64::MAX),
+		success: result.is_ok(),
+	});
+
+	result
 }
 
 async fn process_command(services: Arc<Services>, input: &CommandInput) -> ProcessorResult {
-	let (command, args, body) = match parse(&services, input) {
+	let (AdminArgs { format, command }, args, body) = match parse(&services, input) {
 		| Err(error) => return Err(error),
 		| Ok(parsed) => parsed,
 	};
 
+	if !services
+		.admin
+		.may_run(input.sender.as_deref(), command.roles())
+		.await
+	{
+		let message = "You do not hold an admin role permitting this command.";
+		return Err(reply(
+			RoomMessageEventContent::notice_plain(message),
+			input.reply_id.as_deref(),
+		));
+	}
+
+	let format = format.unwrap_or(input.format);
 	let context = Context {
 		services: &services,
 		body: &body,
+		sender: input.sender.as_deref(),
 		timer: SystemTime::now(),
 		reply_id: input.reply_id.as_deref(),
 		output: BufWriter::new(Vec::new()).into(),
+		format,
+		data: Default::default(),
 	};
 
 	let (result, mut logs) = process(&context, command, &args).await;
@@ -77,6 +122,13 @@ async fn process_command(services: Arc<Services>, input: &CommandInput) -> Proce
 	let output =
 		String::from_utf8(take(output.get_mut())).expect("invalid utf8 in command output stream");
 
+	if format == OutputFormat::Json {
+		let data = context.data.lock().expect("locked").take();
+		logs.push_str(&output);
+		let raw = input.format == OutputFormat::Json;
+		return json_reply(result, data, logs, raw, context.reply_id);
+	}
+
 	match result {
 		| Ok(()) if logs.is_empty() =>
 			Ok(Some(reply(RoomMessageEventContent::notice_markdown(output), context.reply_id))),
@@ -95,6 +147,45 @@ async fn process_command(services: Arc<Services>, input: &CommandInput) -> Proce
 	}
 }
 
+/// Responds with the outcome of a command as a JSON object: whether it
+/// succeeded, the typed `data` of commands providing it, the rendered `output`
+/// and the `error` of a failed command. When the caller requested the JSON
+/// format the object is the data of the response, otherwise it is rendered in
+/// a code block.
+#[allow(clippy::result_large_err)]
+fn json_reply(
+	result: Result,
+	data: Option<JsonValue>,
+	output: String,
+	raw: bool,
+	reply_id: Option<&EventId>,
+) -> ProcessorResult {
+	let mut response = JsonMap::new();
+	response.insert("success".into(), result.is_ok().into());
+	if let Some(data) = data {
+		response.insert("data".into(), data);
+	}
+
+	response.insert("output".into(), output.into());
+	if let Err(error) = &result {
+		response.insert("error".into(), error.to_string().into());
+	}
+
+	let rendered = serde_json::to_string_pretty(&response).expect("response serializes to JSON");
+	let content = if raw {
+		MessageType::new(JSON_MSGTYPE, rendered, response)
+			.map(RoomMessageEventContent::new)
+			.expect("custom message type with an object")
+	} else {
+		RoomMessageEventContent::notice_markdown(format!("```json\n{rendered}\n```"))
+	};
+
+	match result {
+		| Ok(()) => Ok(Some(reply(content, reply_id))),
+		| Err(_) => Err(reply(content, reply_id)),
+	}
+}
+
 #[allow(clippy::result_large_err)]
 fn handle_panic(error: &Error, command: &CommandInput) -> ProcessorResult {
 	let link =
@@ -175,7 +266,7 @@ fn capture_create(context: &Context<'_>) -> (Arc<Capture>, Arc<Mutex<String>>) {
 fn parse<'a>(
 	services: &Arc<Services>,
 	input: &'a CommandInput,
-) -> Result<(AdminCommand, Vec<String>, Vec<&'a str>), CommandOutput> {
+) -> Result<(AdminArgs, Vec<String>, Vec<&'a str>), CommandOutput> {
 	let lines = input
 		.command
 		.lines()
@@ -196,9 +287,9 @@ fn parse<'a>(
 	}
 }
 
-fn parse_command(line: &str) -> Result<(AdminCommand, Vec<String>)> {
+fn parse_command(line: &str) -> Result<(AdminArgs, Vec<String>)> {
 	let argv = parse_line(line);
-	let command = AdminCommand::try_parse_from(&argv)?;
+	let command = AdminArgs::try_parse_from(&argv)?;
 	Ok((command, argv))
 }
 
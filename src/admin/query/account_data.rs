@@ -1,7 +1,9 @@
 use clap::Subcommand;
 use futures::StreamExt;
-use ruma::{OwnedRoomId, OwnedUserId};
+use ruma::{OwnedRoomId, OwnedUserId, events::AnyRawAccountDataEvent};
+use serde_json::Value as JsonValue;
 use tuwunel_core::Result;
+use tuwunel_database::Deserialized;
 
 use crate::{admin_command, admin_command_dispatch};
 
@@ -42,12 +44,15 @@ async fn changes_since(
 		.services
 		.account_data
 		.changes_since(room_id.as_deref(), &user_id, since, None)
+		.map(|event| match event {
+			| AnyRawAccountDataEvent::Global(event) => event.into_json(),
+			| AnyRawAccountDataEvent::Room(event) => event.into_json(),
+		})
 		.collect()
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"))
-		.await
+	self.write_query(query_time, &results).await
 }
 
 #[admin_command]
@@ -58,13 +63,13 @@ async fn account_data_get(
 	room_id: Option<OwnedRoomId>,
 ) -> Result {
 	let timer = tokio::time::Instant::now();
-	let results = self
+	let results: Result<JsonValue> = self
 		.services
 		.account_data
 		.get_raw(room_id.as_deref(), &user_id, &kind)
-		.await;
+		.await
+		.deserialized();
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"))
-		.await
+	self.write_query(query_time, &results?).await
 }
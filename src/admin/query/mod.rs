@@ -4,6 +4,7 @@
 mod presence;
 mod pusher;
 mod raw;
+mod read_receipt;
 mod resolver;
 mod room_alias;
 mod room_state_cache;
@@ -17,10 +18,10 @@
 
 use self::{
 	account_data::AccountDataCommand, appservice::AppserviceCommand, globals::GlobalsCommand,
-	presence::PresenceCommand, pusher::PusherCommand, raw::RawCommand, resolver::ResolverCommand,
-	room_alias::RoomAliasCommand, room_state_cache::RoomStateCacheCommand,
-	room_timeline::RoomTimelineCommand, sending::SendingCommand, short::ShortCommand,
-	users::UsersCommand,
+	presence::PresenceCommand, pusher::PusherCommand, raw::RawCommand,
+	read_receipt::ReadReceiptCommand, resolver::ResolverCommand, room_alias::RoomAliasCommand,
+	room_state_cache::RoomStateCacheCommand, room_timeline::RoomTimelineCommand,
+	sending::SendingCommand, short::ShortCommand, users::UsersCommand,
 };
 use crate::admin_command_dispatch;
 
@@ -40,6 +41,10 @@ pub enum QueryCommand {
 	#[command(subcommand)]
 	Presence(PresenceCommand),
 
+	/// - rooms/read_receipt iterators and getters
+	#[command(subcommand)]
+	ReadReceipt(ReadReceiptCommand),
+
 	/// - rooms/alias.rs iterators and getters
 	#[command(subcommand)]
 	RoomAlias(RoomAliasCommand),
@@ -2,7 +2,8 @@
 
 use base64::prelude::*;
 use clap::Subcommand;
-use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde_json::json;
 use tokio::time::Instant;
 use tuwunel_core::{
 	Err, Result, apply, at, is_zero,
@@ -229,8 +230,20 @@ pub async fn compact(
 	let timer = Instant::now();
 	let results = results.await;
 	let query_time = timer.elapsed();
-	self.write_str(&format!("Jobs completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"))
-		.await
+
+	let data: Vec<_> = results
+		.iter()
+		.map(|result| match result {
+			| Ok(map) => json!({ "map": map }),
+			| Err(error) => json!({ "error": error.to_string() }),
+		})
+		.collect();
+
+	self.write_data(
+		&data,
+		&format!("Jobs completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"),
+	)
+	.await
 }
 
 #[admin_command]
@@ -244,27 +257,23 @@ pub async fn raw_count(&self, map: Option<String>, prefix: Option<String>) -> Re
 		.await;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{count:#?}\n```"))
-		.await
+	self.write_query(query_time, &count).await
 }
 
 #[admin_command]
 pub async fn raw_keys(&self, map: String, prefix: Option<String>) -> Result {
-	writeln!(self, "```").boxed().await?;
-
 	let map = self.services.db.get(map.as_str())?;
 	let timer = Instant::now();
-	prefix
+	let result = prefix
 		.as_deref()
 		.map_or_else(|| map.raw_keys().boxed(), |prefix| map.raw_keys_prefix(prefix).boxed())
 		.map_ok(String::from_utf8_lossy)
-		.try_for_each(|str| writeln!(self, "{str:?}"))
-		.boxed()
+		.map_ok(Cow::into_owned)
+		.try_collect::<Vec<String>>()
 		.await?;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -285,8 +294,7 @@ pub async fn raw_keys_sizes(&self, map: Option<String>, prefix: Option<String>)
 		.await;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("```\n{result:#?}\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -303,8 +311,7 @@ pub async fn raw_keys_total(&self, map: Option<String>, prefix: Option<String>)
 		.await;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("```\n{result:#?}\n\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -326,8 +333,7 @@ pub async fn raw_vals_sizes(&self, map: Option<String>, prefix: Option<String>)
 		.await;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("```\n{result:#?}\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -345,28 +351,23 @@ pub async fn raw_vals_total(&self, map: Option<String>, prefix: Option<String>)
 		.await;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("```\n{result:#?}\n\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
 pub async fn raw_iter(&self, map: String, prefix: Option<String>) -> Result {
-	writeln!(self, "```").await?;
-
 	let map = self.services.db.get(&map)?;
 	let timer = Instant::now();
-	prefix
+	let result = prefix
 		.as_deref()
 		.map_or_else(|| map.raw_stream().boxed(), |prefix| map.raw_stream_prefix(prefix).boxed())
 		.map_ok(apply!(2, String::from_utf8_lossy))
 		.map_ok(apply!(2, Cow::into_owned))
-		.try_for_each(|keyval| writeln!(self, "{keyval:?}"))
-		.boxed()
+		.try_collect::<Vec<(String, String)>>()
 		.await?;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -376,20 +377,18 @@ pub async fn raw_keys_from(
 	start: String,
 	limit: Option<usize>,
 ) -> Result {
-	writeln!(self, "```").await?;
-
 	let map = self.services.db.get(&map)?;
 	let timer = Instant::now();
-	map.raw_keys_from(&start)
+	let result = map
+		.raw_keys_from(&start)
 		.map_ok(String::from_utf8_lossy)
+		.map_ok(Cow::into_owned)
 		.take(limit.unwrap_or(usize::MAX))
-		.try_for_each(|str| writeln!(self, "{str:?}"))
-		.boxed()
+		.try_collect::<Vec<String>>()
 		.await?;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("\n```\n\nQuery completed in {query_time:?}"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -410,8 +409,7 @@ pub async fn raw_iter_from(
 		.await?;
 
 	let query_time = timer.elapsed();
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -439,8 +437,7 @@ pub async fn raw_get(&self, map: String, key: String, base64: bool) -> Result {
 		String::from_utf8_lossy(&handle).to_string()
 	};
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -453,7 +450,8 @@ pub async fn raw_maps(&self) -> Result {
 		.copied()
 		.collect();
 
-	self.write_str(&format!("{list:#?}")).await
+	self.write_data(&list, &format!("{list:#?}"))
+		.await
 }
 
 fn with_maps_or<'a>(
@@ -0,0 +1,56 @@
+use clap::Subcommand;
+use ruma::{OwnedEventId, OwnedRoomOrAliasId, OwnedUserId};
+use tuwunel_core::Result;
+
+use crate::{admin_command, admin_command_dispatch};
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+/// All the getters and iterators from src/service/rooms/read_receipt
+pub enum ReadReceiptCommand {
+	/// - Users who have read up to each event in the room
+	ReadBy {
+		room_id: OwnedRoomOrAliasId,
+
+		/// Only show the readers of this event
+		event_id: Option<OwnedEventId>,
+	},
+
+	/// - The private read receipt of a user in the room
+	PrivateRead {
+		room_id: OwnedRoomOrAliasId,
+		user_id: OwnedUserId,
+	},
+}
+
+#[admin_command]
+async fn read_by(&self, room_id: OwnedRoomOrAliasId, event_id: Option<OwnedEventId>) -> Result {
+	let room_id = self.services.alias.resolve(&room_id).await?;
+
+	let timer = tokio::time::Instant::now();
+	let mut read_by = self.services.read_receipt.read_by(&room_id).await;
+	if let Some(event_id) = &event_id {
+		read_by.retain(|id, _| id == event_id);
+	}
+	let query_time = timer.elapsed();
+
+	self.write_query(query_time, &read_by).await
+}
+
+#[admin_command]
+async fn private_read(&self, room_id: OwnedRoomOrAliasId, user_id: OwnedUserId) -> Result {
+	let room_id = self.services.alias.resolve(&room_id).await?;
+
+	let timer = tokio::time::Instant::now();
+	let receipt = self
+		.services
+		.read_receipt
+		.private_read_get(&room_id, &user_id)
+		.await?;
+	let query_time = timer.elapsed();
+
+	let rendered =
+		format!("Query completed in {query_time:?}:\n\n```json\n{}\n```", receipt.json());
+
+	self.write_data(&receipt, &rendered).await
+}
@@ -1,6 +1,9 @@
+use std::fmt::Write;
+
 use clap::Subcommand;
 use futures::StreamExt;
 use ruma::OwnedServerName;
+use serde_json::json;
 use tuwunel_core::{Result, utils::time};
 
 use crate::{admin_command, admin_command_dispatch};
@@ -24,9 +27,11 @@ pub enum ResolverCommand {
 async fn destinations_cache(&self, server_name: Option<OwnedServerName>) -> Result {
 	use tuwunel_service::resolver::cache::CachedDest;
 
-	writeln!(self, "| Server Name | Destination | Hostname | Expires |").await?;
-	writeln!(self, "| ----------- | ----------- | -------- | ------- |").await?;
+	let mut out = String::new();
+	writeln!(out, "| Server Name | Destination | Hostname | Expires |")?;
+	writeln!(out, "| ----------- | ----------- | -------- | ------- |")?;
 
+	let mut data = Vec::new();
 	let mut destinations = self
 		.services
 		.resolver
@@ -34,43 +39,45 @@ async fn destinations_cache(&self, server_name: Option<OwnedServerName>) -> Resu
 		.destinations()
 		.boxed();
 
-	while let Some((name, CachedDest { dest, host, expire })) = destinations.next().await {
+	while let Some((name, cached)) = destinations.next().await {
 		if let Some(server_name) = server_name.as_ref() {
 			if name != server_name {
 				continue;
 			}
 		}
 
-		let expire = time::format(expire, "%+");
-		self.write_str(&format!("| {name} | {dest} | {host} | {expire} |\n"))
-			.await?;
+		let CachedDest { dest, host, expire } = &cached;
+		let expire = time::format(*expire, "%+");
+		writeln!(out, "| {name} | {dest} | {host} | {expire} |")?;
+		data.push(json!({ "server_name": name, "destination": cached }));
 	}
 
-	Ok(())
+	self.write_data(&data, &out).await
 }
 
 #[admin_command]
 async fn overrides_cache(&self, server_name: Option<String>) -> Result {
 	use tuwunel_service::resolver::cache::CachedOverride;
 
-	writeln!(self, "| Server Name | IP  | Port | Expires | Overriding |").await?;
-	writeln!(self, "| ----------- | --- | ----:| ------- | ---------- |").await?;
+	let mut out = String::new();
+	writeln!(out, "| Server Name | IP  | Port | Expires | Overriding |")?;
+	writeln!(out, "| ----------- | --- | ----:| ------- | ---------- |")?;
 
+	let mut data = Vec::new();
 	let mut overrides = self.services.resolver.cache.overrides().boxed();
 
-	while let Some((name, CachedOverride { ips, port, expire, overriding })) =
-		overrides.next().await
-	{
+	while let Some((name, cached)) = overrides.next().await {
 		if let Some(server_name) = server_name.as_ref() {
 			if name != server_name {
 				continue;
 			}
 		}
 
-		let expire = time::format(expire, "%+");
-		self.write_str(&format!("| {name} | {ips:?} | {port} | {expire} | {overriding:?} |\n"))
-			.await?;
+		let CachedOverride { ips, port, expire, overriding } = &cached;
+		let expire = time::format(*expire, "%+");
+		writeln!(out, "| {name} | {ips:?} | {port} | {expire} | {overriding:?} |")?;
+		data.push(json!({ "name": name, "override": cached }));
 	}
 
-	Ok(())
+	self.write_data(&data, &out).await
 }
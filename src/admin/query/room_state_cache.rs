@@ -88,11 +88,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &result).await
 		},
 		| RoomStateCacheCommand::RoomServers { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -104,11 +100,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::ServerRooms { server } => {
 			let timer = tokio::time::Instant::now();
@@ -120,11 +112,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::RoomMembers { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -136,11 +124,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::LocalUsersInRoom { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -152,11 +136,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::ActiveLocalUsersInRoom { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -168,11 +148,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::RoomJoinedCount { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -182,11 +158,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results?).await
 		},
 		| RoomStateCacheCommand::RoomInvitedCount { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -196,11 +168,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results?).await
 		},
 		| RoomStateCacheCommand::RoomUserOnceJoined { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -212,11 +180,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::RoomMembersInvited { room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -228,11 +192,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::GetInviteCount { room_id, user_id } => {
 			let timer = tokio::time::Instant::now();
@@ -242,11 +202,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results?).await
 		},
 		| RoomStateCacheCommand::GetLeftCount { room_id, user_id } => {
 			let timer = tokio::time::Instant::now();
@@ -256,11 +212,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results?).await
 		},
 		| RoomStateCacheCommand::RoomsJoined { user_id } => {
 			let timer = tokio::time::Instant::now();
@@ -272,11 +224,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::RoomsInvited { user_id } => {
 			let timer = tokio::time::Instant::now();
@@ -287,11 +235,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::RoomsLeft { user_id } => {
 			let timer = tokio::time::Instant::now();
@@ -302,11 +246,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
 		},
 		| RoomStateCacheCommand::InviteState { user_id, room_id } => {
 			let timer = tokio::time::Instant::now();
@@ -316,11 +256,7 @@ pub async fn process(subcommand: RoomStateCacheCommand, context: &Context<'_>) -
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results?).await
 		},
 	}
 }
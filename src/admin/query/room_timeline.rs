@@ -1,6 +1,7 @@
 use clap::Subcommand;
 use futures::TryStreamExt;
 use ruma::OwnedRoomOrAliasId;
+use serde_json::json;
 use tuwunel_core::{PduCount, Result, utils::stream::TryTools};
 
 use crate::{admin_command, admin_command_dispatch};
@@ -33,7 +34,8 @@ pub async fn last(&self, room_id: OwnedRoomOrAliasId) -> Result {
 		.last_timeline_count(None, &room_id, None)
 		.await?;
 
-	self.write_str(&format!("{result:#?}")).await
+	self.write_data(&result.to_string(), &format!("{result:#?}"))
+		.await
 }
 
 #[admin_command]
@@ -55,5 +57,11 @@ pub async fn pdus(
 		.try_collect()
 		.await?;
 
-	self.write_str(&format!("{result:#?}")).await
+	let data: Vec<_> = result
+		.iter()
+		.map(|(count, pdu)| json!({ "count": count.to_string(), "pdu": pdu }))
+		.collect();
+
+	self.write_data(&data, &format!("{result:#?}"))
+		.await
 }
@@ -1,8 +1,9 @@
 use clap::Subcommand;
 use futures::StreamExt;
 use ruma::{OwnedServerName, OwnedUserId};
-use tuwunel_core::{Err, Result};
-use tuwunel_service::sending::Destination;
+use serde_json::{Value as JsonValue, json};
+use tuwunel_core::{Err, Result, matrix::pdu::PduId};
+use tuwunel_service::sending::{Destination, SendingEvent};
 
 use crate::Context;
 
@@ -72,10 +73,20 @@ pub async fn process(subcommand: SendingCommand, context: &Context<'_>) -> Resul
 			let active_requests = results.collect::<Vec<_>>().await;
 			let query_time = timer.elapsed();
 
+			let data: Vec<_> = active_requests
+				.iter()
+				.map(|(_, event, dest)| {
+					json!({ "destination": destination_data(dest), "event": event_data(event) })
+				})
+				.collect();
+
 			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{active_requests:#?}\n```"
-				))
+				.write_data(
+					&data,
+					&format!(
+						"Query completed in {query_time:?}:\n\n```rs\n{active_requests:#?}\n```"
+					),
+				)
 				.await
 		},
 		| SendingCommand::QueuedRequests {
@@ -143,10 +154,18 @@ pub async fn process(subcommand: SendingCommand, context: &Context<'_>) -> Resul
 			let queued_requests = results.collect::<Vec<_>>().await;
 			let query_time = timer.elapsed();
 
+			let data: Vec<_> = queued_requests
+				.iter()
+				.map(|(_, event)| event_data(event))
+				.collect();
+
 			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{queued_requests:#?}\n```"
-				))
+				.write_data(
+					&data,
+					&format!(
+						"Query completed in {query_time:?}:\n\n```rs\n{queued_requests:#?}\n```"
+					),
+				)
 				.await
 		},
 		| SendingCommand::ActiveRequestsFor {
@@ -215,10 +234,18 @@ pub async fn process(subcommand: SendingCommand, context: &Context<'_>) -> Resul
 			let active_requests = results.collect::<Vec<_>>().await;
 			let query_time = timer.elapsed();
 
+			let data: Vec<_> = active_requests
+				.iter()
+				.map(|(_, event)| event_data(event))
+				.collect();
+
 			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{active_requests:#?}\n```"
-				))
+				.write_data(
+					&data,
+					&format!(
+						"Query completed in {query_time:?}:\n\n```rs\n{active_requests:#?}\n```"
+					),
+				)
 				.await
 		},
 		| SendingCommand::GetLatestEduCount { server_name } => {
@@ -230,11 +257,30 @@ pub async fn process(subcommand: SendingCommand, context: &Context<'_>) -> Resul
 				.await;
 			let query_time = timer.elapsed();
 
-			context
-				.write_str(&format!(
-					"Query completed in {query_time:?}:\n\n```rs\n{results:#?}\n```"
-				))
-				.await
+			context.write_query(query_time, &results).await
+		},
+	}
+}
+
+fn destination_data(dest: &Destination) -> JsonValue {
+	match dest {
+		| Destination::Appservice(appservice_id) => json!({ "appservice_id": appservice_id }),
+		| Destination::Push(user_id, push_key) =>
+			json!({ "user_id": user_id, "push_key": push_key }),
+		| Destination::Federation(server_name) => json!({ "server_name": server_name }),
+	}
+}
+
+fn event_data(event: &SendingEvent) -> JsonValue {
+	match event {
+		| SendingEvent::Pdu(pdu_id) => {
+			let PduId { shortroomid, shorteventid } = (*pdu_id).into();
+			json!({ "pdu": { "shortroomid": shortroomid, "count": shorteventid.to_string() } })
+		},
+		| SendingEvent::Edu(edu) => {
+			let edu: JsonValue = serde_json::from_slice(edu).unwrap_or_default();
+			json!({ "edu": edu })
 		},
+		| SendingEvent::Flush => json!("flush"),
 	}
 }
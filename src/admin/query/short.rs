@@ -25,7 +25,8 @@ pub async fn short_event_id(&self, event_id: OwnedEventId) -> Result {
 		.get_shorteventid(&event_id)
 		.await?;
 
-	self.write_str(&format!("{shortid:#?}")).await
+	self.write_data(&shortid, &format!("{shortid:#?}"))
+		.await
 }
 
 #[admin_command]
@@ -38,5 +39,6 @@ pub async fn short_room_id(&self, room_id: OwnedRoomOrAliasId) -> Result {
 		.get_shortroomid(&room_id)
 		.await?;
 
-	self.write_str(&format!("{shortid:#?}")).await
+	self.write_data(&shortid, &format!("{shortid:#?}"))
+		.await
 }
@@ -115,8 +115,7 @@ async fn auth_ldap(&self, user_dn: String, password: String) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -125,8 +124,7 @@ async fn search_ldap(&self, user_id: OwnedUserId) -> Result {
 	let result = self.services.users.search_ldap(&user_id).await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -141,8 +139,7 @@ async fn get_shared_rooms(&self, user_a: OwnedUserId, user_b: OwnedUserId) -> Re
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -161,8 +158,7 @@ async fn get_backup_session(
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -180,8 +176,7 @@ async fn get_room_backups(
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -194,8 +189,7 @@ async fn get_all_backups(&self, user_id: OwnedUserId, version: String) -> Result
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -208,8 +202,7 @@ async fn get_backup_algorithm(&self, user_id: OwnedUserId, version: String) -> R
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -222,8 +215,7 @@ async fn get_latest_backup_version(&self, user_id: OwnedUserId) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -236,8 +228,7 @@ async fn get_latest_backup(&self, user_id: OwnedUserId) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -253,8 +244,7 @@ async fn iter_users(&self) -> Result {
 
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -269,8 +259,7 @@ async fn iter_users2(&self) -> Result {
 
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -279,8 +268,7 @@ async fn count_users(&self) -> Result {
 	let result = self.services.users.count().await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -289,8 +277,7 @@ async fn password_hash(&self, user_id: OwnedUserId) -> Result {
 	let result = self.services.users.password_hash(&user_id).await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -306,8 +293,7 @@ async fn list_devices(&self, user_id: OwnedUserId) -> Result {
 
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{devices:#?}\n```"))
-		.await
+	self.write_query(query_time, &devices).await
 }
 
 #[admin_command]
@@ -321,8 +307,7 @@ async fn list_devices_metadata(&self, user_id: OwnedUserId) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{devices:#?}\n```"))
-		.await
+	self.write_query(query_time, &devices).await
 }
 
 #[admin_command]
@@ -335,8 +320,7 @@ async fn get_device_metadata(&self, user_id: OwnedUserId, device_id: OwnedDevice
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{device:#?}\n```"))
-		.await
+	self.write_query(query_time, &device?).await
 }
 
 #[admin_command]
@@ -349,8 +333,7 @@ async fn get_devices_version(&self, user_id: OwnedUserId) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{device:#?}\n```"))
-		.await
+	self.write_query(query_time, &device?).await
 }
 
 #[admin_command]
@@ -363,8 +346,7 @@ async fn count_one_time_keys(&self, user_id: OwnedUserId, device_id: OwnedDevice
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
 
 #[admin_command]
@@ -377,8 +359,7 @@ async fn get_device_keys(&self, user_id: OwnedUserId, device_id: OwnedDeviceId)
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -391,8 +372,7 @@ async fn get_user_signing_key(&self, user_id: OwnedUserId) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -405,8 +385,7 @@ async fn get_master_key(&self, user_id: OwnedUserId) -> Result {
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result?).await
 }
 
 #[admin_command]
@@ -420,6 +399,5 @@ async fn get_to_device_events(&self, user_id: OwnedUserId, device_id: OwnedDevic
 		.await;
 	let query_time = timer.elapsed();
 
-	self.write_str(&format!("Query completed in {query_time:?}:\n\n```rs\n{result:#?}\n```"))
-		.await
+	self.write_query(query_time, &result).await
 }
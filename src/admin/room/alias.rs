@@ -40,6 +40,18 @@ pub enum RoomAliasCommand {
 		/// If set, only list the aliases for this room
 		room_id: Option<OwnedRoomId>,
 	},
+
+	/// - Find rooms whose canonical alias event names local aliases which do
+	///   not exist or point to another room
+	AuditCanonical {
+		/// If set, only audit this room
+		room_id: Option<OwnedRoomId>,
+
+		/// Remove the dangling aliases from the canonical alias events where a
+		/// local user is permitted to
+		#[arg(long)]
+		fix: bool,
+	},
 }
 
 pub async fn process(command: RoomAliasCommand, context: &Context<'_>) -> Result {
@@ -109,10 +121,30 @@ pub async fn process(command: RoomAliasCommand, context: &Context<'_>) -> Result
 							.await
 						{
 							| Err(err) => Err!("Failed to remove alias: {err}"),
-							| Ok(()) =>
-								context
-									.write_str(&format!("Removed alias from {id}"))
-									.await,
+							| Ok(()) => {
+								let canonical = services
+									.alias
+									.remove_canonical_alias(&id, &room_alias, None)
+									.await;
+
+								match canonical {
+									| Ok(true) =>
+										context
+											.write_str(&format!(
+												"Removed alias from {id} and its canonical \
+												 alias event"
+											))
+											.await,
+									| Ok(false) =>
+										context
+											.write_str(&format!("Removed alias from {id}"))
+											.await,
+									| Err(err) => Err!(
+										"Removed alias from {id} but failed to update its \
+										 canonical alias event: {err}"
+									),
+								}
+							},
 						},
 					}
 				},
@@ -129,7 +161,8 @@ pub async fn process(command: RoomAliasCommand, context: &Context<'_>) -> Result
 								.await,
 					}
 				},
-				| RoomAliasCommand::List { .. } => unreachable!(),
+				| RoomAliasCommand::List { .. } | RoomAliasCommand::AuditCanonical { .. } =>
+					unreachable!(),
 			}
 		},
 		| RoomAliasCommand::List { room_id } =>
@@ -171,5 +204,62 @@ pub async fn process(command: RoomAliasCommand, context: &Context<'_>) -> Result
 				let plain = format!("Aliases:\n{plain_list}");
 				context.write_str(&plain).await
 			},
+		| RoomAliasCommand::AuditCanonical { room_id, fix } => {
+			let room_ids: Vec<OwnedRoomId> = match room_id {
+				| Some(room_id) => vec![room_id],
+				| None =>
+					services
+						.metadata
+						.iter_ids()
+						.map(ToOwned::to_owned)
+						.collect()
+						.await,
+			};
+
+			let mut output = String::new();
+			for room_id in &room_ids {
+				let dangling = services
+					.alias
+					.dangling_canonical_aliases(room_id)
+					.await;
+
+				if dangling.is_empty() {
+					continue;
+				}
+
+				let aliases = dangling
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>()
+					.join(", ");
+
+				let status = if fix {
+					match services
+						.alias
+						.remove_dangling_canonical_aliases(room_id)
+						.await
+					{
+						| Ok(true) => " (fixed)",
+						| Ok(false) => " (no permission to fix)",
+						| Err(_) => " (failed to fix)",
+					}
+				} else {
+					""
+				};
+
+				writeln!(output, "- {room_id}: {aliases}{status}")
+					.expect("should be able to write to string buffer");
+			}
+
+			if output.is_empty() {
+				return context
+					.write_str("No dangling canonical aliases found.")
+					.await;
+			}
+
+			context
+				.write_str(&format!("Rooms with dangling canonical aliases:\n{output}"))
+				.await
+		},
 	}
 }
@@ -1,6 +1,10 @@
-use futures::StreamExt;
-use ruma::OwnedRoomId;
-use tuwunel_core::{Err, Result};
+use std::fmt::Display;
+
+use futures::{FutureExt, StreamExt};
+use ruma::{OwnedRoomId, RoomVersionId};
+use serde_json::json;
+use tuwunel_core::{Err, Result, utils::stream::ReadyExt};
+use tuwunel_service::rooms::upgrade::Report;
 
 use crate::{PAGE_SIZE, admin_command, get_room_info};
 
@@ -55,7 +59,18 @@ pub async fn list_rooms(
 		.collect::<Vec<_>>()
 		.join("\n");
 
-	self.write_str(&format!("Rooms ({}):\n```\n{body}\n```", rooms.len(),))
+	let data: Vec<_> = rooms
+		.iter()
+		.map(|(room_id, joined_members, name)| {
+			json!({
+				"room_id": room_id,
+				"joined_members": joined_members,
+				"name": name,
+			})
+		})
+		.collect();
+
+	self.write_data(&data, &format!("Rooms ({}):\n```\n{body}\n```", rooms.len()))
 		.await
 }
 
@@ -67,7 +82,12 @@ pub async fn exists(&self, room_id: OwnedRoomId) -> Result {
 }
 
 #[admin_command]
-pub async fn delete_room(&self, room_id: OwnedRoomId) -> Result {
+pub async fn delete_room(
+	&self,
+	room_id: OwnedRoomId,
+	block: bool,
+	reason: Option<String>,
+) -> Result {
 	if self.services.admin.is_admin_room(&room_id).await {
 		return Err!("Cannot delete admin room");
 	}
@@ -76,11 +96,185 @@ pub async fn delete_room(&self, room_id: OwnedRoomId) -> Result {
 
 	self.services
 		.delete
-		.delete_room(&room_id, state_lock)
+		.delete_room(&room_id, block, reason, state_lock)
+		.boxed()
 		.await?;
 
+	if block {
+		return self
+			.write_str("Successfully deleted the room from our database and blocked it.")
+			.await;
+	}
+
 	self.write_str("Successfully deleted the room from our database.")
-		.await?;
+		.await
+}
+
+#[admin_command]
+pub async fn upgrade(
+	&self,
+	room_id: OwnedRoomId,
+	version: RoomVersionId,
+	dry_run: bool,
+) -> Result {
+	let report = if dry_run {
+		self.services
+			.upgrade
+			.plan(&room_id, &version)
+			.await?
+	} else {
+		self.services
+			.upgrade
+			.migrate(&room_id, &version)
+			.boxed()
+			.await?
+	};
+
+	self.write_str(&format_report(&report)).await
+}
+
+#[admin_command]
+pub async fn upgrade_all(
+	&self,
+	below: RoomVersionId,
+	to: Option<RoomVersionId>,
+	dry_run: bool,
+) -> Result {
+	let Some(below) = version_number(&below) else {
+		return Err!("Only numeric room versions can be compared.");
+	};
+
+	let to = to.unwrap_or_else(|| self.services.config.default_room_version.clone());
+	if version_number(&to).is_none_or(|to| to < below) {
+		return Err!("Target version {to} is below version {below}.");
+	}
+
+	let rooms: Vec<OwnedRoomId> = self
+		.services
+		.metadata
+		.iter_ids()
+		.filter_map(async |room_id| {
+			let version = self
+				.services
+				.state
+				.get_room_version(room_id)
+				.await;
+			version
+				.ok()
+				.and_then(|version| version_number(&version))
+				.is_some_and(|version| version < below)
+				.then_some(room_id)
+		})
+		.filter_map(async |room_id| {
+			let banned = self.services.metadata.is_banned(room_id).await;
+			let tombstoned = self.services.upgrade.is_tombstoned(room_id).await;
+			(!banned && !tombstoned).then_some(room_id)
+		})
+		.filter(|room_id| {
+			self.services
+				.state_cache
+				.local_users_in_room(room_id)
+				.ready_any(|_| true)
+		})
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	if rooms.is_empty() {
+		return self
+			.write_str(&format!("No rooms below version {below}."))
+			.await;
+	}
+
+	let (mut upgraded, mut failed) = (0_usize, 0_usize);
+	for room_id in &rooms {
+		let result = if dry_run {
+			self.services.upgrade.plan(room_id, &to).await
+		} else {
+			self.services
+				.upgrade
+				.migrate(room_id, &to)
+				.boxed()
+				.await
+		};
+
+		match result {
+			| Ok(report) => {
+				upgraded = upgraded.saturating_add(1);
+				self.write_str(&format_report(&report)).await?;
+			},
+			| Err(e) => {
+				failed = failed.saturating_add(1);
+				writeln!(self, "{room_id}: skipped: {e}\n").await?;
+			},
+		}
+	}
+
+	let verb = if dry_run { "Would upgrade" } else { "Upgraded" };
+	writeln!(
+		self,
+		"{verb} {upgraded} of {} rooms to version {to}; {failed} skipped.",
+		rooms.len()
+	)
+	.await
+}
+
+fn list<T: Display>(items: &[T]) -> String {
+	if items.is_empty() {
+		return "none".to_owned();
+	}
+
+	items
+		.iter()
+		.map(ToString::to_string)
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+fn version_number(version: &RoomVersionId) -> Option<u64> { version.as_str().parse().ok() }
+
+fn format_report(report: &Report) -> String {
+	let version = |version: &Option<RoomVersionId>| {
+		version
+			.as_ref()
+			.map_or("?", RoomVersionId::as_str)
+			.to_owned()
+	};
+
+	let mut lines = vec![format!(
+		"{} (version {} -> {})",
+		report.room_id,
+		version(&report.from_version),
+		version(&report.to_version),
+	)];
+
+	match &report.replacement_room {
+		| Some(replacement_room) => lines.push(format!("replacement room: {replacement_room}")),
+		| None => lines.push("dry run, nothing was changed".to_owned()),
+	}
+
+	if let Some(sender) = &report.sender {
+		lines.push(format!("acting user: {sender}"));
+	}
+
+	lines.extend([
+		format!("local aliases: {}", list(&report.aliases)),
+		format!("canonical alias: {}", report.canonical_alias),
+		format!("published in directory: {}", report.published),
+		format!("pinned events: {}", report.pinned_events),
+		format!("parent spaces: {}", list(&report.space_parents)),
+		format!("space children: {}", report.space_children.len()),
+		format!("local members to join: {}", list(&report.members)),
+		format!("users with tags: {}", report.tags.len()),
+		format!("users with push rules: {}", report.push_rules.len()),
+	]);
+
+	lines.extend(
+		report
+			.errors
+			.iter()
+			.map(|error| format!("error: {error}")),
+	);
 
-	Ok(())
+	format!("```\n{}\n```\n", lines.join("\n"))
 }
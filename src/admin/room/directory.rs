@@ -29,7 +29,7 @@ pub async fn process(command: RoomDirectoryCommand, context: &Context<'_>) -> Re
 	let services = context.services;
 	match command {
 		| RoomDirectoryCommand::Publish { room_id } => {
-			services.directory.set_public(&room_id);
+			services.directory.set_public(&room_id).await;
 			context.write_str("Room published").await
 		},
 		| RoomDirectoryCommand::Unpublish { room_id } => {
@@ -3,14 +3,16 @@
 mod directory;
 mod info;
 mod moderation;
+mod space;
 
 use clap::Subcommand;
-use ruma::OwnedRoomId;
+use ruma::{OwnedRoomId, RoomVersionId};
 use tuwunel_core::Result;
+use tuwunel_service::admin::AdminRole;
 
 use self::{
 	alias::RoomAliasCommand, directory::RoomDirectoryCommand, info::RoomInfoCommand,
-	moderation::RoomModerationCommand,
+	moderation::RoomModerationCommand, space::RoomSpaceCommand,
 };
 use crate::admin_command_dispatch;
 
@@ -52,13 +54,108 @@ pub enum RoomCommand {
 	/// - Manage the room directory
 	Directory(RoomDirectoryCommand),
 
+	#[command(subcommand)]
+	/// - Moderate every room of a space
+	Space(RoomSpaceCommand),
+
 	/// - Check if we know about a room
 	Exists {
 		room_id: OwnedRoomId,
 	},
 
 	/// - Delete room
+	///
+	/// Blocked rooms are listed by `moderation list-banned-rooms` and can be
+	/// unblocked again with `moderation unban-room`.
 	DeleteRoom {
 		room_id: OwnedRoomId,
+
+		/// Keep the room banned after deletion so local users cannot join it
+		/// again over federation
+		#[arg(long)]
+		block: bool,
+
+		/// Kick the local users from the room with this reason before
+		/// deleting it
+		#[arg(long)]
+		reason: Option<String>,
+	},
+
+	/// - Upgrade a room to a new room version
+	///
+	/// Besides the usual tombstone and state transfer this moves published
+	/// directory entries, space parent/child links, pinned events, and local
+	/// users' tags and push rules, then joins local members to the
+	/// replacement room.
+	Upgrade {
+		room_id: OwnedRoomId,
+
+		version: RoomVersionId,
+
+		/// Only report what would be migrated
+		#[arg(long)]
+		dry_run: bool,
 	},
+
+	/// - Upgrade every room with local members below a room version
+	UpgradeAll {
+		/// Upgrade rooms whose version is lower than this
+		#[arg(long)]
+		below: RoomVersionId,
+
+		/// Version to upgrade to; defaults to `default_room_version`
+		#[arg(long)]
+		to: Option<RoomVersionId>,
+
+		/// Only report what would be migrated
+		#[arg(long)]
+		dry_run: bool,
+	},
+}
+
+impl RoomCommand {
+	/// Roles besides superadmin permitted to run the command. Moderators look
+	/// after single rooms; deleting or upgrading rooms, acting on lists of
+	/// rooms, raising power levels and changing server ACLs or memberships
+	/// across a space are reserved for superadmins.
+	pub(crate) fn roles(&self) -> &'static [AdminRole] {
+		use AdminRole::Moderator;
+
+		match self {
+			| Self::ListRooms { .. }
+			| Self::Info(_)
+			| Self::Exists { .. }
+			| Self::Directory(_)
+			| Self::Moderation(
+				RoomModerationCommand::BanRoom { .. }
+				| RoomModerationCommand::UnbanRoom { .. }
+				| RoomModerationCommand::ListBannedRooms { .. },
+			)
+			| Self::Alias(
+				RoomAliasCommand::Set { .. }
+				| RoomAliasCommand::Remove { .. }
+				| RoomAliasCommand::Which { .. }
+				| RoomAliasCommand::List { .. }
+				| RoomAliasCommand::AuditCanonical { fix: false, .. },
+			)
+			| Self::Space(
+				RoomSpaceCommand::List { .. }
+				| RoomSpaceCommand::Ban { .. }
+				| RoomSpaceCommand::Kick { .. }
+				| RoomSpaceCommand::Unban { .. },
+			) => &[Moderator],
+
+			| Self::Moderation(RoomModerationCommand::BanListOfRooms)
+			| Self::Alias(RoomAliasCommand::AuditCanonical { fix: true, .. })
+			| Self::Space(
+				RoomSpaceCommand::SetPowerLevel { .. }
+				| RoomSpaceCommand::DenyServer { .. }
+				| RoomSpaceCommand::UndenyServer { .. }
+				| RoomSpaceCommand::ForceJoin { .. },
+			)
+			| Self::DeleteRoom { .. }
+			| Self::Upgrade { .. }
+			| Self::UpgradeAll { .. } => &[],
+		}
+	}
 }
@@ -35,7 +35,8 @@ pub enum RoomModerationCommand {
 		room: OwnedRoomOrAliasId,
 	},
 
-	/// - List of all rooms we have banned
+	/// - List of all rooms we have banned, including rooms deleted with
+	///   `delete-room --block`
 	ListBannedRooms {
 		#[arg(long)]
 		/// Whether to only output room IDs without supplementary room
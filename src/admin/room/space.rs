@@ -0,0 +1,253 @@
+use std::fmt::Write;
+
+use clap::Subcommand;
+use ruma::{Int, OwnedRoomOrAliasId, OwnedServerName};
+use tuwunel_core::Result;
+use tuwunel_service::rooms::spaces::SpaceAction;
+
+use crate::{
+	admin_command, admin_command_dispatch,
+	utils::{parse_local_user_id, parse_user_id},
+};
+
+/// Space-wide commands apply to the space and every room of its subtree this
+/// server is in, reporting the outcome for each room.
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum RoomSpaceCommand {
+	/// - List the rooms of a space subtree known to this server
+	List {
+		space: OwnedRoomOrAliasId,
+	},
+
+	/// - Ban a user from every room of a space
+	Ban {
+		space: OwnedRoomOrAliasId,
+
+		user_id: String,
+
+		#[arg(long)]
+		reason: Option<String>,
+
+		/// Act as this local user instead of the most powerful local member
+		#[arg(long = "as")]
+		sender: Option<String>,
+	},
+
+	/// - Kick a user from every room of a space
+	Kick {
+		space: OwnedRoomOrAliasId,
+
+		user_id: String,
+
+		#[arg(long)]
+		reason: Option<String>,
+
+		/// Act as this local user instead of the most powerful local member
+		#[arg(long = "as")]
+		sender: Option<String>,
+	},
+
+	/// - Unban a user from every room of a space
+	Unban {
+		space: OwnedRoomOrAliasId,
+
+		user_id: String,
+
+		#[arg(long)]
+		reason: Option<String>,
+
+		/// Act as this local user instead of the most powerful local member
+		#[arg(long = "as")]
+		sender: Option<String>,
+	},
+
+	/// - Set a user's power level in every room of a space
+	SetPowerLevel {
+		space: OwnedRoomOrAliasId,
+
+		user_id: String,
+
+		#[arg(allow_negative_numbers = true)]
+		level: Int,
+
+		/// Act as this local user instead of the most powerful local member
+		#[arg(long = "as")]
+		sender: Option<String>,
+	},
+
+	/// - Add a server to the server ACL deny list of every room of a space
+	DenyServer {
+		space: OwnedRoomOrAliasId,
+
+		server_name: OwnedServerName,
+
+		/// Act as this local user instead of the most powerful local member
+		#[arg(long = "as")]
+		sender: Option<String>,
+	},
+
+	/// - Remove a server from the server ACL deny list of every room of a space
+	UndenyServer {
+		space: OwnedRoomOrAliasId,
+
+		server_name: OwnedServerName,
+
+		/// Act as this local user instead of the most powerful local member
+		#[arg(long = "as")]
+		sender: Option<String>,
+	},
+
+	/// - Join local users to every joinable room of a space they are not in
+	ForceJoin {
+		space: OwnedRoomOrAliasId,
+
+		user_ids: Vec<String>,
+	},
+}
+
+#[admin_command]
+async fn list(&self, space: OwnedRoomOrAliasId) -> Result {
+	let space_id = self.services.alias.resolve(&space).await?;
+	let rooms = self.services.spaces.space_rooms(&space_id).await;
+
+	let mut out = format!("{} rooms in {space_id}:\n", rooms.len());
+	for (room_id, _) in rooms {
+		let name = self
+			.services
+			.state_accessor
+			.get_name(&room_id)
+			.await
+			.unwrap_or_default();
+
+		writeln!(out, "- {room_id}\t{name}")?;
+	}
+
+	self.write_str(&out).await
+}
+
+#[admin_command]
+async fn ban(
+	&self,
+	space: OwnedRoomOrAliasId,
+	user_id: String,
+	reason: Option<String>,
+	sender: Option<String>,
+) -> Result {
+	let user_id = parse_user_id(self.services, &user_id)?;
+	self.apply_to_space(space, SpaceAction::Ban { user_id, reason }, sender)
+		.await
+}
+
+#[admin_command]
+async fn kick(
+	&self,
+	space: OwnedRoomOrAliasId,
+	user_id: String,
+	reason: Option<String>,
+	sender: Option<String>,
+) -> Result {
+	let user_id = parse_user_id(self.services, &user_id)?;
+	self.apply_to_space(space, SpaceAction::Kick { user_id, reason }, sender)
+		.await
+}
+
+#[admin_command]
+async fn unban(
+	&self,
+	space: OwnedRoomOrAliasId,
+	user_id: String,
+	reason: Option<String>,
+	sender: Option<String>,
+) -> Result {
+	let user_id = parse_user_id(self.services, &user_id)?;
+	self.apply_to_space(space, SpaceAction::Unban { user_id, reason }, sender)
+		.await
+}
+
+#[admin_command]
+async fn set_power_level(
+	&self,
+	space: OwnedRoomOrAliasId,
+	user_id: String,
+	level: Int,
+	sender: Option<String>,
+) -> Result {
+	let user_id = parse_user_id(self.services, &user_id)?;
+	self.apply_to_space(space, SpaceAction::SetPowerLevel { user_id, level }, sender)
+		.await
+}
+
+#[admin_command]
+async fn deny_server(
+	&self,
+	space: OwnedRoomOrAliasId,
+	server_name: OwnedServerName,
+	sender: Option<String>,
+) -> Result {
+	self.apply_to_space(space, SpaceAction::DenyServer { server_name }, sender)
+		.await
+}
+
+#[admin_command]
+async fn undeny_server(
+	&self,
+	space: OwnedRoomOrAliasId,
+	server_name: OwnedServerName,
+	sender: Option<String>,
+) -> Result {
+	self.apply_to_space(space, SpaceAction::UndenyServer { server_name }, sender)
+		.await
+}
+
+#[admin_command]
+async fn force_join(&self, space: OwnedRoomOrAliasId, user_ids: Vec<String>) -> Result {
+	for user_id in user_ids {
+		let user_id = parse_local_user_id(self.services, &user_id)?;
+		writeln!(self, "{user_id}:").await?;
+		self.apply_to_space(space.clone(), SpaceAction::ForceJoin { user_id }, None)
+			.await?;
+	}
+
+	Ok(())
+}
+
+#[admin_command]
+async fn apply_to_space(
+	&self,
+	space: OwnedRoomOrAliasId,
+	action: SpaceAction,
+	sender: Option<String>,
+) -> Result {
+	let sender = sender
+		.as_deref()
+		.map(|sender| parse_local_user_id(self.services, sender))
+		.transpose()?;
+
+	let space_id = self.services.alias.resolve(&space).await?;
+	let results = self
+		.services
+		.spaces
+		.apply_to_space(&space_id, &action, sender.as_deref())
+		.await?;
+
+	let failed = results
+		.iter()
+		.filter(|(_, result)| result.is_err())
+		.count();
+
+	let mut out = format!(
+		"Applied to {} of {} rooms in {space_id}:\n",
+		results.len().saturating_sub(failed),
+		results.len()
+	);
+
+	for (room_id, result) in results {
+		match result {
+			| Ok(()) => writeln!(out, "- {room_id}: done")?,
+			| Err(e) => writeln!(out, "- {room_id}: {e}")?,
+		}
+	}
+
+	self.write_str(&out).await
+}
@@ -1,13 +1,17 @@
-use std::{fmt::Write, path::PathBuf, sync::Arc};
+use std::{fmt::Write, path::PathBuf, sync::Arc, time::Duration};
 
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
 use tuwunel_core::{
-	Err, Result, info,
-	utils::{stream::IterStream, time},
+	Err, Result, err, info,
+	utils::{
+		stream::{IterStream, ReadyExt},
+		time::{self, duration_since_epoch, parse_timepoint_ago, timepoint_from_epoch},
+	},
 	warn,
 };
+use tuwunel_service::admin::AuditRecord;
 
-use crate::admin_command;
+use crate::{admin_command, utils::parse_user_id};
 
 #[admin_command]
 pub async fn uptime(&self) -> Result {
@@ -84,6 +88,55 @@ pub async fn clear_caches(&self) -> Result {
 	self.write_str("Done.").await
 }
 
+#[admin_command]
+pub async fn signing_keys(&self) -> Result {
+	let server_keys = &self.services.server_keys;
+	let active_key_id = server_keys.active_key_id();
+	writeln!(self, "Active key: {active_key_id}").await?;
+
+	if let Some(key_id) = server_keys.pending_key_id().await {
+		writeln!(self, "Replaced on restart by: {key_id}").await?;
+	}
+
+	let old_verify_keys = server_keys.old_verify_keys().await;
+	if old_verify_keys.is_empty() {
+		return Ok(());
+	}
+
+	let mut out = String::from("\nOld keys:\n");
+	for (key_id, old) in old_verify_keys {
+		let expired = old
+			.expired_ts
+			.to_system_time()
+			.map(|expired| time::format(expired, "%+"))
+			.unwrap_or_default();
+
+		writeln!(out, "- {key_id} (expired {expired})")?;
+	}
+
+	self.write_str(&out).await
+}
+
+#[admin_command]
+pub async fn rotate_signing_key(&self, from_file: Option<PathBuf>) -> Result {
+	let server_keys = &self.services.server_keys;
+	let key_id = if let Some(path) = from_file {
+		let key = tokio::fs::read_to_string(&path)
+			.await
+			.map_err(|e| err!("Failed to read signing key file {path:?}: {e}"))?;
+
+		server_keys.import_keypair(&key).await?
+	} else {
+		server_keys.rotate_keypair().await?
+	};
+
+	let active_key_id = server_keys.active_key_id();
+	self.write_str(&format!(
+		"Signing key {key_id} replaces {active_key_id} when the server is restarted."
+	))
+	.await
+}
+
 #[admin_command]
 pub async fn list_backups(&self) -> Result {
 	self.services
@@ -113,6 +166,123 @@ pub async fn backup_database(&self) -> Result {
 		.await
 }
 
+#[admin_command]
+pub async fn verify_backup(&self, backup_id: Option<u32>) -> Result {
+	let db = Arc::clone(&self.services.db);
+	let results = self
+		.services
+		.server
+		.runtime()
+		.spawn_blocking(move || db.db.backup_verify(backup_id))
+		.await??;
+
+	results
+		.into_iter()
+		.try_stream()
+		.try_for_each(|result| writeln!(self, "{result}"))
+		.await
+}
+
+#[admin_command]
+pub async fn purge_backups(&self, duration: String) -> Result {
+	let timepoint = parse_timepoint_ago(&duration)?;
+	let db = Arc::clone(&self.services.db);
+	let purged = self
+		.services
+		.server
+		.runtime()
+		.spawn_blocking(move || db.db.backup_purge_before(timepoint))
+		.await??;
+
+	let count = self.services.db.db.backup_count()?;
+	self.write_str(&format!("Purged {purged} backups. Currently have {count} backups."))
+		.await
+}
+
+#[admin_command]
+pub async fn audit_log(
+	&self,
+	sender: Option<String>,
+	origin: Option<String>,
+	contains: Option<String>,
+	failed: bool,
+	since: Option<String>,
+	limit: usize,
+) -> Result {
+	let sender = sender
+		.as_deref()
+		.map(|sender| parse_user_id(self.services, sender))
+		.transpose()?;
+
+	let since: u64 = since
+		.as_deref()
+		.map(parse_timepoint_ago)
+		.transpose()?
+		.map(duration_since_epoch)
+		.map(|since| since.as_millis().try_into())
+		.transpose()?
+		.unwrap_or(0);
+
+	let records: Vec<AuditRecord> = self
+		.services
+		.admin
+		.audit_log()
+		.ready_take_while(|record| record.ts >= since)
+		.ready_filter(|record| {
+			sender
+				.as_ref()
+				.is_none_or(|sender| record.sender.as_ref() == Some(sender))
+				&& origin
+					.as_deref()
+					.is_none_or(|origin| record.origin.to_string() == origin)
+				&& contains
+					.as_deref()
+					.is_none_or(|contains| record.command.contains(contains))
+				&& (!failed || !record.success)
+		})
+		.take(limit)
+		.collect()
+		.await;
+
+	let mut out = String::new();
+	writeln!(out, "| Time | Origin | Sender | Duration | Result | Command |")?;
+	writeln!(out, "| --- | --- | --- | --- | --- | --- |")?;
+
+	for record in &records {
+		let time = timepoint_from_epoch(Duration::from_millis(record.ts))
+			.map(|ts| time::format(ts, "%Y-%m-%d %H:%M:%S"))
+			.unwrap_or_default();
+
+		let sender = record
+			.sender
+			.as_ref()
+			.map(ToString::to_string)
+			.unwrap_or_default();
+
+		let result = if record.success { "ok" } else { "failed" };
+
+		writeln!(
+			out,
+			"| {time} | {} | {sender} | {}ms | {result} | `{}` |",
+			record.origin,
+			record.duration,
+			record
+				.command
+				.lines()
+				.map(str::trim)
+				.filter(|line| !line.is_empty())
+				.collect::<Vec<_>>()
+				.join(" ↵ ")
+				.replace('|', "\\|")
+				.replace('`', "'"),
+		)?;
+	}
+
+	writeln!(out, "\n{} records.", records.len())?;
+
+	self.write_data(&records, &out).await
+}
+
 #[admin_command]
 pub async fn admin_notice(&self, message: Vec<String>) -> Result {
 	let message = message.join(" ");
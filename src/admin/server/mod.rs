@@ -1,10 +1,13 @@
 mod commands;
+mod notices;
+mod roles;
 
 use std::path::PathBuf;
 
 use clap::Subcommand;
 use tuwunel_core::Result;
 
+use self::{notices::ServerNoticesCommand, roles::ServerRolesCommand};
 use crate::admin_command_dispatch;
 
 #[admin_command_dispatch]
@@ -46,11 +49,70 @@ pub enum ServerCommand {
 	/// - List database backups
 	ListBackups,
 
+	/// - Verify the integrity of a database backup, or all backups if no backup
+	///   number is given
+	VerifyBackup {
+		backup_id: Option<u32>,
+	},
+
+	/// - Delete database backups created more than [duration] ago. The most
+	///   recent backup is always kept.
+	PurgeBackups {
+		/// - The relative time (e.g. 30s, 5m, 7d)
+		duration: String,
+	},
+
+	/// - Show the active signing key and the keys retired by rotations
+	SigningKeys,
+
+	/// - Generate a signing key which replaces the active key at the next
+	///   restart. The active key is then published as an old key.
+	RotateSigningKey {
+		/// Import the key of a Synapse signing key file (`ed25519 <version>
+		/// <base64 seed>`) on the server instead of generating one
+		#[arg(long)]
+		from_file: Option<PathBuf>,
+	},
+
+	/// - Search the log of executed admin commands, most recent first
+	AuditLog {
+		/// Only commands issued by this user
+		#[arg(long)]
+		sender: Option<String>,
+
+		/// Only commands issued from: room, console, startup, signal or api
+		#[arg(long)]
+		origin: Option<String>,
+
+		/// Only commands containing this text
+		#[arg(long)]
+		contains: Option<String>,
+
+		/// Only commands which failed
+		#[arg(long)]
+		failed: bool,
+
+		/// Only commands issued within the relative time (e.g. 30m, 7d)
+		#[arg(long)]
+		since: Option<String>,
+
+		#[arg(short, long, default_value_t = 50)]
+		limit: usize,
+	},
+
 	/// - Send a message to the admin room.
 	AdminNotice {
 		message: Vec<String>,
 	},
 
+	#[command(subcommand)]
+	/// - Send server notices to local users
+	Notices(ServerNoticesCommand),
+
+	#[command(subcommand)]
+	/// - Grant and revoke the admin roles delegating commands to users
+	Roles(ServerRolesCommand),
+
 	/// - Hot-reload the server
 	#[clap(alias = "reload")]
 	ReloadMods,
@@ -0,0 +1,184 @@
+use clap::Subcommand;
+use futures::StreamExt;
+use ruma::{OwnedEventId, OwnedUserId, UserId};
+use tuwunel_core::{Err, Result, implement};
+
+use crate::{Context, admin_command, admin_command_dispatch, utils::parse_active_local_user_id};
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum ServerNoticesCommand {
+	/// - Send a server notice to a local user
+	Send {
+		user_id: String,
+
+		/// Send an `m.server_notice.usage_limit_reached` notice which clients
+		/// display as a blocking banner
+		#[arg(long)]
+		usage_limit: bool,
+
+		/// Contact URI included with a usage limit notice
+		#[arg(long)]
+		admin_contact: Option<String>,
+
+		message: Vec<String>,
+	},
+
+	/// - Send a server notice to a newline delimited codeblock of local users
+	///   following the command
+	SendList {
+		#[arg(long)]
+		usage_limit: bool,
+
+		#[arg(long)]
+		admin_contact: Option<String>,
+
+		message: Vec<String>,
+	},
+
+	/// - Send a server notice to every active local user
+	SendAll {
+		#[arg(long)]
+		usage_limit: bool,
+
+		#[arg(long)]
+		admin_contact: Option<String>,
+
+		message: Vec<String>,
+	},
+
+	/// - Remove the usage limit banner from a user's server notices room
+	ClearUsageLimit {
+		user_id: String,
+	},
+}
+
+#[admin_command]
+async fn send(
+	&self,
+	user_id: String,
+	usage_limit: bool,
+	admin_contact: Option<String>,
+	message: Vec<String>,
+) -> Result {
+	let message = notice_body(&message)?;
+	let user_id = parse_active_local_user_id(self.services, &user_id).await?;
+	let event_id = self
+		.notify(&user_id, &message, usage_limit, admin_contact)
+		.await?;
+
+	self.write_str(&format!("Sent server notice {event_id} to {user_id}."))
+		.await
+}
+
+#[admin_command]
+async fn send_list(
+	&self,
+	usage_limit: bool,
+	admin_contact: Option<String>,
+	message: Vec<String>,
+) -> Result {
+	if self.body.len() < 2
+		|| !self.body[0].trim().starts_with("```")
+		|| self.body.last().unwrap_or(&"").trim() != "```"
+	{
+		return Err!("Expected code block in command body. Add --help for details.");
+	}
+
+	let message = notice_body(&message)?;
+	let mut user_ids: Vec<OwnedUserId> = Vec::new();
+	for username in &self.body[1..self.body.len().saturating_sub(1)] {
+		match parse_active_local_user_id(self.services, username).await {
+			| Ok(user_id) => user_ids.push(user_id),
+			| Err(e) => writeln!(self, "Skipping {username}: {e}").await?,
+		}
+	}
+
+	self.notify_all(user_ids, &message, usage_limit, admin_contact)
+		.await
+}
+
+#[admin_command]
+async fn send_all(
+	&self,
+	usage_limit: bool,
+	admin_contact: Option<String>,
+	message: Vec<String>,
+) -> Result {
+	let message = notice_body(&message)?;
+	let user_ids: Vec<OwnedUserId> = self
+		.services
+		.users
+		.list_local_users()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	self.notify_all(user_ids, &message, usage_limit, admin_contact)
+		.await
+}
+
+#[admin_command]
+async fn clear_usage_limit(&self, user_id: String) -> Result {
+	let user_id = parse_active_local_user_id(self.services, &user_id).await?;
+	let cleared = self
+		.services
+		.server_notices
+		.clear_usage_limit(&user_id)
+		.await?;
+
+	if !cleared {
+		return Err!("{user_id} has no usage limit notice.");
+	}
+
+	self.write_str(&format!("Cleared usage limit notice for {user_id}."))
+		.await
+}
+
+#[implement(Context, params = "<'_>")]
+async fn notify_all(
+	&self,
+	user_ids: Vec<OwnedUserId>,
+	message: &str,
+	usage_limit: bool,
+	admin_contact: Option<String>,
+) -> Result {
+	let mut sent: usize = 0;
+	for user_id in &user_ids {
+		match self
+			.notify(user_id, message, usage_limit, admin_contact.clone())
+			.await
+		{
+			| Ok(_) => sent = sent.saturating_add(1),
+			| Err(e) => writeln!(self, "Failed to notify {user_id}: {e}").await?,
+		}
+	}
+
+	writeln!(self, "Sent server notice to {sent} of {} users.", user_ids.len()).await
+}
+
+#[implement(Context, params = "<'_>")]
+async fn notify(
+	&self,
+	user_id: &UserId,
+	message: &str,
+	usage_limit: bool,
+	admin_contact: Option<String>,
+) -> Result<OwnedEventId> {
+	let server_notices = &self.services.server_notices;
+	if usage_limit {
+		server_notices
+			.usage_limit_reached(user_id, message, admin_contact)
+			.await
+	} else {
+		server_notices.send_text(user_id, message).await
+	}
+}
+
+fn notice_body(message: &[String]) -> Result<String> {
+	if message.is_empty() {
+		return Err!("A notice message is required.");
+	}
+
+	Ok(message.join(" "))
+}
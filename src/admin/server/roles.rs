@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use clap::Subcommand;
+use futures::StreamExt;
+use serde_json::json;
+use tuwunel_core::Result;
+use tuwunel_service::admin::AdminRole;
+
+use crate::{admin_command, admin_command_dispatch, utils::parse_local_user_id};
+
+/// Roles delegate groups of admin commands to members of the admin room:
+/// superadmin, moderator, media-admin and support. Members holding no role may
+/// run every command.
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum ServerRolesCommand {
+	/// - Grant an admin role to a local user
+	Grant {
+		user_id: String,
+
+		/// superadmin, moderator, media-admin or support
+		role: String,
+	},
+
+	/// - Revoke an admin role from a local user
+	Revoke {
+		user_id: String,
+
+		/// superadmin, moderator, media-admin or support
+		role: String,
+	},
+
+	/// - List the users holding admin roles
+	List,
+}
+
+#[admin_command]
+async fn grant(&self, user_id: String, role: String) -> Result {
+	let user_id = parse_local_user_id(self.services, &user_id)?;
+	let role: AdminRole = role.parse()?;
+
+	if !self
+		.services
+		.admin
+		.grant_role(&user_id, role)
+		.await
+	{
+		return self
+			.write_str(&format!("{user_id} already holds the {role} role."))
+			.await;
+	}
+
+	let mut out = format!("Granted the {role} role to {user_id}.\n");
+	if !self.services.admin.user_is_admin(&user_id).await {
+		writeln!(out, "The role applies once {user_id} joins the admin room.")?;
+	}
+
+	self.write_str(&out).await
+}
+
+#[admin_command]
+async fn revoke(&self, user_id: String, role: String) -> Result {
+	let user_id = parse_local_user_id(self.services, &user_id)?;
+	let role: AdminRole = role.parse()?;
+
+	if !self
+		.services
+		.admin
+		.revoke_role(&user_id, role)
+		.await
+	{
+		return self
+			.write_str(&format!("{user_id} does not hold the {role} role."))
+			.await;
+	}
+
+	let mut out = format!("Revoked the {role} role from {user_id}.\n");
+	if self
+		.services
+		.admin
+		.user_roles(&user_id)
+		.await
+		.is_empty()
+	{
+		writeln!(out, "{user_id} holds no roles and may run every command again.")?;
+	}
+
+	self.write_str(&out).await
+}
+
+#[admin_command]
+async fn list(&self) -> Result {
+	let holders: Vec<_> = self.services.admin.role_holders().collect().await;
+
+	let mut out = if holders.is_empty() {
+		"No roles are granted; every member of the admin room may run every command.\n".to_owned()
+	} else {
+		format!("{} users hold roles:\n", holders.len())
+	};
+
+	for (user_id, roles) in &holders {
+		let roles: Vec<_> = roles.iter().map(ToString::to_string).collect();
+		writeln!(out, "- {user_id}: {}", roles.join(", "))?;
+	}
+
+	let data: Vec<_> = holders
+		.iter()
+		.map(|(user_id, roles)| json!({ "user_id": user_id, "roles": roles }))
+		.collect();
+
+	self.write_data(&data, &out).await
+}
@@ -12,9 +12,9 @@
 fn get_help_inner(input: &str) {
 	use clap::Parser;
 
-	use crate::admin::AdminCommand;
+	use crate::admin::AdminArgs;
 
-	let Err(error) = AdminCommand::try_parse_from(["argv[0] doesn't matter", input]) else {
+	let Err(error) = AdminArgs::try_parse_from(["argv[0] doesn't matter", input]) else {
 		panic!("no error!");
 	};
 
@@ -24,3 +24,37 @@ fn get_help_inner(input: &str) {
 	assert!(error.contains("Commands:"));
 	assert!(error.contains("Options:"));
 }
+
+#[test]
+fn roles_reserve_escalating_commands() {
+	use clap::Parser;
+	use tuwunel_service::admin::AdminRole::{Moderator, Support};
+
+	use crate::admin::AdminArgs;
+
+	let roles = |line: &str| {
+		AdminArgs::try_parse_from(line.split_whitespace())
+			.expect("command parses")
+			.command
+			.roles()
+	};
+
+	for superadmin_only in [
+		"admin users force-join-room @a:example.com !r:example.com",
+		"admin users force-promote @a:example.com !r:example.com",
+		"admin users deactivate @a:example.com",
+		"admin users redact-event $e",
+		"admin rooms delete-room !r:example.com --block",
+		"admin rooms upgrade-all --below 10",
+		"admin rooms moderation ban-list-of-rooms",
+		"admin federation disable-room !r:example.com",
+	] {
+		assert!(roles(superadmin_only).is_empty(), "{superadmin_only}");
+	}
+
+	assert_eq!(roles("admin users reset-password a"), [Moderator, Support]);
+	assert_eq!(roles("admin rooms moderation ban-room !r:example.com"), [Moderator]);
+	assert_eq!(roles("admin rooms alias audit-canonical"), [Moderator]);
+	assert!(roles("admin rooms alias audit-canonical --fix").is_empty());
+	assert_eq!(roles("admin federation incoming-federation"), [Moderator]);
+}
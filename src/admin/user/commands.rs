@@ -42,7 +42,7 @@ pub async fn list_users(&self) -> Result {
 	plain_msg += users.join("\n").as_str();
 	plain_msg += "\n```";
 
-	self.write_str(&plain_msg).await
+	self.write_data(&users, &plain_msg).await
 }
 
 #[admin_command]
@@ -111,74 +111,18 @@ pub async fn create_user(&self, username: String, password: Option<String>) -> R
 		)
 		.await?;
 
-	if !self
+	for e in self
 		.services
-		.server
-		.config
-		.auto_join_rooms
-		.is_empty()
+		.membership
+		.auto_join(&user_id, &None)
+		.await
 	{
-		for room in &self.services.server.config.auto_join_rooms {
-			let Ok(room_id) = self.services.alias.resolve(room).await else {
-				error!(
-					%user_id,
-					"Failed to resolve room alias to room ID when attempting to auto join {room}, skipping"
-				);
-				continue;
-			};
-
-			if !self
-				.services
-				.state_cache
-				.server_in_room(self.services.globals.server_name(), &room_id)
-				.await
-			{
-				warn!(
-					"Skipping room {room} to automatically join as we have never joined before."
-				);
-				continue;
-			}
-
-			let state_lock = self.services.state.mutex.lock(&room_id).await;
-
-			if let Some(room_server_name) = room.server_name() {
-				match self
-					.services
-					.membership
-					.join(
-						&user_id,
-						&room_id,
-						Some("Automatically joining this room upon registration".to_owned()),
-						&[
-							self.services.globals.server_name().to_owned(),
-							room_server_name.to_owned(),
-						],
-						&None,
-						&state_lock,
-					)
-					.await
-				{
-					| Ok(_response) => {
-						info!("Automatically joined room {room} for user {user_id}");
-					},
-					| Err(e) => {
-						// don't return this error so we don't fail registrations
-						error!(
-							"Failed to automatically join room {room} for user {user_id}: {e}"
-						);
-						self.services
-							.admin
-							.send_text(&format!(
-								"Failed to automatically join room {room} for user {user_id}: \
-								 {e}"
-							))
-							.await;
-					},
-				}
-
-				drop(state_lock);
-			}
-		}
+		// don't return this error so we don't fail registrations
+		error!("{e}");
+		self.services
+			.admin
+			.send_text(&e.to_string())
+			.await;
 	}
 
 	// we dont add a device since we're not the user, just the creator
@@ -218,6 +162,15 @@ pub async fn deactivate(&self, no_leave_rooms: bool, user_id: String) -> Result
 		return Err!("Not allowed to deactivate the server service account.",);
 	}
 
+	if !self
+		.services
+		.admin
+		.may_manage(self.sender, &user_id)
+		.await
+	{
+		return Err!("Only superadmins may deactivate members of the admin room.");
+	}
+
 	deactivate_user(self.services, &user_id, no_leave_rooms).await?;
 
 	self.write_str(&format!("User {user_id} has been deactivated"))
@@ -235,6 +188,15 @@ pub async fn reset_password(&self, username: String, password: Option<String>) -
 		);
 	}
 
+	if !self
+		.services
+		.admin
+		.may_manage(self.sender, &user_id)
+		.await
+	{
+		return Err!("Only superadmins may reset the password of members of the admin room.");
+	}
+
 	let new_password = password.unwrap_or_else(|| utils::random_string(AUTO_GEN_PASSWORD_LENGTH));
 
 	match self
@@ -625,6 +587,12 @@ pub async fn force_join_room(
 		"Parsed user_id must be a local user"
 	);
 
+	if self.services.admin.is_admin_room(&room_id).await
+		&& !self.services.admin.may_run(self.sender, &[]).await
+	{
+		return Err!("Only superadmins may join users to the admin room.");
+	}
+
 	let state_lock = self.services.state.mutex.lock(&room_id).await;
 
 	self.services
@@ -652,6 +620,15 @@ pub async fn force_leave_room(
 		"Parsed user_id must be a local user"
 	);
 
+	if !self
+		.services
+		.admin
+		.may_manage(self.sender, &user_id)
+		.await
+	{
+		return Err!("Only superadmins may force out members of the admin room.");
+	}
+
 	if !self
 		.services
 		.state_cache
@@ -685,6 +662,15 @@ pub async fn force_demote(&self, user_id: String, room_id: OwnedRoomOrAliasId) -
 		"Parsed user_id must be a local user"
 	);
 
+	if !self
+		.services
+		.admin
+		.may_manage(self.sender, &user_id)
+		.await
+	{
+		return Err!("Only superadmins may demote members of the admin room.");
+	}
+
 	let state_lock = self.services.state.mutex.lock(&room_id).await;
 
 	let room_power_levels: Option<RoomPowerLevels> = self
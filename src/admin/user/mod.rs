@@ -3,6 +3,7 @@
 use clap::Subcommand;
 use ruma::{OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId};
 use tuwunel_core::Result;
+use tuwunel_service::admin::AdminRole;
 
 use crate::admin_command_dispatch;
 
@@ -160,3 +161,35 @@ pub enum UserCommand {
 		yes_i_want_to_do_this: bool,
 	},
 }
+
+impl UserCommand {
+	/// Roles besides superadmin permitted to run the command. Support may
+	/// help with accounts but not deactivate them; commands deactivating
+	/// accounts, joining users to rooms, raising power levels, redacting or
+	/// affecting every user are reserved for superadmins.
+	pub(crate) fn roles(&self) -> &'static [AdminRole] {
+		use AdminRole::{Moderator, Support};
+
+		match self {
+			| Self::CreateUser { .. }
+			| Self::ResetPassword { .. }
+			| Self::ListUsers
+			| Self::ListJoinedRooms { .. }
+			| Self::GetRoomTags { .. } => &[Moderator, Support],
+
+			| Self::ForceLeaveRoom { .. }
+			| Self::ForceDemote { .. }
+			| Self::PutRoomTag { .. }
+			| Self::DeleteRoomTag { .. } => &[Moderator],
+
+			| Self::Deactivate { .. }
+			| Self::DeactivateAll { .. }
+			| Self::ForceJoinRoom { .. }
+			| Self::ForcePromote { .. }
+			| Self::MakeUserAdmin { .. }
+			| Self::RedactEvent { .. }
+			| Self::ForceJoinListOfLocalUsers { .. }
+			| Self::ForceJoinAllLocalUsers { .. } => &[],
+		}
+	}
+}
@@ -0,0 +1,55 @@
+use axum::extract::State;
+use serde_json::{Value as JsonValue, json};
+use tuwunel_core::{Err, Result};
+use tuwunel_service::admin::{CommandInput, CommandOrigin, JSON_MSGTYPE, OutputFormat};
+
+use crate::{
+	Ruma,
+	client::endpoints::admin_command::v1::{Request, Response},
+};
+
+/// # `POST /_tuwunel/admin/v1/command`
+///
+/// Runs an admin command as the user and responds with its outcome in the
+/// JSON format: `success`, the typed `data` of commands providing it, the
+/// rendered `output` and the `error` of a failed command.
+///
+/// - Only members of the admin room may run commands, subject to their roles
+/// - Commands which could not be parsed respond with the usage in `error`
+/// - Only served by listeners listing the `admin` route group
+pub async fn admin_command_route(
+	State(services): State<crate::State>,
+	body: Ruma<Request>,
+) -> Result<Response> {
+	let user_id = body.sender_user();
+	if !services.admin.user_is_admin(user_id).await {
+		return Err!(Request(Forbidden("Only server admins may run admin commands.")));
+	}
+
+	if body.command.trim().is_empty() {
+		return Err!(Request(InvalidParam("The command is empty.")));
+	}
+
+	let input = CommandInput {
+		command: body.command.clone(),
+		sender: Some(user_id.to_owned()),
+		origin: CommandOrigin::Api,
+		format: OutputFormat::Json,
+		..Default::default()
+	};
+
+	let (success, output) = match services.admin.process_command(input).await {
+		| Ok(None) => return Ok(Response { outcome: json!({ "success": true }) }),
+		| Ok(Some(output)) => (true, output),
+		| Err(output) => (false, output),
+	};
+
+	// Commands failing to parse or refused by the roles respond in plain text.
+	let outcome = match output.msgtype.msgtype() {
+		| JSON_MSGTYPE => JsonValue::Object(output.msgtype.data().into_owned()),
+		| _ if success => json!({ "success": true, "output": output.body() }),
+		| _ => json!({ "success": false, "error": output.body() }),
+	};
+
+	Ok(Response { outcome })
+}
@@ -5,7 +5,7 @@
 	OwnedServerName, RoomAliasId, RoomId,
 	api::client::alias::{create_alias, delete_alias, get_alias},
 };
-use tuwunel_core::{Err, Result, debug};
+use tuwunel_core::{Err, Result, debug, err, result::LogErr};
 use tuwunel_service::Services;
 
 use crate::Ruma;
@@ -53,7 +53,8 @@ pub async fn create_alias_route(
 ///
 /// Deletes a room alias from this server.
 ///
-/// - TODO: Update canonical alias event
+/// - Removes the alias from the room's canonical alias event when a local user
+///   is permitted to update it
 pub async fn delete_alias_route(
 	State(services): State<crate::State>,
 	body: Ruma<delete_alias::v3::Request>,
@@ -64,12 +65,23 @@ pub async fn delete_alias_route(
 		.appservice_checks(&body.room_alias, &body.appservice_info)
 		.await?;
 
+	let room_id = services
+		.alias
+		.resolve_local_alias(&body.room_alias)
+		.await
+		.map_err(|_| err!(Request(NotFound("Alias does not exist or is invalid."))))?;
+
 	services
 		.alias
 		.remove_alias(&body.room_alias, sender_user)
 		.await?;
 
-	// TODO: update alt_aliases?
+	services
+		.alias
+		.remove_canonical_alias(&room_id, &body.room_alias, Some(sender_user))
+		.await
+		.log_err()
+		.ok();
 
 	Ok(delete_alias::v3::Response::new())
 }
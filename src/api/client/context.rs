@@ -1,7 +1,7 @@
 use axum::extract::State;
 use futures::{
 	FutureExt, StreamExt, TryFutureExt, TryStreamExt,
-	future::{OptionFuture, join, join3, try_join3},
+	future::{OptionFuture, join, join3, try_join4},
 };
 use ruma::{OwnedEventId, UserId, api::client::context::get_context, events::StateEventType};
 use tuwunel_core::{
@@ -16,7 +16,10 @@
 
 use crate::{
 	Ruma,
-	client::message::{event_filter, ignored_filter, lazy_loading_witness, visibility_filter},
+	client::message::{
+		bundle_aggregations, event_filter, ignored_filter, lazy_loading_witness,
+		visibility_filter,
+	},
 };
 
 const LIMIT_MAX: usize = 100;
@@ -26,8 +29,8 @@
 ///
 /// Allows loading room history around an event.
 ///
-/// - Only works if the user is joined (TODO: always allow, but only show events
-///   if the user was joined, depending on history_visibility)
+/// - Events are shown depending on `history_visibility`; former members can
+///   read the room up to their leave event
 pub async fn get_context_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_context::v3::Request>,
@@ -64,7 +67,13 @@ pub async fn get_context_route(
 		.user_can_see_event(sender_user, room_id, event_id)
 		.map(Ok);
 
-	let (base_id, base_pdu, visible) = try_join3(base_id, base_pdu, visible).await?;
+	let left_count = services
+		.state_accessor
+		.user_left_count(sender_user, room_id)
+		.map(Ok);
+
+	let (base_id, base_pdu, visible, left_count) =
+		try_join4(base_id, base_pdu, visible, left_count).await?;
 
 	if base_pdu.room_id != *room_id || base_pdu.event_id != *event_id {
 		return Err!(Request(NotFound("Base event not found.")));
@@ -81,7 +90,9 @@ pub async fn get_context_route(
 
 	let base_count = base_id.pdu_count();
 
-	let base_event = ignored_filter(&services, (base_count, base_pdu), sender_user);
+	let base_event = ignored_filter(&services, (base_count, base_pdu), sender_user)
+		.map(|item| item.map(|item| bundle_aggregations(&services, item, sender_user)))
+		.then(OptionFuture::from);
 
 	let events_before = services
 		.timeline
@@ -91,16 +102,19 @@ pub async fn get_context_route(
 		.wide_filter_map(|item| ignored_filter(&services, item, sender_user))
 		.wide_filter_map(|item| visibility_filter(&services, item, sender_user))
 		.take(limit / 2)
+		.wide_then(|item| bundle_aggregations(&services, item, sender_user))
 		.collect();
 
 	let events_after = services
 		.timeline
 		.pdus(Some(sender_user), room_id, Some(base_count))
 		.ignore_err()
+		.ready_take_while(|(count, _)| left_count.is_none_or(|left_count| *count <= left_count))
 		.ready_filter_map(|item| event_filter(item, filter))
 		.wide_filter_map(|item| ignored_filter(&services, item, sender_user))
 		.wide_filter_map(|item| visibility_filter(&services, item, sender_user))
 		.take(limit / 2)
+		.wide_then(|item| bundle_aggregations(&services, item, sender_user))
 		.collect();
 
 	let (base_event, events_before, events_after): (_, Vec<_>, Vec<_>) =
@@ -0,0 +1,140 @@
+use axum::extract::State;
+use futures::StreamExt;
+use ruma::api::client::dehydrated_device::{
+	delete_dehydrated_device, get_dehydrated_device, get_events, put_dehydrated_device,
+};
+use tuwunel_core::{Err, Result, debug_warn, err};
+
+use crate::Ruma;
+
+/// # `PUT /_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device`
+///
+/// Stores a dehydrated device for the sender user along with its keys.
+///
+/// - Replaces any previous dehydrated device, discarding its queued to-device
+///   events
+pub async fn put_dehydrated_device_route(
+	State(services): State<crate::State>,
+	body: Ruma<put_dehydrated_device::unstable::Request>,
+) -> Result<put_dehydrated_device::unstable::Response> {
+	let sender_user = body.sender_user();
+	let device_id = &body.device_id;
+
+	let device_keys = body.device_keys.deserialize().map_err(|e| {
+		err!(Request(BadJson(debug_warn!("Invalid dehydrated device keys: {e}"))))
+	})?;
+
+	if device_keys.user_id != sender_user || device_keys.device_id != *device_id {
+		return Err!(Request(InvalidParam(
+			"Device keys do not belong to the dehydrated device."
+		)));
+	}
+
+	services
+		.users
+		.set_dehydrated_device(
+			sender_user,
+			device_id,
+			body.device_data.clone(),
+			body.initial_device_display_name.clone(),
+		)
+		.await?;
+
+	for (key_id, one_time_key) in &body.one_time_keys {
+		services
+			.users
+			.add_one_time_key(sender_user, device_id, key_id, one_time_key)
+			.await?;
+	}
+
+	for (key_id, fallback_key) in &body.fallback_keys {
+		services
+			.users
+			.add_fallback_key(sender_user, device_id, key_id, fallback_key)
+			.await?;
+	}
+
+	services
+		.users
+		.add_device_keys(sender_user, device_id, &body.device_keys)
+		.await;
+
+	Ok(put_dehydrated_device::unstable::Response { device_id: device_id.clone() })
+}
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device`
+///
+/// Returns the dehydrated device of the sender user for rehydration.
+pub async fn get_dehydrated_device_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_dehydrated_device::unstable::Request>,
+) -> Result<get_dehydrated_device::unstable::Response> {
+	let (device_id, device_data) = services
+		.users
+		.get_dehydrated_device(body.sender_user())
+		.await?;
+
+	Ok(get_dehydrated_device::unstable::Response { device_id, device_data })
+}
+
+/// # `DELETE /_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device`
+///
+/// Removes the dehydrated device of the sender user.
+pub async fn delete_dehydrated_device_route(
+	State(services): State<crate::State>,
+	body: Ruma<delete_dehydrated_device::unstable::Request>,
+) -> Result<delete_dehydrated_device::unstable::Response> {
+	let device_id = services
+		.users
+		.remove_dehydrated_device(body.sender_user())
+		.await?;
+
+	Ok(delete_dehydrated_device::unstable::Response { device_id })
+}
+
+/// # `POST /_matrix/client/unstable/org.matrix.msc3814.v1/dehydrated_device/{deviceId}/events`
+///
+/// Returns the to-device events queued for the dehydrated device.
+///
+/// - Events before `next_batch` were received by the client and are removed
+pub async fn get_dehydrated_events_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_events::unstable::Request>,
+) -> Result<get_events::unstable::Response> {
+	let sender_user = body.sender_user();
+	let device_id = &body.device_id;
+
+	if !services
+		.users
+		.is_dehydrated_device(sender_user, device_id)
+		.await
+	{
+		return Err!(Request(Forbidden("Device {device_id} is not a dehydrated device.")));
+	}
+
+	let since: Option<u64> = body
+		.next_batch
+		.as_deref()
+		.map(str::parse)
+		.transpose()
+		.map_err(|_| err!(Request(InvalidParam("Invalid next_batch token."))))?;
+
+	if let Some(since) = since {
+		services
+			.users
+			.remove_to_device_events(sender_user, device_id, since)
+			.await;
+	}
+
+	let next_batch = services.globals.current_count();
+	let events: Vec<_> = services
+		.users
+		.get_to_device_events(sender_user, device_id, since, Some(next_batch))
+		.collect()
+		.await;
+
+	Ok(get_events::unstable::Response {
+		next_batch: Some(next_batch.to_string()),
+		events,
+	})
+}
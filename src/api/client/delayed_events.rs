@@ -0,0 +1,63 @@
+use axum::{
+	Json,
+	extract::State,
+	response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde_json::json;
+use tuwunel_core::{Err, Result};
+
+use crate::{
+	Ruma,
+	client::endpoints::{
+		get_delayed_events,
+		update_delayed_event::{self, unstable::UpdateAction},
+	},
+};
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc4140/delayed_events`
+///
+/// Lists the delayed events scheduled by the user.
+pub async fn get_delayed_events_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_delayed_events::unstable::Request>,
+) -> Result<get_delayed_events::unstable::Response> {
+	let delayed_events = services
+		.delayed_events
+		.delayed_events_for(body.sender_user())
+		.collect()
+		.await;
+
+	Ok(get_delayed_events::unstable::Response { delayed_events })
+}
+
+/// # `POST /_matrix/client/unstable/org.matrix.msc4140/delayed_events/{delayId}`
+///
+/// Cancels a delayed event, restarts its delay or sends it immediately.
+///
+/// - Only the sender of the delayed event may manage it
+pub async fn update_delayed_event_route(
+	State(services): State<crate::State>,
+	body: Ruma<update_delayed_event::unstable::Request>,
+) -> Result<update_delayed_event::unstable::Response> {
+	let delayed_events = &services.delayed_events;
+	let event = delayed_events.get(&body.delay_id).await?;
+	if event.sender != body.sender_user() {
+		return Err!(Request(NotFound("Delayed event not found.")));
+	}
+
+	match body.action {
+		| UpdateAction::Cancel => delayed_events.cancel(&body.delay_id).await?,
+		| UpdateAction::Restart => delayed_events.restart(&body.delay_id).await?,
+		| UpdateAction::Send => {
+			delayed_events.send(&body.delay_id).await?;
+		},
+	}
+
+	Ok(update_delayed_event::unstable::Response {})
+}
+
+/// Response of the event sending endpoints when the event was delayed.
+pub(crate) fn delayed_event_response(delay_id: &str) -> Response {
+	Json(json!({ "delay_id": delay_id })).into_response()
+}
@@ -0,0 +1,231 @@
+//! Request and response types of the client endpoints ruma does not define.
+//! They are laid out as in ruma so their routes authenticate through
+//! [`crate::Ruma`] like any other.
+
+pub mod get_read_by {
+	//! `GET /_matrix/client/unstable/net.tuwunel/rooms/{roomId}/read_by`
+	//!
+	//! Users who have read up to each event in a room.
+
+	pub mod unstable {
+		use std::collections::BTreeMap;
+
+		use ruma::{
+			OwnedEventId, OwnedRoomId, OwnedUserId,
+			api::{Metadata, client::Error, metadata, request, response},
+			events::receipt::Receipt,
+		};
+
+		const METADATA: Metadata = metadata! {
+			method: GET,
+			rate_limited: false,
+			authentication: AccessToken,
+			history: {
+				unstable => "/_matrix/client/unstable/net.tuwunel/rooms/{room_id}/read_by",
+			}
+		};
+
+		#[request(error = Error)]
+		pub struct Request {
+			#[ruma_api(path)]
+			pub room_id: OwnedRoomId,
+
+			/// Only return the readers of this event.
+			#[ruma_api(query)]
+			#[serde(default, skip_serializing_if = "Option::is_none")]
+			pub event_id: Option<OwnedEventId>,
+		}
+
+		#[response(error = Error)]
+		pub struct Response {
+			/// Public read receipts keyed by the event they point at.
+			pub read_by: BTreeMap<OwnedEventId, BTreeMap<OwnedUserId, Receipt>>,
+		}
+	}
+}
+
+pub mod get_rtc_transports {
+	//! `GET /_matrix/client/unstable/org.matrix.msc4143/rtc/transports`
+	//!
+	//! MatrixRTC foci available to clients of the server (MSC4143).
+
+	pub mod unstable {
+		use ruma::api::{
+			Metadata,
+			client::{Error, discovery::discover_homeserver::RtcFocusInfo},
+			metadata, request, response,
+		};
+
+		const METADATA: Metadata = metadata! {
+			method: GET,
+			rate_limited: false,
+			authentication: AccessToken,
+			history: {
+				unstable => "/_matrix/client/unstable/org.matrix.msc4143/rtc/transports",
+			}
+		};
+
+		#[request(error = Error)]
+		#[derive(Default)]
+		pub struct Request {}
+
+		#[response(error = Error)]
+		pub struct Response {
+			pub rtc_transports: Vec<RtcFocusInfo>,
+		}
+	}
+}
+
+pub mod get_delayed_events {
+	//! `GET /_matrix/client/unstable/org.matrix.msc4140/delayed_events`
+	//!
+	//! Delayed events scheduled by the user (MSC4140).
+
+	pub mod unstable {
+		use ruma::api::{Metadata, client::Error, metadata, request, response};
+		use tuwunel_service::delayed_events::DelayedEvent;
+
+		const METADATA: Metadata = metadata! {
+			method: GET,
+			rate_limited: false,
+			authentication: AccessToken,
+			history: {
+				unstable => "/_matrix/client/unstable/org.matrix.msc4140/delayed_events",
+			}
+		};
+
+		#[request(error = Error)]
+		#[derive(Default)]
+		pub struct Request {}
+
+		#[response(error = Error)]
+		pub struct Response {
+			pub delayed_events: Vec<DelayedEvent>,
+		}
+	}
+}
+
+pub mod update_delayed_event {
+	//! `POST /_matrix/client/unstable/org.matrix.msc4140/delayed_events/
+	//! {delayId}`
+	//!
+	//! Cancels a delayed event, restarts its delay or sends it immediately
+	//! (MSC4140).
+
+	pub mod unstable {
+		use ruma::api::{Metadata, client::Error, metadata, request, response};
+		use serde::{Deserialize, Serialize};
+
+		const METADATA: Metadata = metadata! {
+			method: POST,
+			rate_limited: true,
+			authentication: AccessToken,
+			history: {
+				unstable => "/_matrix/client/unstable/org.matrix.msc4140/delayed_events/{delay_id}",
+			}
+		};
+
+		#[request(error = Error)]
+		pub struct Request {
+			#[ruma_api(path)]
+			pub delay_id: String,
+
+			pub action: UpdateAction,
+		}
+
+		#[response(error = Error)]
+		#[derive(Default)]
+		pub struct Response {}
+
+		#[derive(Clone, Debug, Deserialize, Serialize)]
+		#[serde(rename_all = "snake_case")]
+		pub enum UpdateAction {
+			Cancel,
+			Restart,
+			Send,
+		}
+	}
+}
+
+pub mod moderate_space {
+	//! `POST /_tuwunel/client/spaces/{spaceId}/moderate`
+	//!
+	//! Moderation action applied to every room of a space subtree.
+
+	pub mod unstable {
+		use ruma::{
+			OwnedRoomId,
+			api::{Metadata, client::Error, metadata, request, response},
+		};
+		use serde::{Deserialize, Serialize};
+		use tuwunel_service::rooms::spaces::SpaceAction;
+
+		const METADATA: Metadata = metadata! {
+			method: POST,
+			rate_limited: false,
+			authentication: AccessToken,
+			history: {
+				unstable => "/_tuwunel/client/spaces/{space_id}/moderate",
+			}
+		};
+
+		#[request(error = Error)]
+		pub struct Request {
+			#[ruma_api(path)]
+			pub space_id: OwnedRoomId,
+
+			#[ruma_api(body)]
+			pub action: SpaceAction,
+		}
+
+		#[response(error = Error)]
+		pub struct Response {
+			/// Outcome of the action in each room of the subtree.
+			pub results: Vec<RoomResult>,
+		}
+
+		#[derive(Clone, Debug, Deserialize, Serialize)]
+		pub struct RoomResult {
+			pub room_id: OwnedRoomId,
+
+			/// Why the action failed in the room.
+			#[serde(default, skip_serializing_if = "Option::is_none")]
+			pub error: Option<String>,
+		}
+	}
+}
+
+pub mod admin_command {
+	//! `POST /_tuwunel/admin/v1/command`
+	//!
+	//! Admin command run as the user with its outcome in the JSON format.
+
+	pub mod v1 {
+		use ruma::api::{Metadata, client::Error, metadata, request, response};
+		use serde_json::Value as JsonValue;
+
+		const METADATA: Metadata = metadata! {
+			method: POST,
+			rate_limited: false,
+			authentication: AccessToken,
+			history: {
+				unstable => "/_tuwunel/admin/v1/command",
+			}
+		};
+
+		#[request(error = Error)]
+		pub struct Request {
+			/// The command as typed in the admin room without the `!admin`
+			/// prefix, followed by any lines of its code block.
+			pub command: String,
+		}
+
+		#[response(error = Error)]
+		pub struct Response {
+			/// `success`, the typed `data` of commands providing it, the
+			/// rendered `output` and the `error` of a failed command.
+			#[ruma_api(body)]
+			pub outcome: JsonValue,
+		}
+	}
+}
@@ -1,7 +1,7 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 use axum::extract::State;
-use futures::{StreamExt, stream::FuturesUnordered};
+use futures::{StreamExt, TryFutureExt, stream::FuturesUnordered};
 use ruma::{
 	CanonicalJsonObject, CanonicalJsonValue, OneTimeKeyAlgorithm, OwnedDeviceId, OwnedUserId,
 	UserId,
@@ -32,6 +32,7 @@
 /// Publish end-to-end encryption keys for the sender device.
 ///
 /// - Adds one time keys
+/// - Adds fallback keys, replacing the previous key of the same algorithm
 /// - If there are no device keys yet: Adds device keys (TODO: merge with
 ///   existing keys?)
 pub async fn upload_keys_route(
@@ -61,6 +62,27 @@ pub async fn upload_keys_route(
 			.await?;
 	}
 
+	for (key_id, fallback_key) in &body.fallback_keys {
+		if fallback_key
+			.deserialize()
+			.inspect_err(|e| {
+				debug_warn!(
+					?key_id,
+					?fallback_key,
+					"Invalid fallback key JSON submitted by client, skipping: {e}"
+				);
+			})
+			.is_err()
+		{
+			continue;
+		}
+
+		services
+			.users
+			.add_fallback_key(sender_user, sender_device, key_id, fallback_key)
+			.await?;
+	}
+
 	if let Some(device_keys) = &body.device_keys {
 		let deser_device_keys = device_keys.deserialize().map_err(|e| {
 			err!(Request(BadJson(debug_warn!(
@@ -635,9 +657,15 @@ pub async fn claim_keys_helper(
 
 		let mut container = BTreeMap::new();
 		for (device_id, key_algorithm) in map {
+			// Fall back to the device's fallback key once one-time keys run out
 			if let Ok(one_time_keys) = services
 				.users
 				.take_one_time_key(user_id, device_id, key_algorithm)
+				.or_else(|_| {
+					services
+						.users
+						.take_fallback_key(user_id, device_id, key_algorithm)
+				})
 				.await
 			{
 				let mut c = BTreeMap::new();
@@ -61,8 +61,8 @@
 ///
 /// Allows paginating through room history.
 ///
-/// - Only works if the user is joined (TODO: always allow, but only show events
-///   where the user was joined, depending on `history_visibility`)
+/// - Events are shown depending on `history_visibility`; former members can
+///   read the room up to their leave event
 pub async fn get_message_events_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_message_events::v3::Request>,
@@ -89,6 +89,18 @@ pub async fn get_message_events_route(
 
 	let to: Option<PduCount> = body.to.as_deref().map(str::parse).flat_ok();
 
+	// Former members cannot paginate past their leave event
+	let left_count = services
+		.state_accessor
+		.user_left_count(sender_user, room_id)
+		.await;
+
+	let from = match (&body.dir, left_count) {
+		| (Direction::Backward, Some(left_count)) =>
+			from.min(left_count.saturating_inc(Direction::Forward)),
+		| _ => from,
+	};
+
 	let limit: usize = body
 		.limit
 		.try_into()
@@ -120,10 +132,12 @@ pub async fn get_message_events_route(
 
 	let events: Vec<_> = it
 		.ready_take_while(|(count, _)| Some(*count) != to)
+		.ready_take_while(|(count, _)| left_count.is_none_or(|left_count| *count <= left_count))
 		.ready_filter_map(|item| event_filter(item, filter))
 		.wide_filter_map(|item| ignored_filter(&services, item, sender_user))
 		.wide_filter_map(|item| visibility_filter(&services, item, sender_user))
 		.take(limit)
+		.wide_then(|item| bundle_aggregations(&services, item, sender_user))
 		.collect()
 		.await;
 
@@ -294,6 +308,24 @@ pub async fn visibility_filter(
 		.then_some(item)
 }
 
+/// Adds the bundled aggregations of the event's relations for the user.
+#[inline]
+pub async fn bundle_aggregations(
+	services: &Services,
+	mut item: PdusIterItem,
+	user_id: &UserId,
+) -> PdusIterItem {
+	let (_, pdu) = &mut item;
+
+	services
+		.pdu_metadata
+		.bundle_aggregations(user_id, pdu)
+		.await
+		.ok();
+
+	item
+}
+
 #[inline]
 pub fn event_filter(item: PdusIterItem, filter: &RoomEventFilter) -> Option<PdusIterItem> {
 	let (_, pdu) = &item;
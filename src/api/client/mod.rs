@@ -1,12 +1,16 @@
 pub mod account;
 pub mod account_data;
+pub mod admin;
 pub mod alias;
 pub mod appservice;
 pub mod backup;
 pub mod capabilities;
 pub mod context;
+pub mod dehydrated_device;
+pub mod delayed_events;
 pub mod device;
 pub mod directory;
+pub mod endpoints;
 pub mod filter;
 pub mod keys;
 pub mod media;
@@ -23,10 +27,12 @@
 pub mod relations;
 pub mod report;
 pub mod room;
+pub mod rtc;
 pub mod search;
 pub mod send;
 pub mod session;
 pub mod space;
+pub mod space_moderation;
 pub mod state;
 pub mod sync;
 pub mod tag;
@@ -44,11 +50,14 @@
 
 pub use account::*;
 pub use account_data::*;
+pub use admin::*;
 pub use alias::*;
 pub use appservice::*;
 pub use backup::*;
 pub use capabilities::*;
 pub use context::*;
+pub use dehydrated_device::*;
+pub use delayed_events::*;
 pub use device::*;
 pub use directory::*;
 pub use filter::*;
@@ -67,10 +76,12 @@
 pub use relations::*;
 pub use report::*;
 pub use room::*;
+pub use rtc::*;
 pub use search::*;
 pub use send::*;
 pub use session::*;
 pub use space::*;
+pub use space_moderation::*;
 pub use state::*;
 pub use sync::*;
 pub use tag::*;
@@ -1,17 +1,18 @@
 use std::collections::BTreeMap;
 
 use axum::extract::State;
+use futures::{StreamExt, stream::iter};
 use ruma::{
 	MilliSecondsSinceUnixEpoch,
 	api::client::{read_marker::set_read_marker, receipt::create_receipt},
 	events::{
 		RoomAccountDataEventType,
-		receipt::{ReceiptThread, ReceiptType},
+		receipt::{Receipt, ReceiptThread, ReceiptType},
 	},
 };
-use tuwunel_core::{Err, PduCount, Result, err};
+use tuwunel_core::{Err, PduCount, Result, err, utils::ReadyExt};
 
-use crate::Ruma;
+use crate::{Ruma, client::endpoints::get_read_by};
 
 /// # `POST /_matrix/client/r0/rooms/{roomId}/read_markers`
 ///
@@ -45,7 +46,8 @@ pub async fn set_read_marker_route(
 	if body.private_read_receipt.is_some() || body.read_receipt.is_some() {
 		services
 			.user
-			.reset_notification_counts(sender_user, &body.room_id);
+			.reset_notification_counts(sender_user, &body.room_id)
+			.await;
 	}
 
 	// ping presence
@@ -61,7 +63,7 @@ pub async fn set_read_marker_route(
 			event.to_owned(),
 			BTreeMap::from_iter([(
 				ReceiptType::Read,
-				BTreeMap::from_iter([(sender_user.to_owned(), ruma::events::receipt::Receipt {
+				BTreeMap::from_iter([(sender_user.to_owned(), Receipt {
 					ts: Some(MilliSecondsSinceUnixEpoch::now()),
 					thread: ReceiptThread::Unthreaded,
 				})]),
@@ -96,7 +98,10 @@ pub async fn set_read_marker_route(
 
 		services
 			.read_receipt
-			.private_read_set(&body.room_id, sender_user, count);
+			.private_read_set(&body.room_id, sender_user, count, &Receipt {
+				ts: Some(MilliSecondsSinceUnixEpoch::now()),
+				thread: ReceiptThread::Unthreaded,
+			});
 	}
 
 	Ok(set_read_marker::v3::Response {})
@@ -105,21 +110,35 @@ pub async fn set_read_marker_route(
 /// # `POST /_matrix/client/r0/rooms/{roomId}/receipt/{receiptType}/{eventId}`
 ///
 /// Sets private read marker and public read receipt EDU.
+///
+/// - Threaded receipts only reset the unread counts of their thread
 pub async fn create_receipt_route(
 	State(services): State<crate::State>,
 	body: Ruma<create_receipt::v3::Request>,
 ) -> Result<create_receipt::v3::Response> {
 	let sender_user = body.sender_user();
 
+	if matches!(&body.receipt_type, create_receipt::v3::ReceiptType::FullyRead)
+		&& body.thread != ReceiptThread::Unthreaded
+	{
+		return Err!(Request(InvalidParam("Fully read markers cannot be threaded.")));
+	}
+
 	if matches!(
 		&body.receipt_type,
 		create_receipt::v3::ReceiptType::Read | create_receipt::v3::ReceiptType::ReadPrivate
 	) {
 		services
 			.user
-			.reset_notification_counts(sender_user, &body.room_id);
+			.reset_thread_notification_counts(sender_user, &body.room_id, &body.thread)
+			.await;
 	}
 
+	let receipt = Receipt {
+		ts: Some(MilliSecondsSinceUnixEpoch::now()),
+		thread: body.thread.clone(),
+	};
+
 	// ping presence
 	if services.config.allow_local_presence {
 		services
@@ -150,13 +169,7 @@ pub async fn create_receipt_route(
 				body.event_id.clone(),
 				BTreeMap::from_iter([(
 					ReceiptType::Read,
-					BTreeMap::from_iter([(
-						sender_user.to_owned(),
-						ruma::events::receipt::Receipt {
-							ts: Some(MilliSecondsSinceUnixEpoch::now()),
-							thread: ReceiptThread::Unthreaded,
-						},
-					)]),
+					BTreeMap::from_iter([(sender_user.to_owned(), receipt)]),
 				)]),
 			)]);
 
@@ -187,7 +200,7 @@ pub async fn create_receipt_route(
 
 			services
 				.read_receipt
-				.private_read_set(&body.room_id, sender_user, count);
+				.private_read_set(&body.room_id, sender_user, count, &receipt);
 		},
 		| _ => {
 			return Err!(Request(InvalidParam(warn!(
@@ -199,3 +212,52 @@ pub async fn create_receipt_route(
 
 	Ok(create_receipt::v3::Response {})
 }
+
+/// # `GET /_matrix/client/unstable/net.tuwunel/rooms/{roomId}/read_by`
+///
+/// Users who have read up to each event in the room according to their public
+/// read receipts.
+///
+/// - The user must be joined to the room
+/// - Receipts of users ignored by the user are left out
+pub async fn get_read_by_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_read_by::unstable::Request>,
+) -> Result<get_read_by::unstable::Response> {
+	let sender_user = body.sender_user();
+	if !services
+		.state_cache
+		.is_joined(sender_user, &body.room_id)
+		.await
+	{
+		return Err!(Request(Forbidden("You are not joined to this room.")));
+	}
+
+	let mut read_by = services.read_receipt.read_by(&body.room_id).await;
+
+	if let Some(event_id) = &body.event_id {
+		read_by.retain(|id, _| id == event_id);
+	}
+
+	let read_by = iter(read_by)
+		.then(async |(event_id, users)| {
+			let users = iter(users)
+				.filter_map(async |(user_id, receipt)| {
+					services
+						.users
+						.user_is_ignored(&user_id, sender_user)
+						.await
+						.eq(&false)
+						.then_some((user_id, receipt))
+				})
+				.collect::<BTreeMap<_, _>>()
+				.await;
+
+			(event_id, users)
+		})
+		.ready_filter(|(_, users)| !users.is_empty())
+		.collect()
+		.await;
+
+	Ok(get_read_by::unstable::Response { read_by })
+}
@@ -534,55 +534,13 @@ pub async fn register_route(
 		&& !services.server.config.auto_join_rooms.is_empty()
 		&& (services.config.allow_guests_auto_join_rooms || !is_guest)
 	{
-		for room in &services.server.config.auto_join_rooms {
-			let Ok(room_id) = services.alias.resolve(room).await else {
-				error!(
-					"Failed to resolve room alias to room ID when attempting to auto join \
-					 {room}, skipping"
-				);
-				continue;
-			};
-
-			if !services
-				.state_cache
-				.server_in_room(services.globals.server_name(), &room_id)
-				.await
-			{
-				warn!(
-					"Skipping room {room} to automatically join as we have never joined before."
-				);
-				continue;
-			}
-
-			if let Some(room_server_name) = room.server_name() {
-				let state_lock = services.state.mutex.lock(&room_id).await;
-
-				match services
-					.membership
-					.join(
-						&user_id,
-						&room_id,
-						Some("Automatically joining this room upon registration".to_owned()),
-						&[services.globals.server_name().to_owned(), room_server_name.to_owned()],
-						&body.appservice_info,
-						&state_lock,
-					)
-					.boxed()
-					.await
-				{
-					| Err(e) => {
-						// don't return this error so we don't fail registrations
-						error!(
-							"Failed to automatically join room {room} for user {user_id}: {e}"
-						);
-					},
-					| _ => {
-						info!("Automatically joined room {room} for user {user_id}");
-					},
-				}
-
-				drop(state_lock);
-			}
+		for e in services
+			.membership
+			.auto_join(&user_id, &body.appservice_info)
+			.await
+		{
+			// don't return this error so we don't fail registrations
+			error!("{e}");
 		}
 	}
 
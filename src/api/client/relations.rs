@@ -151,6 +151,15 @@ async fn paginate_relations_with_filter(
 		.ready_take_while(|(count, _)| Some(*count) != to)
 		.wide_filter_map(|item| visibility_filter(services, sender_user, item))
 		.take(limit)
+		.wide_then(async |(count, mut pdu)| {
+			services
+				.pdu_metadata
+				.bundle_aggregations(sender_user, pdu.as_mut_pdu())
+				.await
+				.ok();
+
+			(count, pdu)
+		})
 		.collect()
 		.await;
 
@@ -375,7 +375,7 @@ pub async fn create_room_route(
 	}
 
 	if body.visibility == room::Visibility::Public {
-		services.directory.set_public(&room_id);
+		services.directory.set_public(&room_id).await;
 
 		if services.server.config.admin_room_notices {
 			services
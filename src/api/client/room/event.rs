@@ -38,5 +38,11 @@ pub async fn get_room_event_route(
 
 	event.add_age().ok();
 
+	services
+		.pdu_metadata
+		.bundle_aggregations(body.sender_user(), &mut event)
+		.await
+		.ok();
+
 	Ok(get_room_event::v3::Response { event: event.into_format() })
 }
@@ -0,0 +1,203 @@
+use axum::{Json, extract::State, response::IntoResponse};
+use base64::{Engine as _, engine::general_purpose::STANDARD_NO_PAD};
+use ruma::{
+	OwnedRoomId, OwnedServerName, OwnedUserId, RoomId,
+	api::{
+		client::discovery::discover_homeserver::RtcFocusInfo,
+		federation::openid::get_openid_userinfo,
+	},
+};
+use serde::{Deserialize, Serialize};
+use tuwunel_core::{
+	Err, Result, err,
+	jwt::{Algorithm, EncodingKey, Header, encode},
+	utils::{hash::sha256, time::now_secs},
+};
+use tuwunel_service::Services;
+
+use crate::{Ruma, client::endpoints::get_rtc_transports};
+
+/// Path of the built-in LiveKit authorization service under the client URL.
+const LIVEKIT_SERVICE_PATH: &str = "_tuwunel/livekit";
+
+/// Slot of the room-wide call (MSC4143) which LiveKit rooms are derived from.
+const LIVEKIT_ROOM_SLOT: &str = "m.call#ROOM";
+
+/// Request of a MatrixRTC client for a LiveKit token, as made to
+/// lk-jwt-service.
+#[derive(Deserialize)]
+pub struct LiveKitTokenRequest {
+	room: OwnedRoomId,
+	openid_token: OpenIdToken,
+	device_id: String,
+}
+
+#[derive(Deserialize)]
+struct OpenIdToken {
+	access_token: String,
+	matrix_server_name: OwnedServerName,
+}
+
+#[derive(Serialize)]
+struct LiveKitClaims<'a> {
+	iss: &'a str,
+	sub: String,
+	nbf: u64,
+	exp: u64,
+	video: VideoGrant,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoGrant {
+	room: String,
+	room_join: bool,
+	room_create: bool,
+	can_publish: bool,
+	can_subscribe: bool,
+}
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc4143/rtc/transports`
+///
+/// MatrixRTC foci available to clients of this server.
+pub async fn rtc_transports_route(
+	State(services): State<crate::State>,
+	_body: Ruma<get_rtc_transports::unstable::Request>,
+) -> Result<get_rtc_transports::unstable::Response> {
+	Ok(get_rtc_transports::unstable::Response { rtc_transports: rtc_foci(&services) })
+}
+
+/// # `POST /_tuwunel/livekit/sfu/get`
+///
+/// Built-in LiveKit authorization service exchanging an OpenID token for a
+/// LiveKit token to join the call of a room, compatible with lk-jwt-service.
+///
+/// - The user must be joined to the room
+/// - Only users of this server may create the LiveKit room
+pub async fn livekit_sfu_get_route(
+	State(services): State<crate::State>,
+	Json(body): Json<LiveKitTokenRequest>,
+) -> Result<impl IntoResponse> {
+	let config = &services.server.config.matrix_rtc;
+	let (Some(url), Some(key), Some(secret)) =
+		(&config.livekit_url, &config.livekit_key, &config.livekit_secret)
+	else {
+		return Err!(Request(NotFound("LiveKit is not configured on this server.")));
+	};
+
+	let room_id = &body.room;
+	let user_id = openid_user(&services, &body.openid_token).await?;
+	if !services
+		.state_cache
+		.is_joined(&user_id, room_id)
+		.await
+	{
+		return Err!(Request(Forbidden("{user_id} is not joined to {room_id}.")));
+	}
+
+	let now = now_secs();
+	let claims = LiveKitClaims {
+		iss: key,
+		sub: format!("{user_id}:{}", body.device_id),
+		nbf: now,
+		exp: now.saturating_add(config.livekit_token_ttl),
+		video: VideoGrant {
+			room: livekit_room(room_id),
+			room_join: true,
+			room_create: services.globals.user_is_local(&user_id),
+			can_publish: true,
+			can_subscribe: true,
+		},
+	};
+
+	let jwt = encode(
+		&Header::new(Algorithm::HS256),
+		&claims,
+		&EncodingKey::from_secret(secret.as_bytes()),
+	)
+	.map_err(|e| err!("Failed to encode LiveKit token: {e}"))?;
+
+	Ok(Json(serde_json::json!({
+		"url": url,
+		"jwt": jwt,
+	})))
+}
+
+/// MatrixRTC foci advertised to clients: the built-in LiveKit authorization
+/// service when configured, followed by the configured foci.
+pub(crate) fn rtc_foci(services: &Services) -> Vec<RtcFocusInfo> {
+	let config = &services.server.config;
+	let builtin = config
+		.matrix_rtc
+		.livekit_enabled()
+		.then(|| livekit_service_url(services));
+
+	builtin
+		.into_iter()
+		.chain(
+			config
+				.matrix_rtc
+				.foci
+				.iter()
+				.map(|url| url.as_str().to_owned()),
+		)
+		.map(|url| RtcFocusInfo::livekit(url.trim_end_matches('/').to_owned()))
+		.collect()
+}
+
+/// URL the built-in LiveKit authorization service is reached at: as
+/// configured, else under the client URL of `/.well-known/matrix/client`, else
+/// under the server name.
+fn livekit_service_url(services: &Services) -> String {
+	let config = &services.server.config;
+	if let Some(url) = &config.matrix_rtc.livekit_service_url {
+		return url.to_string();
+	}
+
+	config
+		.well_known
+		.client
+		.as_ref()
+		.and_then(|client| client.join(LIVEKIT_SERVICE_PATH).ok())
+		.map_or_else(
+			|| format!("https://{}/{LIVEKIT_SERVICE_PATH}", services.globals.server_name()),
+			|url| url.to_string(),
+		)
+}
+
+/// LiveKit room of the room-wide call of a room, derived like lk-jwt-service
+/// so calls meet in the same LiveKit room whichever service authorized them.
+fn livekit_room(room_id: &RoomId) -> String {
+	let alias = format!("{room_id}|{LIVEKIT_ROOM_SLOT}");
+
+	STANDARD_NO_PAD.encode(sha256::hash(alias.as_bytes()))
+}
+
+/// User the OpenID token was issued to, asking the user's server when it is
+/// not ours.
+async fn openid_user(services: &Services, token: &OpenIdToken) -> Result<OwnedUserId> {
+	let server_name = &token.matrix_server_name;
+	if services.globals.server_is_ours(server_name) {
+		return services
+			.users
+			.find_from_openid_token(&token.access_token)
+			.await
+			.map_err(|_| err!(Request(Unauthorized("Invalid OpenID token."))));
+	}
+
+	let request = get_openid_userinfo::v1::Request::new(token.access_token.clone());
+	let response = services
+		.sending
+		.send_federation_request(server_name, request)
+		.await
+		.map_err(|e| err!(Request(Unauthorized("Failed to validate OpenID token: {e}"))))?;
+
+	let user_id = response.sub;
+	if user_id.server_name() != server_name {
+		return Err!(Request(Unauthorized(
+			"OpenID token of {server_name} was issued to {user_id}."
+		)));
+	}
+
+	Ok(user_id)
+}
@@ -1,29 +1,51 @@
-use std::collections::BTreeMap;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	iter::once,
+};
 
 use axum::extract::State;
-use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt, future::OptionFuture};
+use futures::{
+	FutureExt, StreamExt, TryFutureExt, TryStreamExt,
+	future::{OptionFuture, join},
+};
 use ruma::{
-	OwnedRoomId, RoomId, UInt, UserId,
+	EventId, OwnedRoomId, OwnedUserId, RoomId, UInt, UserId,
 	api::client::search::search_events::{
 		self,
-		v3::{Criteria, EventContextResult, ResultCategories, ResultRoomEvents, SearchResult},
+		v3::{
+			Criteria, EventContextResult, GroupingKey, OwnedRoomIdOrUserId, ResultCategories,
+			ResultGroup, ResultRoomEvents, SearchResult, UserProfile,
+		},
 	},
-	events::AnyStateEvent,
+	events::{AnyStateEvent, StateEventType, room::member::RoomMemberEventContent},
 	serde::Raw,
 };
 use search_events::v3::{Request, Response};
 use tuwunel_core::{
-	Err, Result, at, is_true,
+	Err, PduCount, Result, at, is_true,
 	matrix::Event,
+	ref_at,
 	result::FlatOk,
-	utils::{IterStream, stream::ReadyExt},
+	utils::{
+		IterStream,
+		stream::{ReadyExt, TryIgnore, WidebandExt},
+	},
+};
+use tuwunel_service::{
+	Services,
+	rooms::{search::RoomQuery, short::ShortStateHash, timeline::PdusIterItem},
 };
-use tuwunel_service::{Services, rooms::search::RoomQuery};
 
-use crate::Ruma;
+use crate::{
+	Ruma,
+	client::message::{bundle_aggregations, ignored_filter, visibility_filter},
+};
 
 type RoomStates = BTreeMap<OwnedRoomId, RoomState>;
 type RoomState = Vec<Raw<AnyStateEvent>>;
+type Groups = BTreeMap<GroupingKey, BTreeMap<OwnedRoomIdOrUserId, ResultGroup>>;
+type Profiles = BTreeMap<OwnedUserId, UserProfile>;
+type Hit = (Option<PduCount>, PdusIterItem);
 
 const LIMIT_DEFAULT: usize = 10;
 const LIMIT_MAX: usize = 100;
@@ -33,8 +55,11 @@
 ///
 /// Searches rooms for messages.
 ///
-/// - Only works if the user is currently joined to the room (TODO: Respect
-///   history visibility)
+/// - Searches rooms the user is joined to, and rooms they left up to the point
+///   of leaving
+/// - Includes the requested context around each result, with sender profiles at
+///   the time of the result
+/// - Groups results by room or sender when requested
 pub async fn search_events_route(
 	State(services): State<crate::State>,
 	body: Ruma<Request>,
@@ -86,10 +111,16 @@ async fn category_room_events(
 		.map(IterStream::stream)
 		.map(StreamExt::boxed)
 		.unwrap_or_else(|| {
+			let left = services
+				.state_cache
+				.rooms_left(sender_user)
+				.map(at!(0));
+
 			services
 				.state_cache
 				.rooms_joined(sender_user)
 				.map(ToOwned::to_owned)
+				.chain(left)
 				.boxed()
 		});
 
@@ -101,17 +132,20 @@ async fn category_room_events(
 				.then_some(room_id)
 		})
 		.filter_map(async |room_id| {
+			let until = room_until(services, sender_user, &room_id).await;
 			let query = RoomQuery {
 				room_id: &room_id,
 				user_id: Some(sender_user),
 				criteria,
 				skip: next_batch,
 				limit,
+				until,
 			};
 
 			let (count, results) = services.search.search_pdus(&query).await.ok()?;
 
 			results
+				.map(|result| (until, result))
 				.collect::<Vec<_>>()
 				.map(|results| (room_id.clone(), count, results))
 				.map(Some)
@@ -138,23 +172,14 @@ async fn category_room_events(
 		.collect()
 		.await;
 
-	let results: Vec<SearchResult> = results
+	let hits: Vec<Hit> = results.into_iter().flat_map(at!(2)).collect();
+
+	let groups = group_results(criteria, &hits);
+
+	let results: Vec<SearchResult> = hits
 		.into_iter()
-		.map(at!(2))
-		.flatten()
 		.stream()
-		.map(Event::into_format)
-		.map(|result| SearchResult {
-			rank: None,
-			result: Some(result),
-			context: EventContextResult {
-				profile_info: BTreeMap::new(), //TODO
-				events_after: Vec::new(),      //TODO
-				events_before: Vec::new(),     //TODO
-				start: None,                   //TODO
-				end: None,                     //TODO
-			},
-		})
+		.then(|hit| search_result(services, sender_user, criteria, hit))
 		.collect()
 		.await;
 
@@ -175,10 +200,202 @@ async fn category_room_events(
 		results,
 		state,
 		highlights,
-		groups: BTreeMap::new(), // TODO
+		groups,
 	})
 }
 
+async fn search_result(
+	services: &Services,
+	user_id: &UserId,
+	criteria: &Criteria,
+	(until, hit): Hit,
+) -> SearchResult {
+	let (count, pdu) = bundle_aggregations(services, hit, user_id).await;
+	let event_context = &criteria.event_context;
+	let room_id = pdu.room_id();
+
+	let before_limit: usize = event_context
+		.before_limit
+		.try_into()
+		.unwrap_or(LIMIT_DEFAULT)
+		.min(LIMIT_MAX);
+
+	let after_limit: usize = event_context
+		.after_limit
+		.try_into()
+		.unwrap_or(LIMIT_DEFAULT)
+		.min(LIMIT_MAX);
+
+	let events_before = services
+		.timeline
+		.pdus_rev(Some(user_id), room_id, Some(count))
+		.ignore_err()
+		.wide_filter_map(|item| ignored_filter(services, item, user_id))
+		.wide_filter_map(|item| visibility_filter(services, item, user_id))
+		.take(before_limit)
+		.wide_then(|item| bundle_aggregations(services, item, user_id))
+		.collect();
+
+	let events_after = services
+		.timeline
+		.pdus(Some(user_id), room_id, Some(count))
+		.ignore_err()
+		.ready_take_while(move |(pdu_count, _)| until.is_none_or(|until| *pdu_count <= until))
+		.wide_filter_map(|item| ignored_filter(services, item, user_id))
+		.wide_filter_map(|item| visibility_filter(services, item, user_id))
+		.take(after_limit)
+		.wide_then(|item| bundle_aggregations(services, item, user_id))
+		.collect();
+
+	let (events_before, events_after): (Vec<_>, Vec<_>) =
+		join(events_before, events_after).boxed().await;
+
+	let profile_info: OptionFuture<_> = event_context
+		.include_profile
+		.then(|| {
+			let senders: BTreeSet<&UserId> = once(&pdu)
+				.chain(events_before.iter().map(ref_at!(1)))
+				.chain(events_after.iter().map(ref_at!(1)))
+				.map(Event::sender)
+				.collect();
+
+			procure_profiles(services, pdu.event_id(), senders)
+		})
+		.into();
+
+	let profile_info = profile_info.await.unwrap_or_default();
+
+	SearchResult {
+		rank: None,
+		context: EventContextResult {
+			start: events_before
+				.last()
+				.map(at!(0))
+				.or(Some(count))
+				.as_ref()
+				.map(ToString::to_string),
+
+			end: events_after
+				.last()
+				.map(at!(0))
+				.or(Some(count))
+				.as_ref()
+				.map(ToString::to_string),
+
+			events_before: events_before
+				.into_iter()
+				.map(at!(1))
+				.map(Event::into_format)
+				.collect(),
+
+			events_after: events_after
+				.into_iter()
+				.map(at!(1))
+				.map(Event::into_format)
+				.collect(),
+
+			profile_info,
+		},
+		result: Some(pdu.into_format()),
+	}
+}
+
+/// Profiles of the senders from their membership at the time of the event.
+async fn procure_profiles(
+	services: &Services,
+	event_id: &EventId,
+	senders: BTreeSet<&UserId>,
+) -> Profiles {
+	let Ok(shortstatehash) = services
+		.state_accessor
+		.pdu_shortstatehash(event_id)
+		.await
+	else {
+		return Profiles::new();
+	};
+
+	senders
+		.into_iter()
+		.stream()
+		.filter_map(async |sender| {
+			procure_profile(services, shortstatehash, sender)
+				.await
+				.map(|profile| (sender.to_owned(), profile))
+		})
+		.collect()
+		.await
+}
+
+async fn procure_profile(
+	services: &Services,
+	shortstatehash: ShortStateHash,
+	user_id: &UserId,
+) -> Option<UserProfile> {
+	let content: RoomMemberEventContent = services
+		.state_accessor
+		.state_get_content(shortstatehash, &StateEventType::RoomMember, user_id.as_str())
+		.await
+		.ok()?;
+
+	Some(UserProfile {
+		avatar_url: content.avatar_url,
+		displayname: content.displayname,
+	})
+}
+
+/// Groups the event ids of the results by each requested key, ordered by the
+/// first result of each group.
+fn group_results(criteria: &Criteria, hits: &[Hit]) -> Groups {
+	criteria
+		.groupings
+		.group_by
+		.iter()
+		.filter_map(|grouping| grouping.key.clone())
+		.map(|key| {
+			let mut groups: BTreeMap<OwnedRoomIdOrUserId, ResultGroup> = BTreeMap::new();
+			for (_, (_, pdu)) in hits {
+				let group = match key {
+					| GroupingKey::RoomId =>
+						OwnedRoomIdOrUserId::RoomId(pdu.room_id().to_owned()),
+					| GroupingKey::Sender => OwnedRoomIdOrUserId::UserId(pdu.sender().to_owned()),
+					| _ => continue,
+				};
+
+				let order = groups.len().try_into().ok();
+				groups
+					.entry(group)
+					.or_insert_with(|| ResultGroup {
+						next_batch: None,
+						order,
+						results: Vec::new(),
+					})
+					.results
+					.push(pdu.event_id().to_owned());
+			}
+
+			(key, groups)
+		})
+		.collect()
+}
+
+/// Rooms the user left are searched up to the point of leaving.
+async fn room_until(services: &Services, user_id: &UserId, room_id: &RoomId) -> Option<PduCount> {
+	if services
+		.state_cache
+		.is_joined(user_id, room_id)
+		.await
+	{
+		return None;
+	}
+
+	services
+		.state_cache
+		.get_left_count(room_id, user_id)
+		.await
+		.ok()
+		.map(PduCount::Normal)
+}
+
 async fn procure_room_state(services: &Services, room_id: &RoomId) -> Result<RoomState> {
 	let state = services
 		.state_accessor
@@ -199,10 +416,14 @@ async fn check_room_visible(
 	let check_visible = search.filter.rooms.is_some();
 	let check_state = check_visible && search.include_state.is_some_and(is_true!());
 
-	let is_joined = !check_visible
+	let is_member = !check_visible
 		|| services
 			.state_cache
 			.is_joined(user_id, room_id)
+			.await
+		|| services
+			.state_cache
+			.is_left(user_id, room_id)
 			.await;
 
 	let state_visible = !check_state
@@ -211,7 +432,7 @@ async fn check_room_visible(
 			.user_can_see_state_events(user_id, room_id)
 			.await;
 
-	if !is_joined || !state_visible {
+	if !is_member || !state_visible {
 		return Err!(Request(Forbidden("You don't have permission to view {room_id:?}")));
 	}
 
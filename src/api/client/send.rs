@@ -1,11 +1,16 @@
 use std::collections::BTreeMap;
 
-use axum::extract::State;
+use axum::{
+	extract::State,
+	response::{IntoResponse, Response},
+};
+use futures::FutureExt;
 use ruma::{api::client::message::send_message_event, events::MessageLikeEventType};
 use serde_json::from_str;
 use tuwunel_core::{Err, Result, err, matrix::pdu::PduBuilder, utils};
+use tuwunel_service::transaction_ids::TxnResponse;
 
-use crate::Ruma;
+use crate::{Ruma, RumaResponse, client::delayed_events::delayed_event_response};
 
 /// # `PUT /_matrix/client/v3/rooms/{roomId}/send/{eventType}/{txnId}`
 ///
@@ -95,3 +100,70 @@ pub async fn send_message_event_route(
 
 	Ok(send_message_event::v3::Response { event_id })
 }
+
+/// # `PUT /_matrix/client/v3/rooms/{roomId}/send/{eventType}/{txnId}`
+///
+/// Send a message event into the room, or schedule it as a delayed event
+/// (MSC4140) when the `org.matrix.msc4140.delay` query parameter is given.
+///
+/// - Delayed events are sent with the authority of the sender when their delay
+///   elapses
+/// - Is a NOOP if the txn id was already used to schedule a delayed event and
+///   returns the same delay id again
+pub async fn send_message_event_delayable_route(
+	State(services): State<crate::State>,
+	body: Ruma<send_message_event::v3::Request>,
+) -> Result<Response> {
+	let sender_user = body.sender_user();
+	let sender_device = body.sender_device.as_deref();
+
+	let existing_txnid = services
+		.transaction_ids
+		.existing_txn_response(sender_user, sender_device, &body.txn_id)
+		.await
+		.ok();
+
+	if let Some(TxnResponse::Delayed(delay_id)) = &existing_txnid {
+		return Ok(delayed_event_response(delay_id));
+	}
+
+	let Some(delay) = body.delay else {
+		return send_message_event_route(State(services), body)
+			.boxed()
+			.await
+			.map(RumaResponse)
+			.map(IntoResponse::into_response);
+	};
+
+	if existing_txnid.is_some() {
+		return Err!(Request(InvalidParam(
+			"Tried to use txn id already used for an incompatible endpoint."
+		)));
+	}
+
+	if MessageLikeEventType::RoomEncrypted == body.event_type && !services.config.allow_encryption
+	{
+		return Err!(Request(Forbidden("Encryption has been disabled")));
+	}
+
+	let delay_id = services
+		.delayed_events
+		.schedule(
+			sender_user,
+			&body.room_id,
+			body.event_type.to_string(),
+			None,
+			body.body.body.json().to_owned(),
+			delay,
+		)
+		.await?;
+
+	services.transaction_ids.add_delayed_txnid(
+		sender_user,
+		sender_device,
+		&body.txn_id,
+		&delay_id,
+	);
+
+	Ok(delayed_event_response(&delay_id))
+}
@@ -0,0 +1,40 @@
+use axum::extract::State;
+use tuwunel_core::{Err, Result};
+use tuwunel_service::rooms::spaces::SpaceAction;
+
+use crate::{
+	Ruma,
+	client::endpoints::moderate_space::unstable::{Request, Response, RoomResult},
+};
+
+/// # `POST /_tuwunel/client/spaces/{space_id}/moderate`
+///
+/// Applies a moderation action to the space and every room of its subtree
+/// this server is in, as the user, reporting the outcome for each room.
+///
+/// - The body names the action, e.g. `{"action": "ban", "user_id": "@a:b"}`;
+///   one of `ban`, `kick`, `unban`, `set_power_level`, `deny_server` or
+///   `undeny_server`
+/// - The action is only taken in rooms where the user is joined and has enough
+///   power
+pub async fn moderate_space_route(
+	State(services): State<crate::State>,
+	body: Ruma<Request>,
+) -> Result<Response> {
+	if matches!(body.action, SpaceAction::ForceJoin { .. }) {
+		return Err!(Request(Forbidden("Only server admins may force users to join rooms.")));
+	}
+
+	let results = services
+		.spaces
+		.apply_to_space(&body.space_id, &body.action, Some(body.sender_user()))
+		.await?
+		.into_iter()
+		.map(|(room_id, result)| RoomResult {
+			room_id,
+			error: result.err().map(|e| e.to_string()),
+		})
+		.collect();
+
+	Ok(Response { results })
+}
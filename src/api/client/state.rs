@@ -1,29 +1,24 @@
-use axum::extract::State;
-use futures::{FutureExt, TryFutureExt, TryStreamExt};
+use axum::{
+	extract::State,
+	response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use futures::{FutureExt, TryStreamExt};
 use ruma::{
-	OwnedEventId, RoomId, UserId,
+	OwnedEventId, OwnedTransactionId, RoomId, UserId,
 	api::client::state::{get_state_event_for_key, get_state_events, send_state_event},
-	events::{
-		AnyStateEventContent, StateEventType,
-		room::{
-			canonical_alias::RoomCanonicalAliasEventContent,
-			history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
-			join_rules::{JoinRule, RoomJoinRulesEventContent},
-			member::{MembershipState, RoomMemberEventContent},
-			server_acl::RoomServerAclEventContent,
-		},
-	},
+	events::{AnyStateEventContent, StateEventType},
 	serde::Raw,
 };
 use serde_json::json;
 use tuwunel_core::{
-	Err, Result, err, is_false,
+	Err, Result, err,
 	matrix::{Event, pdu::PduBuilder},
-	utils::BoolExt,
+	utils::{BoolExt, hash::sha256},
 };
-use tuwunel_service::Services;
+use tuwunel_service::{Services, transaction_ids::TxnResponse};
 
-use crate::{Ruma, RumaResponse};
+use crate::{Ruma, RumaResponse, client::delayed_events::delayed_event_response};
 
 /// # `PUT /_matrix/client/*/rooms/{roomId}/state/{eventType}/{stateKey}`
 ///
@@ -52,17 +47,91 @@ pub async fn send_state_event_for_key_route(
 	})
 }
 
+/// # `PUT /_matrix/client/*/rooms/{roomId}/state/{eventType}/{stateKey}`
+///
+/// Sends a state event into the room, or schedules it as a delayed event
+/// (MSC4140) when the `org.matrix.msc4140.delay` query parameter is given.
+///
+/// - Repeating the request while the delayed event is scheduled returns the
+///   same delay id again
+pub async fn send_state_event_for_key_delayable_route(
+	State(services): State<crate::State>,
+	body: Ruma<send_state_event::v3::Request>,
+) -> Result<Response> {
+	let Some(delay) = body.delay else {
+		return send_state_event_for_key_route(State(services), body)
+			.boxed()
+			.await
+			.map(RumaResponse)
+			.map(IntoResponse::into_response);
+	};
+
+	services
+		.state
+		.allowed_to_send_state_event(
+			&body.room_id,
+			&body.event_type,
+			&body.state_key,
+			&body.body.body,
+		)
+		.await?;
+
+	// State requests carry no transaction id; retries are recognized by content
+	let sender_user = body.sender_user();
+	let sender_device = body.sender_device.as_deref();
+	let txn_id: OwnedTransactionId = URL_SAFE_NO_PAD
+		.encode(sha256::delimited(
+			[
+				body.room_id.as_str(),
+				body.event_type.to_string().as_str(),
+				body.state_key.as_str(),
+				body.body.body.json().get(),
+			]
+			.into_iter(),
+		))
+		.into();
+
+	if let Ok(TxnResponse::Delayed(delay_id)) = services
+		.transaction_ids
+		.existing_txn_response(sender_user, sender_device, &txn_id)
+		.await && services
+		.delayed_events
+		.get(&delay_id)
+		.await
+		.is_ok()
+	{
+		return Ok(delayed_event_response(&delay_id));
+	}
+
+	let delay_id = services
+		.delayed_events
+		.schedule(
+			sender_user,
+			&body.room_id,
+			body.event_type.to_string(),
+			Some(body.state_key.clone()),
+			body.body.body.json().to_owned(),
+			delay,
+		)
+		.await?;
+
+	services
+		.transaction_ids
+		.add_delayed_txnid(sender_user, sender_device, &txn_id, &delay_id);
+
+	Ok(delayed_event_response(&delay_id))
+}
+
 /// # `PUT /_matrix/client/*/rooms/{roomId}/state/{eventType}`
 ///
 /// Sends a state event into the room.
 pub async fn send_state_event_for_empty_key_route(
 	State(services): State<crate::State>,
 	body: Ruma<send_state_event::v3::Request>,
-) -> Result<RumaResponse<send_state_event::v3::Response>> {
-	send_state_event_for_key_route(State(services), body)
+) -> Result<Response> {
+	send_state_event_for_key_delayable_route(State(services), body)
 		.boxed()
 		.await
-		.map(RumaResponse)
 }
 
 /// # `GET /_matrix/client/v3/rooms/{roomid}/state`
@@ -179,7 +248,11 @@ async fn send_state_event_for_key_helper(
 	state_key: &str,
 	timestamp: Option<ruma::MilliSecondsSinceUnixEpoch>,
 ) -> Result<OwnedEventId> {
-	allowed_to_send_state_event(services, room_id, event_type, state_key, json).await?;
+	services
+		.state
+		.allowed_to_send_state_event(room_id, event_type, state_key, json)
+		.await?;
+
 	let state_lock = services.state.mutex.lock(room_id).await;
 	let event_id = services
 		.timeline
@@ -199,203 +272,3 @@ async fn send_state_event_for_key_helper(
 
 	Ok(event_id)
 }
-
-async fn allowed_to_send_state_event(
-	services: &Services,
-	room_id: &RoomId,
-	event_type: &StateEventType,
-	state_key: &str,
-	json: &Raw<AnyStateEventContent>,
-) -> Result {
-	match event_type {
-		| StateEventType::RoomCreate => {
-			return Err!(Request(BadJson(debug_warn!(
-				?room_id,
-				"You cannot update m.room.create after a room has been created."
-			))));
-		},
-		| StateEventType::RoomServerAcl => {
-			// prevents common ACL paw-guns as ACL management is difficult and prone to
-			// irreversible mistakes
-			match json.deserialize_as_unchecked::<RoomServerAclEventContent>() {
-				| Ok(acl_content) => {
-					if acl_content.allow_is_empty() {
-						return Err!(Request(BadJson(debug_warn!(
-							?room_id,
-							"Sending an ACL event with an empty allow key will permanently \
-							 brick the room for non-tuwunel's as this equates to no servers \
-							 being allowed to participate in this room."
-						))));
-					}
-
-					if acl_content.deny_contains("*") && acl_content.allow_contains("*") {
-						return Err!(Request(BadJson(debug_warn!(
-							?room_id,
-							"Sending an ACL event with a deny and allow key value of \"*\" will \
-							 permanently brick the room for non-tuwunel's as this equates to no \
-							 servers being allowed to participate in this room."
-						))));
-					}
-
-					if acl_content.deny_contains("*")
-						&& !acl_content.is_allowed(services.globals.server_name())
-						&& !acl_content.allow_contains(services.globals.server_name().as_str())
-					{
-						return Err!(Request(BadJson(debug_warn!(
-							?room_id,
-							"Sending an ACL event with a deny key value of \"*\" and without \
-							 your own server name in the allow key will result in you being \
-							 unable to participate in this room."
-						))));
-					}
-
-					if !acl_content.allow_contains("*")
-						&& !acl_content.is_allowed(services.globals.server_name())
-						&& !acl_content.allow_contains(services.globals.server_name().as_str())
-					{
-						return Err!(Request(BadJson(debug_warn!(
-							?room_id,
-							"Sending an ACL event for an allow key without \"*\" and without \
-							 your own server name in the allow key will result in you being \
-							 unable to participate in this room."
-						))));
-					}
-				},
-				| Err(e) => {
-					return Err!(Request(BadJson(debug_warn!(
-						"Room server ACL event is invalid: {e}"
-					))));
-				},
-			}
-		},
-		| StateEventType::RoomEncryption =>
-		// Forbid m.room.encryption if encryption is disabled
-			if !services.config.allow_encryption {
-				return Err!(Request(Forbidden("Encryption is disabled on this homeserver.")));
-			},
-		| StateEventType::RoomJoinRules => {
-			// admin room is a sensitive room, it should not ever be made public
-			if let Ok(admin_room_id) = services.admin.get_admin_room().await {
-				if admin_room_id == room_id {
-					match json.deserialize_as_unchecked::<RoomJoinRulesEventContent>() {
-						| Ok(join_rule) =>
-							if join_rule.join_rule == JoinRule::Public {
-								return Err!(Request(Forbidden(
-									"Admin room is a sensitive room, it cannot be made public"
-								)));
-							},
-						| Err(e) => {
-							return Err!(Request(BadJson(debug_warn!(
-								"Room join rules event is invalid: {e}"
-							))));
-						},
-					}
-				}
-			}
-		},
-		| StateEventType::RoomHistoryVisibility => {
-			// admin room is a sensitive room, it should not ever be made world readable
-			if let Ok(admin_room_id) = services.admin.get_admin_room().await {
-				match json.deserialize_as_unchecked::<RoomHistoryVisibilityEventContent>() {
-					| Ok(visibility_content) => {
-						if admin_room_id == room_id
-							&& visibility_content.history_visibility
-								== HistoryVisibility::WorldReadable
-						{
-							return Err!(Request(Forbidden(
-								"Admin room is a sensitive room, it cannot be made world \
-								 readable (public room history)."
-							)));
-						}
-					},
-					| Err(e) => {
-						return Err!(Request(BadJson(debug_warn!(
-							"Room history visibility event is invalid: {e}"
-						))));
-					},
-				}
-			}
-		},
-		| StateEventType::RoomCanonicalAlias => {
-			match json.deserialize_as_unchecked::<RoomCanonicalAliasEventContent>() {
-				| Ok(canonical_alias_content) => {
-					let mut aliases = canonical_alias_content.alt_aliases.clone();
-
-					if let Some(alias) = canonical_alias_content.alias {
-						aliases.push(alias);
-					}
-
-					for alias in aliases {
-						let (alias_room_id, _servers) = services
-							.alias
-							.resolve_alias(&alias, None)
-							.await
-							.map_err(|e| {
-								err!(Request(BadAlias("Failed resolving alias \"{alias}\": {e}")))
-							})?;
-
-						if alias_room_id != room_id {
-							return Err!(Request(BadAlias(
-								"Room alias {alias} does not belong to room {room_id}"
-							)));
-						}
-					}
-				},
-				| Err(e) => {
-					return Err!(Request(InvalidParam(debug_warn!(
-						"Room canonical alias event is invalid: {e}"
-					))));
-				},
-			}
-		},
-		| StateEventType::RoomMember =>
-			match json.deserialize_as_unchecked::<RoomMemberEventContent>() {
-				| Ok(membership_content) => {
-					let Ok(_state_key) = UserId::parse(state_key) else {
-						return Err!(Request(BadJson(
-							"Membership event has invalid or non-existent state key"
-						)));
-					};
-
-					if let Some(authorising_user) =
-						membership_content.join_authorized_via_users_server
-					{
-						if membership_content.membership != MembershipState::Join {
-							return Err!(Request(BadJson(
-								"join_authorised_via_users_server is only for member joins"
-							)));
-						}
-
-						if !services.globals.user_is_local(&authorising_user) {
-							return Err!(Request(InvalidParam(
-								"Authorising user {authorising_user} does not belong to this \
-								 homeserver"
-							)));
-						}
-
-						services
-							.state_cache
-							.is_joined(&authorising_user, room_id)
-							.map(is_false!())
-							.map(BoolExt::into_result)
-							.map_err(|()| {
-								err!(Request(InvalidParam(
-									"Authorising user {authorising_user} is not in the room. \
-									 They cannot authorise the join."
-								)))
-							})
-							.await?;
-					}
-				},
-				| Err(e) => {
-					return Err!(Request(BadJson(
-						"Membership content must have a valid JSON body with at least a valid \
-						 membership state: {e}"
-					)));
-				},
-			},
-		| _ => (),
-	}
-
-	Ok(())
-}
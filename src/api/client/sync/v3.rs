@@ -61,10 +61,14 @@
 		lazy_loading::{Options, Witness},
 		short::{ShortEventId, ShortStateHash, ShortStateKey},
 	},
+	sync::stream_device_id,
 };
 
 use super::{load_timeline, share_encrypted_room};
-use crate::{Ruma, RumaResponse, client::ignored_filter};
+use crate::{
+	Ruma, RumaResponse,
+	client::{bundle_aggregations, ignored_filter, visibility_filter},
+};
 
 #[derive(Default)]
 struct StateChanges {
@@ -102,15 +106,20 @@ struct StateChanges {
 /// - If there are events in the timeline we send or the user send updated his
 ///   read mark: Notification counts
 /// - EDUs that are active now (read receipts, typing updates, presence)
-/// - TODO: Allow multiple sync streams to support Pantalaimon
+/// - Multiple sync loops may share a device by naming their stream with the
+///   `stream_id` query parameter (e.g. Pantalaimon); each receives a complete
+///   stream of to-device events
 ///
 /// For invited rooms:
 /// - If the user was invited after `since`: A subset of the state of the room
 ///   at the point of the invite
 ///
 /// For left rooms:
-/// - If the user left after `since`: `prev_batch` token, empty state (TODO:
-///   subset of the state at the point of the leave)
+/// - If the user left after `since`: the timeline up to and including the leave
+///   event as far as history visibility allows, the state at the start of that
+///   timeline and `prev_batch` token
+/// - With `include_leave` in the filter: archived rooms on initial sync or when
+///   requested explicitly
 #[tracing::instrument(
 	name = "sync",
 	level = "debug",
@@ -212,6 +221,14 @@ async fn build_sync_events(
 	next_batch: u64,
 ) -> Result<sync_events::v3::Response> {
 	let (sender_user, sender_device) = body.sender();
+	let stream_id = body.stream_id.as_deref();
+	services
+		.sync
+		.check_stream(sender_user, sender_device, stream_id)
+		.await?;
+
+	// Per-device state of the room (lazy loading) is kept for each stream.
+	let stream_device = stream_device_id(sender_device, stream_id);
 
 	let full_state = body.body.full_state;
 	let filter = match body.body.filter.as_ref() {
@@ -232,7 +249,7 @@ async fn build_sync_events(
 			load_joined_room(
 				services,
 				sender_user,
-				sender_device,
+				&stream_device,
 				room_id.clone(),
 				since,
 				next_batch,
@@ -349,16 +366,21 @@ async fn build_sync_events(
 		.users
 		.count_one_time_keys(sender_user, sender_device);
 
-	// Remove all to-device events the device received *last time*
+	let device_unused_fallback_key_types = services
+		.users
+		.unused_fallback_key_types(sender_user, sender_device);
+
+	// Remove all to-device events the device received *last time* on all of its
+	// streams
 	let remove_to_device_events =
 		services
-			.users
-			.remove_to_device_events(sender_user, sender_device, since);
+			.sync
+			.ack_to_device(sender_user, sender_device, stream_id, since);
 
 	let (
 		account_data,
 		keys_changed,
-		device_one_time_keys_count,
+		(device_one_time_keys_count, device_unused_fallback_key_types),
 		((), to_device_events, presence_updates),
 		(
 			(joined_rooms, mut device_list_updates, left_encrypted_users),
@@ -369,7 +391,7 @@ async fn build_sync_events(
 	) = join5(
 		account_data,
 		keys_changed,
-		device_one_time_keys_count,
+		join(device_one_time_keys_count, device_unused_fallback_key_types),
 		join3(remove_to_device_events, to_device_events, presence_updates),
 		join4(joined_rooms, left_rooms, invited_rooms, knocked_rooms),
 	)
@@ -407,8 +429,7 @@ async fn build_sync_events(
 			left: device_list_left.into_iter().collect(),
 		},
 		device_one_time_keys_count,
-		// Fallback keys are not yet supported
-		device_unused_fallback_key_types: None,
+		device_unused_fallback_key_types: Some(device_unused_fallback_key_types),
 		next_batch: next_batch.to_string(),
 		presence: Presence { events: presence_events },
 		rooms: Rooms {
@@ -563,27 +584,81 @@ async fn handle_left_room(
 		return Ok(None);
 	};
 
-	let Ok(left_shortstatehash) = services
+	let Ok(left_pdu_count) = services
+		.timeline
+		.get_pdu_count(&left_event_id)
+		.await
+	else {
+		warn!(event_id = %left_event_id, "Leave event not in timeline of {room_id}");
+		return Ok(None);
+	};
+
+	let timeline_limit: usize = filter
+		.room
+		.timeline
+		.limit
+		.unwrap_or_else(|| uint!(10))
+		.try_into()?;
+
+	// The timeline runs up to and including the leave event; history visibility
+	// decides what the former member may still see.
+	let (timeline_pdus, limited, _) = load_timeline(
+		services,
+		sender_user,
+		room_id,
+		PduCount::Normal(since),
+		Some(left_pdu_count),
+		timeline_limit,
+	)
+	.await?;
+
+	let timeline_pdus: Vec<_> = timeline_pdus
+		.into_iter()
+		.stream()
+		.ready_filter(|(_, pdu)| filter.room.timeline.matches(pdu))
+		.wide_filter_map(|item| ignored_filter(services, item, sender_user))
+		.wide_filter_map(|item| visibility_filter(services, item, sender_user))
+		.wide_then(|item| bundle_aggregations(services, item, sender_user))
+		.collect()
+		.await;
+
+	let prev_batch = timeline_pdus
+		.first()
+		.map_or(left_pdu_count, |(count, _)| *count);
+
+	let leave_in_timeline = timeline_pdus
+		.iter()
+		.any(|(_, pdu)| pdu.event_id == left_event_id);
+
+	// State is given as of the start of the timeline, or at the leave when the
+	// timeline is empty.
+	let state_at = timeline_pdus
+		.first()
+		.map_or(&*left_event_id, |(_, pdu)| pdu.event_id());
+
+	let Ok(state_shortstatehash) = services
 		.state_accessor
-		.pdu_shortstatehash(&left_event_id)
+		.pdu_shortstatehash(state_at)
 		.await
 	else {
-		warn!(event_id = %left_event_id, "Leave event has no state in {room_id}");
+		warn!(event_id = %state_at, "Leave event has no state in {room_id}");
 		return Ok(None);
 	};
 
 	let mut left_state_ids: HashMap<_, _> = services
 		.state_accessor
-		.state_full_ids(left_shortstatehash)
+		.state_full_ids(state_shortstatehash)
 		.collect()
 		.await;
 
-	let leave_shortstatekey = services
-		.short
-		.get_or_create_shortstatekey(&StateEventType::RoomMember, sender_user.as_str())
-		.await;
+	if !leave_in_timeline {
+		let leave_shortstatekey = services
+			.short
+			.get_or_create_shortstatekey(&StateEventType::RoomMember, sender_user.as_str())
+			.await;
 
-	left_state_ids.insert(leave_shortstatekey, left_event_id);
+		left_state_ids.insert(leave_shortstatekey, left_event_id.clone());
+	}
 
 	for (shortstatekey, event_id) in left_state_ids {
 		if full_state || since_state_ids.get(&shortstatekey) != Some(&event_id) {
@@ -608,17 +683,29 @@ async fn handle_left_room(
 				continue;
 			};
 
-			left_state_events.push(pdu.into_format());
+			if filter.room.state.matches(&pdu) {
+				left_state_events.push(pdu.into_format());
+			}
 		}
 	}
 
+	let account_data_events = services
+		.account_data
+		.changes_since(Some(room_id), sender_user, since, Some(next_batch))
+		.ready_filter_map(|e| extract_variant!(e, AnyRawAccountDataEvent::Room))
+		.collect()
+		.await;
+
 	Ok(Some(LeftRoom {
-		account_data: RoomAccountData { events: Vec::new() },
+		account_data: RoomAccountData { events: account_data_events },
 		timeline: Timeline {
-			// TODO: support left timeline events so we dont need to set limited to true
-			limited: true,
-			prev_batch: Some(next_batch.to_string()),
-			events: Vec::new(), // and so we dont need to set this to empty vec
+			limited,
+			prev_batch: Some(prev_batch.to_string()),
+			events: timeline_pdus
+				.into_iter()
+				.map(at!(1))
+				.map(Event::into_format)
+				.collect(),
 		},
 		state: RoomState::Before(StateEvents { events: left_state_events }),
 	}))
@@ -853,6 +940,15 @@ async fn load_joined_room(
 		})
 		.into();
 
+	let thread_counts: OptionFuture<_> = (send_notification_counts
+		&& filter.room.timeline.unread_thread_notifications)
+		.then(|| {
+			services
+				.user
+				.thread_notification_counts(sender_user, room_id)
+		})
+		.into();
+
 	let private_read_event: OptionFuture<_> = last_privateread_update
 		.gt(&since)
 		.then(|| {
@@ -931,6 +1027,7 @@ async fn load_joined_room(
 		.into_iter()
 		.stream()
 		.wide_filter_map(|item| ignored_filter(services, item, sender_user))
+		.wide_then(|item| bundle_aggregations(services, item, sender_user))
 		.map(at!(1))
 		.chain(joined_sender_member.into_iter().stream())
 		.ready_filter(include_in_timeline)
@@ -950,11 +1047,11 @@ async fn load_joined_room(
 		.collect();
 
 	let (
-		(notification_count, highlight_count),
+		(notification_count, highlight_count, thread_counts),
 		((mut device_list_updates, left_encrypted_users), device_updates),
 		(room_events, account_data_events, typing_events, private_read_event),
 	) = join3(
-		join(notification_count, highlight_count),
+		join3(notification_count, highlight_count, thread_counts),
 		join(device_list_updates, device_updates),
 		join4(room_events, account_data_events, typing_events, private_read_event),
 	)
@@ -994,6 +1091,31 @@ async fn load_joined_room(
 		.chain(private_read_event.flatten().into_iter())
 		.collect();
 
+	// With unread thread notifications requested the room counts only cover the
+	// main timeline.
+	let thread_counts = thread_counts.unwrap_or_default();
+	let (notification_count, highlight_count) = thread_counts.values().fold(
+		(notification_count, highlight_count),
+		|(notification_count, highlight_count), counts| {
+			(
+				notification_count
+					.map(|count| count.saturating_sub(ruma_from_u64(counts.notification_count))),
+				highlight_count
+					.map(|count| count.saturating_sub(ruma_from_u64(counts.highlight_count))),
+			)
+		},
+	);
+
+	let unread_thread_notifications = thread_counts
+		.into_iter()
+		.map(|(thread_id, counts)| {
+			(thread_id, UnreadNotificationsCount {
+				highlight_count: Some(ruma_from_u64(counts.highlight_count)),
+				notification_count: Some(ruma_from_u64(counts.notification_count)),
+			})
+		})
+		.collect();
+
 	let joined_room = JoinedRoom {
 		account_data: RoomAccountData { events: account_data_events },
 		ephemeral: Ephemeral { events: edus },
@@ -1012,7 +1134,7 @@ async fn load_joined_room(
 				.collect(),
 		},
 		unread_notifications: UnreadNotificationsCount { highlight_count, notification_count },
-		unread_thread_notifications: BTreeMap::new(),
+		unread_thread_notifications,
 	};
 
 	Ok((joined_room, device_list_updates, left_encrypted_users))
@@ -19,6 +19,7 @@
 	directory::RoomTypeFilter,
 	events::{
 		AnyRawAccountDataEvent, AnySyncEphemeralRoomEvent, StateEventType, TimelineEventType,
+		receipt::SyncReceiptEvent,
 		room::member::{MembershipState, RoomMemberEventContent},
 		typing::TypingEventContent,
 	},
@@ -41,7 +42,7 @@
 use super::share_encrypted_room;
 use crate::{
 	Ruma,
-	client::{DEFAULT_BUMP_TYPES, ignored_filter, sync::load_timeline},
+	client::{DEFAULT_BUMP_TYPES, bundle_aggregations, ignored_filter, sync::load_timeline},
 };
 
 type SyncInfo<'a> = (&'a UserId, &'a DeviceId, u64, &'a sync_events::v5::Request);
@@ -66,6 +67,11 @@ pub async fn sync_events_v5_route(
 
 	let sender_user = body.sender_user();
 	let sender_device = body.sender_device();
+	services
+		.sync
+		.check_stream(sender_user, sender_device, body.conn_id.as_deref())
+		.await?;
+
 	let snake_key = into_snake_key(sender_user, sender_device, body.conn_id.clone());
 	let globalsince = body
 		.pos
@@ -448,50 +454,21 @@ async fn process_rooms<'a, Rooms>(
 			);
 		}
 
-		let last_privateread_update = services
-			.read_receipt
-			.last_privateread_update(sender_user, room_id)
-			.await;
-
-		let private_read_event: OptionFuture<_> = (last_privateread_update > *roomsince)
-			.then(|| {
-				services
-					.read_receipt
-					.private_read_get(room_id, sender_user)
-					.ok()
-			})
-			.into();
-
-		let mut receipts: Vec<Raw<AnySyncEphemeralRoomEvent>> = services
-			.read_receipt
-			.readreceipts_since(room_id, *roomsince, Some(next_batch))
-			.filter_map(async |(read_user, _ts, v)| {
-				services
-					.users
-					.user_is_ignored(read_user, sender_user)
-					.await
-					.or_some(v)
-			})
-			.collect()
-			.await;
-
-		if let Some(private_read_event) = private_read_event.await.flatten() {
-			receipts.push(private_read_event);
-		}
-
-		let receipt_size = receipts.len();
+		let receipts =
+			collect_room_receipts(services, sender_user, room_id, *roomsince, next_batch).await;
 
-		if receipt_size > 0 {
+		let has_receipts = receipts.is_some();
+		if let Some(receipts) = receipts {
 			response
 				.extensions
 				.receipts
 				.rooms
-				.insert(room_id.clone(), pack_receipts(Box::new(receipts.into_iter())));
+				.insert(room_id.clone(), receipts);
 		}
 
 		if *roomsince != 0
 			&& timeline_pdus.is_empty()
-			&& receipt_size == 0
+			&& !has_receipts
 			&& response
 				.extensions
 				.account_data
@@ -525,6 +502,7 @@ async fn process_rooms<'a, Rooms>(
 			.iter()
 			.stream()
 			.filter_map(|item| ignored_filter(services, item.clone(), sender_user))
+			.then(|item| bundle_aggregations(services, item, sender_user))
 			.map(at!(1))
 			.map(Event::into_format)
 			.collect()
@@ -910,7 +888,12 @@ async fn collect_e2ee<'a, Rooms>(
 	}
 
 	Ok(sync_events::v5::response::E2EE {
-		device_unused_fallback_key_types: None,
+		device_unused_fallback_key_types: Some(
+			services
+				.users
+				.unused_fallback_key_types(sender_user, sender_device)
+				.await,
+		),
 
 		device_one_time_keys_count: services
 			.users
@@ -933,16 +916,25 @@ async fn collect_to_device(
 		return None;
 	}
 
+	// Everything up to the position the stream acknowledged was received
+	let since = body
+		.extensions
+		.to_device
+		.since
+		.as_ref()
+		.and_then(|string| string.parse().ok())
+		.unwrap_or(globalsince);
+
 	services
-		.users
-		.remove_to_device_events(sender_user, sender_device, globalsince)
+		.sync
+		.ack_to_device(sender_user, sender_device, body.conn_id.as_deref(), since)
 		.await;
 
 	Some(sync_events::v5::response::ToDevice {
 		next_batch: next_batch.to_string(),
 		events: services
 			.users
-			.get_to_device_events(sender_user, sender_device, None, Some(next_batch))
+			.get_to_device_events(sender_user, sender_device, Some(since), Some(next_batch))
 			.collect()
 			.await,
 	})
@@ -989,12 +981,81 @@ async fn collect_typing_events<'a, Rooms>(
 }
 
 async fn collect_receipts(
-	_services: &Services,
-	(_sender_user, _, _globalsince, _body): SyncInfo<'_>,
-	_next_batch: u64,
+	services: &Services,
+	(sender_user, _, globalsince, body): SyncInfo<'_>,
+	next_batch: u64,
 ) -> sync_events::v5::response::Receipts {
-	sync_events::v5::response::Receipts { rooms: BTreeMap::new() }
-	// TODO: get explicitly requested read receipts
+	let mut receipts = sync_events::v5::response::Receipts { rooms: BTreeMap::new() };
+
+	if !body.extensions.receipts.enabled.unwrap_or(false) {
+		return receipts;
+	}
+
+	if let Some(rooms) = &body.extensions.receipts.rooms {
+		for room in rooms
+			.iter()
+			.filter_map(|erc| extract_variant!(erc, ExtensionRoomConfig::Room))
+		{
+			if !services
+				.state_cache
+				.is_joined(sender_user, room)
+				.await
+			{
+				continue;
+			}
+
+			if let Some(room_receipts) =
+				collect_room_receipts(services, sender_user, room, globalsince, next_batch).await
+			{
+				receipts.rooms.insert(room.clone(), room_receipts);
+			}
+		}
+	}
+
+	receipts
+}
+
+/// Public receipts of users not ignored by the sender along with the sender's
+/// private receipt, packed into one receipt event.
+async fn collect_room_receipts(
+	services: &Services,
+	sender_user: &UserId,
+	room_id: &RoomId,
+	since: u64,
+	next_batch: u64,
+) -> Option<Raw<SyncReceiptEvent>> {
+	let last_privateread_update = services
+		.read_receipt
+		.last_privateread_update(sender_user, room_id)
+		.await;
+
+	let private_read_event: OptionFuture<_> = (last_privateread_update > since)
+		.then(|| {
+			services
+				.read_receipt
+				.private_read_get(room_id, sender_user)
+				.ok()
+		})
+		.into();
+
+	let mut receipts: Vec<Raw<AnySyncEphemeralRoomEvent>> = services
+		.read_receipt
+		.readreceipts_since(room_id, since, Some(next_batch))
+		.filter_map(async |(read_user, _ts, v)| {
+			services
+				.users
+				.user_is_ignored(read_user, sender_user)
+				.await
+				.or_some(v)
+		})
+		.collect()
+		.await;
+
+	if let Some(private_read_event) = private_read_event.await.flatten() {
+		receipts.push(private_read_event);
+	}
+
+	(!receipts.is_empty()).then(|| pack_receipts(receipts.into_iter()))
 }
 
 fn filter_rooms<'a, Rooms>(
@@ -35,12 +35,22 @@ pub async fn get_threads_route(
 		.threads
 		.threads_until(body.sender_user(), &body.room_id, from, &body.include)
 		.take(limit)
-		.try_filter_map(async |(count, pdu)| {
-			Ok(services
+		.try_filter_map(async |(count, mut pdu)| {
+			if !services
 				.state_accessor
 				.user_can_see_event(body.sender_user(), &body.room_id, &pdu.event_id)
 				.await
-				.then_some((count, pdu)))
+			{
+				return Ok(None);
+			}
+
+			services
+				.pdu_metadata
+				.bundle_aggregations(body.sender_user(), &mut pdu)
+				.await
+				.ok();
+
+			Ok(Some((count, pdu)))
 		})
 		.try_collect()
 		.await?;
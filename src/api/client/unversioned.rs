@@ -1,7 +1,8 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::atomic::Ordering};
 
 use axum::{Json, extract::State, response::IntoResponse};
 use futures::StreamExt;
+use http::header;
 use ruma::api::client::discovery::get_supported_versions;
 use tuwunel_core::Result;
 
@@ -46,11 +47,13 @@ pub async fn get_supported_versions_route(
 			("org.matrix.msc2836".to_owned(), true), /* threading/threads (https://github.com/matrix-org/matrix-spec-proposals/pull/2836) */
 			("org.matrix.msc2946".to_owned(), true), /* spaces/hierarchy summaries (https://github.com/matrix-org/matrix-spec-proposals/pull/2946) */
 			("org.matrix.msc3026.busy_presence".to_owned(), true), /* busy presence status (https://github.com/matrix-org/matrix-spec-proposals/pull/3026) */
+			("org.matrix.msc3814".to_owned(), true), /* dehydrated devices (https://github.com/matrix-org/matrix-spec-proposals/pull/3814) */
 			("org.matrix.msc3827".to_owned(), true), /* filtering of /publicRooms by room type (https://github.com/matrix-org/matrix-spec-proposals/pull/3827) */
 			("org.matrix.msc3952_intentional_mentions".to_owned(), true), /* intentional mentions (https://github.com/matrix-org/matrix-spec-proposals/pull/3952) */
 			("org.matrix.msc3575".to_owned(), true), /* sliding sync (https://github.com/matrix-org/matrix-spec-proposals/pull/3575/files#r1588877046) */
 			("org.matrix.msc3916.stable".to_owned(), true), /* authenticated media (https://github.com/matrix-org/matrix-spec-proposals/pull/3916) */
 			("org.matrix.msc4180".to_owned(), true), /* stable flag for 3916 (https://github.com/matrix-org/matrix-spec-proposals/pull/4180) */
+			("org.matrix.msc4140".to_owned(), true), /* delayed events (https://github.com/matrix-org/matrix-spec-proposals/pull/4140) */
 			("uk.tcpip.msc4133".to_owned(), true), /* Extending User Profile API with Key:Value Pairs (https://github.com/matrix-org/matrix-spec-proposals/pull/4133) */
 			("us.cloke.msc4175".to_owned(), true), /* Profile field for user time zone (https://github.com/matrix-org/matrix-spec-proposals/pull/4175) */
 			("org.matrix.simplified_msc3575".to_owned(), true), /* Simplified Sliding sync (https://github.com/matrix-org/matrix-spec-proposals/pull/4186) */
@@ -85,3 +88,41 @@ pub async fn tuwunel_local_user_count(
 		"count": user_count
 	})))
 }
+
+/// # `GET /_tuwunel/metrics`
+///
+/// Tuwunel-specific API exposing request counters in the Prometheus text
+/// exposition format. Only served on listeners with the "metrics" route group.
+pub async fn tuwunel_metrics(State(services): State<crate::State>) -> Result<impl IntoResponse> {
+	let metrics = &services.server.metrics;
+	let counters: [(&str, u64); 4] = [
+		("requests_total", metrics.requests_count.load(Ordering::Relaxed)),
+		(
+			"requests_finished_total",
+			metrics
+				.requests_handle_finished
+				.load(Ordering::Relaxed),
+		),
+		(
+			"requests_active",
+			metrics
+				.requests_handle_active
+				.load(Ordering::Relaxed)
+				.into(),
+		),
+		(
+			"requests_panic_total",
+			metrics
+				.requests_panic
+				.load(Ordering::Relaxed)
+				.into(),
+		),
+	];
+
+	let body: String = counters
+		.iter()
+		.map(|(name, value)| format!("tuwunel_{name} {value}\n"))
+		.collect();
+
+	Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
@@ -5,11 +5,14 @@
 };
 use tuwunel_core::{Err, Result};
 
+use super::rtc_foci;
 use crate::Ruma;
 
 /// # `GET /.well-known/matrix/client`
 ///
 /// Returns the .well-known URL if it is configured, otherwise returns 404.
+///
+/// - Includes the MatrixRTC foci (MSC4143) when any are configured
 pub async fn well_known_client(
 	State(services): State<crate::State>,
 	_body: Ruma<discover_homeserver::Request>,
@@ -23,6 +26,7 @@ pub async fn well_known_client(
 		homeserver: HomeserverInfo { base_url: client_url },
 		identity_server: None,
 		tile_server: None,
+		rtc_foci: rtc_foci(&services),
 	})
 }
 
@@ -5,23 +5,66 @@
 mod response;
 pub mod state;
 
-use std::str::FromStr;
+use std::{collections::BTreeSet, str::FromStr};
 
 use axum::{
 	Router,
 	response::{IntoResponse, Redirect},
-	routing::{any, get, post},
+	routing::{any, get, post, put},
 };
 use http::{Uri, uri};
-use tuwunel_core::{Server, err};
+use ruma::api::{
+	IncomingRequest,
+	client::{message::send_message_event, state::send_state_event},
+};
+use tuwunel_core::{Server, config::RouteGroup, err};
 
 use self::handler::RouterExt;
 pub use self::{args::Args as Ruma, response::RumaResponse, state::State};
 use crate::{client, server};
 
-pub fn build(router: Router<State>, server: &Server) -> Router<State> {
-	let config = &server.config;
-	let mut router = router
+/// Build the router with the route groups enabled for a listener.
+pub fn build(
+	router: Router<State>,
+	server: &Server,
+	routes: &BTreeSet<RouteGroup>,
+) -> Router<State> {
+	let mut router = router;
+	if routes.contains(&RouteGroup::Client) {
+		router = client(router);
+	}
+
+	if routes.contains(&RouteGroup::Media) {
+		router = media(router, server);
+	}
+
+	if routes.contains(&RouteGroup::Federation) {
+		router = federation(router, server);
+	}
+
+	if routes.contains(&RouteGroup::WellKnown) {
+		router = well_known(router, server);
+	}
+
+	// Served where clients may reach it by default: the client URL or the
+	// server name.
+	if routes.contains(&RouteGroup::Client) || routes.contains(&RouteGroup::WellKnown) {
+		router = livekit(router);
+	}
+
+	if routes.contains(&RouteGroup::Admin) {
+		router = admin(router);
+	}
+
+	if routes.contains(&RouteGroup::Metrics) {
+		router = metrics(router);
+	}
+
+	router
+}
+
+fn client(router: Router<State>) -> Router<State> {
+	delayable(router)
         .ruma_route(&client::get_timezone_key_route)
         .ruma_route(&client::get_profile_field_route)
         .ruma_route(&client::set_profile_field_route)
@@ -90,6 +133,7 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::get_backup_keys_route)
 		.ruma_route(&client::set_read_marker_route)
 		.ruma_route(&client::create_receipt_route)
+		.ruma_route(&client::get_read_by_route)
 		.ruma_route(&client::create_typing_event_route)
 		.ruma_route(&client::create_room_route)
 		.ruma_route(&client::redact_event_route)
@@ -116,8 +160,6 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::search_users_route)
 		.ruma_route(&client::get_member_events_route)
 		.ruma_route(&client::get_protocols_route)
-		.ruma_route(&client::send_message_event_route)
-		.ruma_route(&client::send_state_event_for_key_route)
 		.ruma_route(&client::get_state_events_route)
 		.ruma_route(&client::get_state_events_for_key_route)
 		// Ruma doesn't have support for multiple paths for a single endpoint yet, and these routes
@@ -149,18 +191,20 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::get_message_events_route)
 		.ruma_route(&client::search_events_route)
 		.ruma_route(&client::turn_server_route)
+		.ruma_route(&client::rtc_transports_route)
+		.ruma_route(&client::get_delayed_events_route)
+		.ruma_route(&client::update_delayed_event_route)
+		.ruma_route(&client::moderate_space_route)
 		.ruma_route(&client::send_event_to_device_route)
-		.ruma_route(&client::create_content_route)
-		.ruma_route(&client::get_content_thumbnail_route)
-		.ruma_route(&client::get_content_route)
-		.ruma_route(&client::get_content_as_filename_route)
-		.ruma_route(&client::get_media_preview_route)
-		.ruma_route(&client::get_media_config_route)
 		.ruma_route(&client::get_devices_route)
 		.ruma_route(&client::get_device_route)
 		.ruma_route(&client::update_device_route)
 		.ruma_route(&client::delete_device_route)
 		.ruma_route(&client::delete_devices_route)
+		.ruma_route(&client::put_dehydrated_device_route)
+		.ruma_route(&client::get_dehydrated_device_route)
+		.ruma_route(&client::delete_dehydrated_device_route)
+		.ruma_route(&client::get_dehydrated_events_route)
 		.ruma_route(&client::get_tags_route)
 		.ruma_route(&client::update_tag_route)
 		.ruma_route(&client::delete_tag_route)
@@ -181,20 +225,91 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 			"/_matrix/client/unstable/im.nheko.summary/rooms/{room_id_or_alias}/summary",
 			get(client::get_room_summary_legacy)
 		)
-		.ruma_route(&client::well_known_support)
-		.ruma_route(&client::well_known_client)
 		.route("/_tuwunel/server_version", get(client::tuwunel_server_version))
 		.ruma_route(&client::room_initial_sync_route)
-		.route("/client/server.json", get(client::syncv3_client_server_json));
+}
 
-	if config.allow_federation {
+/// Event sending routes answering with a delay id instead of the ruma response
+/// when the event is delayed (MSC4140).
+fn delayable(router: Router<State>) -> Router<State> {
+	let router = send_message_event::v3::Request::METADATA
+		.history
+		.all_paths()
+		.fold(router, |router, path| {
+			router.route(path, put(client::send_message_event_delayable_route))
+		});
+
+	send_state_event::v3::Request::METADATA
+		.history
+		.all_paths()
+		.fold(router, |router, path| {
+			router.route(path, put(client::send_state_event_for_key_delayable_route))
+		})
+}
+
+fn media(router: Router<State>, server: &Server) -> Router<State> {
+	let config = &server.config;
+	let mut router = router
+		.ruma_route(&client::create_content_route)
+		.ruma_route(&client::get_content_thumbnail_route)
+		.ruma_route(&client::get_content_route)
+		.ruma_route(&client::get_content_as_filename_route)
+		.ruma_route(&client::get_media_preview_route)
+		.ruma_route(&client::get_media_config_route);
+
+	if config.allow_legacy_media {
+		router = router
+			.ruma_route(&client::get_media_config_legacy_route)
+			.ruma_route(&client::get_media_preview_legacy_route)
+			.ruma_route(&client::get_content_legacy_route)
+			.ruma_route(&client::get_content_as_filename_legacy_route)
+			.ruma_route(&client::get_content_thumbnail_legacy_route)
+			.route("/_matrix/media/v1/config", get(client::get_media_config_legacy_legacy_route))
+			.route("/_matrix/media/v1/upload", post(client::create_content_legacy_route))
+			.route(
+				"/_matrix/media/v1/preview_url",
+				get(client::get_media_preview_legacy_legacy_route),
+			)
+			.route(
+				"/_matrix/media/v1/download/{server_name}/{media_id}",
+				get(client::get_content_legacy_legacy_route),
+			)
+			.route(
+				"/_matrix/media/v1/download/{server_name}/{media_id}/{file_name}",
+				get(client::get_content_as_filename_legacy_legacy_route),
+			)
+			.route(
+				"/_matrix/media/v1/thumbnail/{server_name}/{media_id}",
+				get(client::get_content_thumbnail_legacy_legacy_route),
+			);
+	} else {
 		router = router
+			.route("/_matrix/media/v1/{*path}", any(legacy_media_disabled))
+			.route("/_matrix/media/v3/config", any(legacy_media_disabled))
+			.route("/_matrix/media/v3/download/{*path}", any(legacy_media_disabled))
+			.route("/_matrix/media/v3/thumbnail/{*path}", any(legacy_media_disabled))
+			.route("/_matrix/media/v3/preview_url", any(redirect_legacy_preview))
+			.route("/_matrix/media/r0/config", any(legacy_media_disabled))
+			.route("/_matrix/media/r0/download/{*path}", any(legacy_media_disabled))
+			.route("/_matrix/media/r0/thumbnail/{*path}", any(legacy_media_disabled))
+			.route("/_matrix/media/r0/preview_url", any(redirect_legacy_preview));
+	}
+
+	router
+}
+
+fn federation(router: Router<State>, server: &Server) -> Router<State> {
+	let config = &server.config;
+	if config.allow_federation {
+		router
 			.ruma_route(&server::get_server_version_route)
 			.route("/_matrix/key/v2/server", get(server::get_server_keys_route))
 			.route(
 				"/_matrix/key/v2/server/{key_id}",
 				get(server::get_server_keys_deprecated_route),
 			)
+			.ruma_route(&server::get_remote_server_keys_route)
+			.ruma_route(&server::get_remote_server_keys_batch_route)
 			.ruma_route(&server::get_public_rooms_route)
 			.ruma_route(&server::get_public_rooms_filtered_route)
 			.ruma_route(&server::send_transaction_message_route)
@@ -220,57 +335,41 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 			.ruma_route(&server::claim_keys_route)
 			.ruma_route(&server::get_openid_userinfo_route)
 			.ruma_route(&server::get_hierarchy_route)
-			.ruma_route(&server::well_known_server)
 			.ruma_route(&server::get_content_route)
 			.ruma_route(&server::get_content_thumbnail_route)
-			.route("/_tuwunel/local_user_count", get(client::tuwunel_local_user_count));
+			.route("/_tuwunel/local_user_count", get(client::tuwunel_local_user_count))
 	} else {
-		router = router
+		router
 			.route("/_matrix/federation/{*path}", any(federation_disabled))
-			.route("/.well-known/matrix/server", any(federation_disabled))
 			.route("/_matrix/key/{*path}", any(federation_disabled))
-			.route("/_tuwunel/local_user_count", any(federation_disabled));
+			.route("/_tuwunel/local_user_count", any(federation_disabled))
 	}
+}
 
-	if config.allow_legacy_media {
-		router = router
-			.ruma_route(&client::get_media_config_legacy_route)
-			.ruma_route(&client::get_media_preview_legacy_route)
-			.ruma_route(&client::get_content_legacy_route)
-			.ruma_route(&client::get_content_as_filename_legacy_route)
-			.ruma_route(&client::get_content_thumbnail_legacy_route)
-			.route("/_matrix/media/v1/config", get(client::get_media_config_legacy_legacy_route))
-			.route("/_matrix/media/v1/upload", post(client::create_content_legacy_route))
-			.route(
-				"/_matrix/media/v1/preview_url",
-				get(client::get_media_preview_legacy_legacy_route),
-			)
-			.route(
-				"/_matrix/media/v1/download/{server_name}/{media_id}",
-				get(client::get_content_legacy_legacy_route),
-			)
-			.route(
-				"/_matrix/media/v1/download/{server_name}/{media_id}/{file_name}",
-				get(client::get_content_as_filename_legacy_legacy_route),
-			)
-			.route(
-				"/_matrix/media/v1/thumbnail/{server_name}/{media_id}",
-				get(client::get_content_thumbnail_legacy_legacy_route),
-			);
+fn well_known(router: Router<State>, server: &Server) -> Router<State> {
+	let config = &server.config;
+	let router = router
+		.ruma_route(&client::well_known_support)
+		.ruma_route(&client::well_known_client)
+		.route("/client/server.json", get(client::syncv3_client_server_json));
+
+	if config.allow_federation {
+		router.ruma_route(&server::well_known_server)
 	} else {
-		router = router
-			.route("/_matrix/media/v1/{*path}", any(legacy_media_disabled))
-			.route("/_matrix/media/v3/config", any(legacy_media_disabled))
-			.route("/_matrix/media/v3/download/{*path}", any(legacy_media_disabled))
-			.route("/_matrix/media/v3/thumbnail/{*path}", any(legacy_media_disabled))
-			.route("/_matrix/media/v3/preview_url", any(redirect_legacy_preview))
-			.route("/_matrix/media/r0/config", any(legacy_media_disabled))
-			.route("/_matrix/media/r0/download/{*path}", any(legacy_media_disabled))
-			.route("/_matrix/media/r0/thumbnail/{*path}", any(legacy_media_disabled))
-			.route("/_matrix/media/r0/preview_url", any(redirect_legacy_preview));
+		router.route("/.well-known/matrix/server", any(federation_disabled))
 	}
+}
 
-	router
+fn livekit(router: Router<State>) -> Router<State> {
+	router.route("/_tuwunel/livekit/sfu/get", post(client::livekit_sfu_get_route))
+}
+
+fn admin(router: Router<State>) -> Router<State> {
+	router.ruma_route(&client::admin_command_route)
+}
+
+fn metrics(router: Router<State>) -> Router<State> {
+	router.route("/_tuwunel/metrics", get(client::tuwunel_metrics))
 }
 
 async fn redirect_legacy_preview(uri: Uri) -> impl IntoResponse {
@@ -1,4 +1,4 @@
-use std::{fmt::Debug, mem, ops::Deref};
+use std::{fmt::Debug, mem, ops::Deref, time::Duration};
 
 use axum::{body::Body, extract::FromRequest};
 use bytes::{BufMut, Bytes, BytesMut};
@@ -37,6 +37,14 @@ pub struct Args<T> {
 	/// Parsed JSON content.
 	/// None when body is not a valid string
 	pub json_body: Option<CanonicalJsonValue>,
+
+	/// Sync stream identifier from the query string; lets several sync loops
+	/// share one device. None for the default stream.
+	pub stream_id: Option<String>,
+
+	/// Delay of a delayed event (MSC4140) from the query string. None when
+	/// the event is sent immediately.
+	pub delay: Option<Duration>,
 }
 
 impl<T> Args<T> {
@@ -118,6 +126,8 @@ async fn from_request(
 			sender_user: auth.sender_user,
 			sender_device: auth.sender_device,
 			appservice_info: auth.appservice_info,
+			stream_id: request.query.stream_id.take(),
+			delay: request.query.delay.map(Duration::from_millis),
 			json_body,
 		})
 	}
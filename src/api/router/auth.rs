@@ -249,8 +249,22 @@ async fn auth_appservice(
 		return Err!(Request(Exclusive("User is not in namespace.")));
 	}
 
+	// Masquerading as one of the user's devices (MSC3202)
+	let sender_device: Option<OwnedDeviceId> = request.query.device_id.as_deref().map(Into::into);
+	if let Some(device_id) = sender_device.as_deref() {
+		if services
+			.users
+			.get_device_metadata(&user_id, device_id)
+			.await
+			.is_err()
+		{
+			return Err!(Request(Forbidden("Device {device_id} does not belong to {user_id}.")));
+		}
+	}
+
 	Ok(Auth {
 		sender_user: Some(user_id),
+		sender_device,
 		appservice_info: Some(*info),
 		..Auth::default()
 	})
@@ -11,6 +11,11 @@
 pub struct QueryParams {
 	pub access_token: Option<String>,
 	pub user_id: Option<String>,
+	#[serde(alias = "org.matrix.msc3202.device_id")]
+	pub device_id: Option<String>,
+	pub stream_id: Option<String>,
+	#[serde(rename = "org.matrix.msc4140.delay")]
+	pub delay: Option<u64>,
 }
 
 pub struct Request {
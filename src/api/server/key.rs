@@ -4,15 +4,33 @@
 };
 
 use axum::{Json, extract::State, response::IntoResponse};
+use futures::StreamExt;
 use ruma::{
-	MilliSecondsSinceUnixEpoch, Signatures,
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OwnedServerSigningKeyId, ServerName,
+	Signatures,
 	api::{
 		OutgoingResponse,
-		federation::discovery::{OldVerifyKey, ServerSigningKeys, get_server_keys},
+		federation::discovery::{
+			OldVerifyKey, ServerSigningKeys, get_remote_server_keys,
+			get_remote_server_keys_batch, get_server_keys,
+		},
 	},
 	serde::Raw,
 };
-use tuwunel_core::{Result, utils::timepoint_from_now};
+use serde_json::value::to_raw_value;
+use tuwunel_core::{
+	Err, Result, debug_warn,
+	utils::{
+		stream::{BroadbandExt, IterStream},
+		timepoint_from_now,
+	},
+};
+use tuwunel_service::Services;
+
+use crate::Ruma;
+
+/// Servers one notary batch request may ask keys for.
+const NOTARY_BATCH_MAX: usize = 256;
 
 /// # `GET /_matrix/key/v2/server`
 ///
@@ -20,11 +38,95 @@
 ///
 /// - Matrix does not support invalidating public keys, so the key returned by
 ///   this will be valid forever.
+/// - Keys retired by a rotation are listed in `old_verify_keys` with the time
+///   they expired.
 // Response type for this endpoint is Json because we need to calculate a
 // signature for the response
 pub async fn get_server_keys_route(
 	State(services): State<crate::State>,
 ) -> Result<impl IntoResponse> {
+	Ok(Json(own_server_keys(&services).await?))
+}
+
+/// # `GET /_matrix/key/v2/query/{serverName}`
+///
+/// Gets the public signing keys of another server as a notary.
+///
+/// - Keys are counter-signed by this server
+/// - Keys not valid until `minimum_valid_until_ts` are fetched from the origin
+pub async fn get_remote_server_keys_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_remote_server_keys::v2::Request>,
+) -> Result<get_remote_server_keys::v2::Response> {
+	let server_keys = notary_keys(&services, &body.server_name, &[], body.minimum_valid_until_ts)
+		.await
+		.into_iter()
+		.collect();
+
+	Ok(get_remote_server_keys::v2::Response::new(server_keys))
+}
+
+/// # `POST /_matrix/key/v2/query`
+///
+/// Gets the public signing keys of several servers as a notary.
+///
+/// - Keys are counter-signed by this server
+/// - Keys missing the requested key ids or not valid until the greatest
+///   `minimum_valid_until_ts` of a server's criteria are fetched from the
+///   origin
+pub async fn get_remote_server_keys_batch_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_remote_server_keys_batch::v2::Request>,
+) -> Result<get_remote_server_keys_batch::v2::Response> {
+	if body.server_keys.len() > NOTARY_BATCH_MAX {
+		return Err!(Request(InvalidParam(
+			"Too many servers requested; the limit is {NOTARY_BATCH_MAX}."
+		)));
+	}
+
+	let server_keys = body
+		.server_keys
+		.iter()
+		.stream()
+		.broad_filter_map(async |(server_name, criteria)| {
+			let key_ids: Vec<_> = criteria.keys().cloned().collect();
+			let minimum_valid_until_ts = criteria
+				.values()
+				.filter_map(|criteria| criteria.minimum_valid_until_ts)
+				.max()
+				.unwrap_or_else(MilliSecondsSinceUnixEpoch::now);
+
+			notary_keys(&services, server_name, &key_ids, minimum_valid_until_ts).await
+		})
+		.collect()
+		.await;
+
+	Ok(get_remote_server_keys_batch::v2::Response::new(server_keys))
+}
+
+async fn notary_keys(
+	services: &Services,
+	server_name: &ServerName,
+	key_ids: &[OwnedServerSigningKeyId],
+	minimum_valid_until_ts: MilliSecondsSinceUnixEpoch,
+) -> Option<Raw<ServerSigningKeys>> {
+	let keys = if services.globals.server_is_ours(server_name) {
+		own_server_keys(services).await
+	} else {
+		services
+			.server_keys
+			.notary_keys(server_name, key_ids, Some(minimum_valid_until_ts))
+			.await
+	};
+
+	keys.and_then(|keys| to_raw_value(&keys).map_err(Into::into))
+		.map(Raw::from_json)
+		.inspect_err(|e| debug_warn!(%server_name, "Not serving keys as notary: {e}"))
+		.ok()
+}
+
+/// Our own signing keys, signed by the active key.
+async fn own_server_keys(services: &Services) -> Result<CanonicalJsonObject> {
 	let server_name = services.globals.server_name();
 	let active_key_id = services.server_keys.active_key_id();
 	let mut all_keys = services
@@ -36,9 +138,16 @@ pub async fn get_server_keys_route(
 		.remove_entry(active_key_id)
 		.expect("active verify_key is missing");
 
+	let mut retired = services.server_keys.old_verify_keys().await;
 	let old_verify_keys = all_keys
 		.into_iter()
-		.map(|(id, key)| (id, OldVerifyKey::new(expires_ts(), key.key)))
+		.map(|(id, key)| {
+			let old = retired
+				.remove(&id)
+				.unwrap_or_else(|| OldVerifyKey::new(expires_ts(), key.key));
+
+			(id, old)
+		})
 		.collect();
 
 	let server_key = ServerSigningKeys {
@@ -57,7 +166,7 @@ pub async fn get_server_keys_route(
 
 	services.server_keys.sign_json(&mut response)?;
 
-	Ok(Json(response))
+	Ok(response)
 }
 
 fn valid_until_ts() -> MilliSecondsSinceUnixEpoch {
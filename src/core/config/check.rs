@@ -2,9 +2,13 @@
 
 use either::Either;
 use figment::Figment;
+use ruma::UserId;
 
 use super::DEPRECATED_KEYS;
-use crate::{Config, Err, Result, Server, debug, debug_info, debug_warn, error, warn};
+use crate::{
+	Config, Err, Result, Server, debug, debug_info, debug_warn, error,
+	server::SERVER_USER_LOCALPART, warn,
+};
 
 /// Performs check() with additional checks specific to reloading old config
 /// with new config.
@@ -72,6 +76,53 @@ pub fn check(config: &Config) -> Result {
 		warn!("Configuration item `listening` is set to `false`. Cannot hear anyone.");
 	}
 
+	for listener in &config.listener {
+		let name = listener.name();
+		if cfg!(not(unix)) && listener.unix_socket_path.is_some() {
+			return Err!(Config(
+				"listener.unix_socket_path",
+				"UNIX socket support is only available on *nix platforms (listener {name:?})."
+			));
+		}
+
+		if listener.unix_socket_path.is_none() && listener.port.is_empty() {
+			return Err!(Config(
+				"listener.port",
+				"Listener {name:?} requires either a port or a unix_socket_path."
+			));
+		}
+
+		if listener.unix_socket_path.is_some() && listener.is_tls() {
+			return Err!(Config(
+				"listener.tls",
+				"Listener {name:?} cannot serve TLS on a UNIX socket."
+			));
+		}
+
+		if listener.tls_certs.is_some() != listener.tls_key.is_some() {
+			return Err!(Config(
+				"listener.tls_key",
+				"Listener {name:?} requires both tls_certs and tls_key."
+			));
+		}
+
+		if listener.tls
+			&& listener.tls_certs.is_none()
+			&& config.tls.certs.is_none()
+			&& config.tls.acme_domains.is_empty()
+		{
+			return Err!(Config(
+				"listener.tls",
+				"Listener {name:?} uses the global TLS certificate but [global.tls] has no \
+				 certs or acme_domains."
+			));
+		}
+
+		if listener.routes.is_empty() {
+			warn!("Listener {name:?} has no routes configured and will only respond with 404.");
+		}
+	}
+
 	if config.unix_socket_path.is_none() {
 		config.get_bind_addrs().iter().for_each(|addr| {
 			use std::path::Path;
@@ -121,6 +172,54 @@ pub fn check(config: &Config) -> Result {
 		));
 	}
 
+	if !config.tls.acme_domains.is_empty() {
+		if config.tls.certs.is_some() || config.tls.key.is_some() {
+			return Err!(Config(
+				"tls.acme_domains",
+				"ACME cannot be used together with static tls.certs or tls.key files."
+			));
+		}
+
+		if !matches!(config.tls.acme_challenge.as_str(), "tls-alpn-01" | "http-01") {
+			return Err!(Config(
+				"tls.acme_challenge",
+				"Unsupported ACME challenge type. Use \"tls-alpn-01\" or \"http-01\"."
+			));
+		}
+	}
+
+	if config.database_backup_restore.is_some() {
+		if config.database_backup_path.is_none() {
+			return Err!(Config(
+				"database_backup_restore",
+				"Restoring a backup requires database_backup_path to be set."
+			));
+		}
+
+		if config.rocksdb_read_only || config.rocksdb_secondary {
+			return Err!(Config(
+				"database_backup_restore",
+				"Restoring a backup cannot be combined with read-only or secondary modes."
+			));
+		}
+	}
+
+	if let Some(localpart) = &config.server_notices_localpart {
+		if localpart == SERVER_USER_LOCALPART {
+			return Err!(Config(
+				"server_notices_localpart",
+				"The server notices user must be distinct from the server user."
+			));
+		}
+
+		if let Err(e) = UserId::parse_with_server_name(localpart.as_str(), &config.server_name) {
+			return Err!(Config(
+				"server_notices_localpart",
+				"{localpart:?} is not a valid user localpart: {e}"
+			));
+		}
+	}
+
 	// yeah, unless the user built a debug build hopefully for local testing only
 	if cfg!(not(debug_assertions)) && config.server_name == "your.server.name" {
 		return Err!(Config(
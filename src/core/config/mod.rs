@@ -16,7 +16,7 @@
 pub use figment::{Figment, value::Value as FigmentValue};
 use regex::RegexSet;
 use ruma::{
-	OwnedRoomOrAliasId, OwnedServerName, OwnedUserId, RoomVersionId,
+	OwnedMxcUri, OwnedRoomOrAliasId, OwnedServerName, OwnedUserId, RoomVersionId,
 	api::client::discovery::discover_support::ContactRole,
 };
 use serde::{Deserialize, de::IgnoredAny};
@@ -57,7 +57,7 @@
 ### https://tuwunel.chat/configuration.html
 "#,
 	ignore = "catchall well_known tls blurhashing allow_invalid_tls_certificates ldap jwt \
-	          appservice"
+	          matrix_rtc appservice listener"
 )]
 pub struct Config {
 	/// The server_name is the pretty name of this server. It is used as a
@@ -145,6 +145,10 @@ pub struct Config {
 	#[serde(default = "default_unix_socket_perms")]
 	pub unix_socket_perms: u32,
 
+	// external structure; separate section
+	#[serde(default)]
+	pub listener: Vec<ListenerConfig>,
+
 	/// tuwunel supports online database backups using RocksDB's Backup engine
 	/// API. To use this, set a database backup path that tuwunel can write
 	/// to.
@@ -162,6 +166,19 @@ pub struct Config {
 	#[serde(default = "default_database_backups_to_keep")]
 	pub database_backups_to_keep: i16,
 
+	/// Restore a backup from "database_backup_path" into "database_path" at
+	/// startup before the database is opened. The value is either the numeric
+	/// id of a backup (see `!admin server list-backups`) or "latest". The
+	/// backup is verified prior to restoring. Any existing database files in
+	/// "database_path" are replaced.
+	///
+	/// This is usually set using the `--restore-backup` command-line argument
+	/// rather than in the config file; leaving it set will restore the backup
+	/// on every startup.
+	///
+	/// example: "latest"
+	pub database_backup_restore: Option<String>,
+
 	/// Set this to any float value to multiply tuwunel's in-memory LRU caches
 	/// with such as "auth_chain_cache_capacity".
 	///
@@ -252,6 +269,13 @@ pub struct Config {
 	#[serde(default = "default_roomid_spacehierarchy_cache_capacity")]
 	pub roomid_spacehierarchy_cache_capacity: u32,
 
+	/// Capacity of the cache of where former members left a room, consulted
+	/// for every event shown to them under shared history visibility.
+	///
+	/// default: varies by system
+	#[serde(default = "default_userroomid_leftcount_cache_capacity")]
+	pub userroomid_leftcount_cache_capacity: u32,
+
 	/// Minimum timeout a client can request for long-polling sync. Requests
 	/// will be clamped up to this value if smaller.
 	///
@@ -664,6 +688,21 @@ pub struct Config {
 	#[serde(default = "true_fn")]
 	pub allow_room_creation: bool,
 
+	/// Maximum delay in seconds clients may schedule delayed events (MSC4140)
+	/// with, such as MatrixRTC call memberships and scheduled messages. Set
+	/// to 0 to disable delayed events.
+	///
+	/// default: 86400
+	#[serde(default = "default_max_event_delay")]
+	pub max_event_delay: u64,
+
+	/// Maximum number of delayed events (MSC4140) a user may have scheduled
+	/// at once.
+	///
+	/// default: 100
+	#[serde(default = "default_max_delayed_events_per_user")]
+	pub max_delayed_events_per_user: usize,
+
 	/// Set to false to disable users from joining or creating room versions
 	/// that aren't officially supported by tuwunel. Unstable room versions may
 	/// have flawed specifications or our implementation may be non-conforming.
@@ -762,8 +801,8 @@ pub struct Config {
 	/// Servers listed here will be used to gather public keys of other servers
 	/// (notary trusted key servers).
 	///
-	/// Currently, tuwunel doesn't support inbound batched key requests, so
-	/// this list should only contain other Synapse servers.
+	/// Other tuwunel servers can be listed too; every server with federation
+	/// enabled serves cached keys of other servers as a notary.
 	///
 	/// example: ["matrix.org", "tchncs.de"]
 	///
@@ -940,6 +979,9 @@ pub struct Config {
 	/// registered users join. The rooms specified must be rooms that you have
 	/// joined at least once on the server, and must be public.
 	///
+	/// Spaces are joined along with their public children and the restricted
+	/// children open to members of the space, including those of subspaces.
+	///
 	/// example: ["#tuwunel:tuwunel.chat",
 	/// "!eoIzvAvVwY23LPDay8:tuwunel.chat"]
 	///
@@ -947,6 +989,11 @@ pub struct Config {
 	#[serde(default = "Vec::new")]
 	pub auto_join_rooms: Vec<OwnedRoomOrAliasId>,
 
+	/// Only join the children of spaces in `auto_join_rooms` which the space
+	/// marks as suggested.
+	#[serde(default)]
+	pub auto_join_suggested_only: bool,
+
 	/// Config option to automatically deactivate the account of any user who
 	/// attempts to join a:
 	/// - banned room
@@ -1463,6 +1510,13 @@ pub struct Config {
 	#[serde(default, with = "serde_regex")]
 	pub forbidden_remote_room_directory_server_names: RegexSet,
 
+	/// Time in seconds the room directories of other servers are cached for
+	/// when our users browse them. Set to 0 to always ask the remote server.
+	///
+	/// default: 300
+	#[serde(default = "default_remote_directory_cache_ttl")]
+	pub remote_directory_cache_ttl: u64,
+
 	#[allow(clippy::doc_link_with_quotes)]
 	/// Vector list of IPv4 and IPv6 CIDR ranges / subnets *in quotes* that you
 	/// do not want tuwunel to send outbound requests to. Defaults to
@@ -1700,6 +1754,13 @@ pub struct Config {
 	#[serde(default = "default_admin_log_capture")]
 	pub admin_log_capture: String,
 
+	/// Also emit the audit record of every admin command as an event with the
+	/// `admin_audit` log target, so they can be routed separately from other
+	/// logs. Records are always kept in the database and can be searched with
+	/// `!admin server audit-log`.
+	#[serde(default)]
+	pub admin_audit_log: bool,
+
 	/// The default room tag to apply on the admin room.
 	///
 	/// On some clients like Element, the room tag "m.server_notice" is a
@@ -1711,6 +1772,32 @@ pub struct Config {
 	#[serde(default = "default_admin_room_tag")]
 	pub admin_room_tag: String,
 
+	/// Localpart of the system user which delivers server notices to users,
+	/// e.g. "notices" for @notices:example.com. Each user receives notices in
+	/// their own room with this user, tagged "m.server_notice" so clients
+	/// display it as a system alerts room. Server notices are disabled when
+	/// this is unset.
+	///
+	/// example: "notices"
+	pub server_notices_localpart: Option<String>,
+
+	/// Display name of the server notices user.
+	///
+	/// default: "Server Notices"
+	#[serde(default = "default_server_notices_displayname")]
+	pub server_notices_displayname: String,
+
+	/// Avatar of the server notices user as an mxc:// URI.
+	///
+	/// example: "mxc://example.com/abcdef"
+	pub server_notices_avatar_url: Option<OwnedMxcUri>,
+
+	/// Name given to newly created server notices rooms.
+	///
+	/// default: "Server Notices"
+	#[serde(default = "default_server_notices_displayname")]
+	pub server_notices_room_name: String,
+
 	/// Whether to grant the first user to register admin privileges by joining
 	/// them to the admin room. Note that technically the next user to register
 	/// when the admin room is empty (or only contains the server-user) is
@@ -1969,6 +2056,10 @@ pub struct Config {
 	#[serde(default)]
 	pub jwt: JwtConfig,
 
+	// external structure; separate section
+	#[serde(default)]
+	pub matrix_rtc: MatrixRtcConfig,
+
 	// external structure; separate section
 	#[serde(default)]
 	pub appservice: BTreeMap<String, AppService>,
@@ -1995,6 +2086,191 @@ pub struct TlsConfig {
 	/// Whether to listen and allow for HTTP and HTTPS connections (insecure!)
 	#[serde(default)]
 	pub dual_protocol: bool,
+
+	/// Domain names to obtain a certificate for automatically using ACME
+	/// (e.g. Let's Encrypt). Setting this enables ACME and is mutually
+	/// exclusive with `certs` and `key`. The certificate is renewed in the
+	/// background and swapped in without dropping connections.
+	///
+	/// example: ["matrix.example.com"]
+	///
+	/// default: []
+	#[serde(default)]
+	pub acme_domains: Vec<String>,
+
+	/// ACME directory URL of the certificate authority. For testing against a
+	/// local Pebble instance use e.g. "https://localhost:14000/dir" and set
+	/// the SSL_CERT_FILE environment variable to Pebble's root certificate.
+	///
+	/// default: "https://acme-v02.api.letsencrypt.org/directory"
+	#[serde(default = "default_acme_directory")]
+	pub acme_directory: Url,
+
+	/// Contact URIs for the ACME account.
+	///
+	/// example: ["mailto:admin@example.com"]
+	///
+	/// default: []
+	#[serde(default)]
+	pub acme_contact: Vec<String>,
+
+	/// ACME challenge type to use: "tls-alpn-01" is answered by the TLS
+	/// listener itself; "http-01" requires an additional plaintext listener on
+	/// `acme_http01_port`.
+	///
+	/// default: "tls-alpn-01"
+	#[serde(default = "default_acme_challenge")]
+	pub acme_challenge: String,
+
+	/// Port for the plaintext listener answering "http-01" challenges. The
+	/// listener binds the same addresses as the TLS listener.
+	///
+	/// default: 80
+	#[serde(default = "default_acme_http01_port")]
+	pub acme_http01_port: u16,
+
+	/// Directory storing the ACME account and certificate. Defaults to a
+	/// directory beside the database path, named after it with an "-acme"
+	/// suffix.
+	///
+	/// example: "/var/lib/tuwunel-acme"
+	pub acme_state_dir: Option<PathBuf>,
+
+	/// Renew the certificate when it expires in fewer than this many days.
+	///
+	/// default: 30
+	#[serde(default = "default_acme_renew_days")]
+	pub acme_renew_days: u64,
+}
+
+/// Listener definitions. When any `[[global.listener]]` section is present the
+/// top-level `address`, `port`, `unix_socket_path` and static TLS settings are
+/// not used to listen; each listener is configured independently instead.
+#[derive(Clone, Debug, Deserialize)]
+#[config_example_generator(
+	filename = "tuwunel-example.toml",
+	section = "[global.listener]"
+)]
+pub struct ListenerConfig {
+	/// Name identifying the listener in logs.
+	///
+	/// example: "internal"
+	pub name: Option<String>,
+
+	/// The IP address(es) to listen on. Defaults to the top-level `address`.
+	///
+	/// example: ["127.0.0.1", "::1"]
+	///
+	/// default: []
+	#[serde(default)]
+	pub address: Vec<IpAddr>,
+
+	/// The port(s) to listen on. Required unless `unix_socket_path` is set.
+	///
+	/// example: [8448]
+	///
+	/// default: []
+	#[serde(default)]
+	pub port: Vec<u16>,
+
+	/// Listen on this UNIX socket instead of addresses and ports.
+	///
+	/// example: "/run/tuwunel/admin.sock"
+	pub unix_socket_path: Option<PathBuf>,
+
+	/// The permissions (in octal) to create the UNIX socket with.
+	///
+	/// default: 660
+	#[serde(default = "default_unix_socket_perms")]
+	pub unix_socket_perms: u32,
+
+	/// Serve TLS using the certificate configured in `[global.tls]` (either
+	/// static files or ACME).
+	#[serde(default)]
+	pub tls: bool,
+
+	/// Serve TLS using this certificate file instead of `[global.tls]`.
+	///
+	/// example: "/path/to/my/certificate.crt"
+	pub tls_certs: Option<String>,
+
+	/// Private key for `tls_certs`.
+	///
+	/// example: "/path/to/my/certificate.key"
+	pub tls_key: Option<String>,
+
+	/// Whether to accept HTTP and HTTPS connections on this listener
+	/// (insecure!)
+	#[serde(default)]
+	pub tls_dual_protocol: bool,
+
+	/// Route groups served by this listener. Available groups are "client",
+	/// "federation", "media", "admin", "metrics" and "well_known". Requests
+	/// for routes outside these groups receive 404. All groups except
+	/// "admin" and "metrics" are served by default.
+	///
+	/// example: ["federation", "media", "well_known"]
+	#[serde(default = "default_listener_routes")]
+	pub routes: BTreeSet<RouteGroup>,
+}
+
+/// Groups of routes which can be enabled per-listener.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteGroup {
+	/// Client-Server API excluding media.
+	Client,
+
+	/// Server-Server API including the key server and federation media.
+	Federation,
+
+	/// Client media repository, including legacy unauthenticated media.
+	Media,
+
+	/// Administrative HTTP API.
+	Admin,
+
+	/// Server metrics.
+	Metrics,
+
+	/// `/.well-known/matrix/*` and related discovery documents.
+	WellKnown,
+}
+
+impl RouteGroup {
+	pub const ALL: [Self; 6] = [
+		Self::Client,
+		Self::Federation,
+		Self::Media,
+		Self::Admin,
+		Self::Metrics,
+		Self::WellKnown,
+	];
+	/// Groups served by listeners which don't configure their routes. Admin and
+	/// metrics must be enabled explicitly.
+	pub const DEFAULT: [Self; 4] = [Self::Client, Self::Federation, Self::Media, Self::WellKnown];
+}
+
+impl ListenerConfig {
+	/// Display name for logging.
+	#[must_use]
+	pub fn name(&self) -> &str { self.name.as_deref().unwrap_or("default") }
+
+	/// Whether this listener serves TLS.
+	#[must_use]
+	pub fn is_tls(&self) -> bool { self.tls || self.tls_certs.is_some() }
+
+	#[must_use]
+	pub fn get_bind_addrs(&self) -> Vec<SocketAddr> {
+		self.address
+			.iter()
+			.flat_map(|host| {
+				self.port
+					.iter()
+					.map(move |port| SocketAddr::new(*host, *port))
+			})
+			.collect()
+	}
 }
 
 #[allow(rustdoc::broken_intra_doc_links, rustdoc::bare_urls)]
@@ -2243,6 +2519,66 @@ pub struct JwtConfig {
 	pub validate_signature: bool,
 }
 
+#[derive(Clone, Debug, Default, Deserialize)]
+#[config_example_generator(
+	filename = "tuwunel-example.toml",
+	section = "global.matrix_rtc"
+)]
+pub struct MatrixRtcConfig {
+	/// URLs of LiveKit authorization services (such as lk-jwt-service)
+	/// advertised to MatrixRTC clients like Element Call as foci, in
+	/// `/.well-known/matrix/client` and the MSC4143 `rtc/transports` endpoint.
+	///
+	/// example: ["https://livekit-jwt.example.com"]
+	///
+	/// default: []
+	#[serde(default)]
+	pub foci: Vec<Url>,
+
+	/// Websocket URL of a LiveKit SFU. Setting this together with
+	/// `livekit_key` and `livekit_secret` enables the built-in LiveKit
+	/// authorization service, which is advertised ahead of `foci` at
+	/// `livekit_service_url` and issues tokens to users joined to the call's
+	/// room. This replaces running a separate lk-jwt-service.
+	///
+	/// example: "wss://livekit.example.com"
+	pub livekit_url: Option<Url>,
+
+	/// Public URL of the built-in LiveKit authorization service advertised to
+	/// clients. The service is served at `/_tuwunel/livekit` by listeners
+	/// serving the "client" or "well_known" routes. Defaults to that path
+	/// under the `[global.well_known]` client URL, or under
+	/// `https://<server_name>` when that is not set.
+	///
+	/// example: "https://matrix.example.com/_tuwunel/livekit"
+	pub livekit_service_url: Option<Url>,
+
+	/// LiveKit API key the built-in authorization service issues tokens
+	/// with.
+	pub livekit_key: Option<String>,
+
+	/// LiveKit API secret the built-in authorization service signs tokens
+	/// with.
+	///
+	/// display: sensitive
+	pub livekit_secret: Option<String>,
+
+	/// Lifetime of the LiveKit tokens issued by the built-in authorization
+	/// service, in seconds.
+	///
+	/// default: 3600
+	#[serde(default = "default_livekit_token_ttl")]
+	pub livekit_token_ttl: u64,
+}
+
+impl MatrixRtcConfig {
+	/// Whether the built-in LiveKit authorization service is configured.
+	#[must_use]
+	pub fn livekit_enabled(&self) -> bool {
+		self.livekit_url.is_some() && self.livekit_key.is_some() && self.livekit_secret.is_some()
+	}
+}
+
 #[derive(Clone, Debug, Default, Deserialize)]
 #[config_example_generator(
 	filename = "tuwunel-example.toml",
@@ -2312,6 +2648,15 @@ pub struct AppService {
 	/// default: false
 	#[serde(default)]
 	pub device_management: bool,
+
+	/// Whether the application service wants device list changes, one-time
+	/// key counts and unused fallback key types of its users pushed in
+	/// transactions, as part of MSC3202. Masquerading as a device with the
+	/// `device_id` query parameter is allowed regardless.
+	///
+	/// default: false
+	#[serde(default, alias = "org.matrix.msc3202")]
+	pub msc3202: bool,
 }
 
 impl From<AppService> for ruma::api::appservice::Registration {
@@ -2426,7 +2771,40 @@ pub fn new(raw_config: &Figment) -> Result<Self> {
 		Ok(config)
 	}
 
+	/// Listeners to serve. Without any `[[global.listener]]` sections this is
+	/// a single listener synthesized from the top-level settings serving the
+	/// default routes.
 	#[must_use]
+	pub fn get_listeners(&self) -> Vec<ListenerConfig> {
+		if !self.listener.is_empty() {
+			return self
+				.listener
+				.iter()
+				.cloned()
+				.map(|mut listener| {
+					if listener.address.is_empty() {
+						listener.address = self.get_bind_hosts();
+					}
+
+					listener
+				})
+				.collect();
+		}
+
+		vec![ListenerConfig {
+			name: None,
+			address: self.get_bind_hosts(),
+			port: self.get_bind_ports(),
+			unix_socket_path: self.unix_socket_path.clone(),
+			unix_socket_perms: self.unix_socket_perms,
+			tls: self.tls.certs.is_some() || !self.tls.acme_domains.is_empty(),
+			tls_certs: None,
+			tls_key: None,
+			tls_dual_protocol: self.tls.dual_protocol,
+			routes: default_listener_routes(),
+		}]
+	}
+
 	pub fn get_bind_addrs(&self) -> Vec<SocketAddr> {
 		let mut addrs = Vec::with_capacity(
 			self.get_bind_hosts()
@@ -2471,6 +2849,8 @@ fn default_port() -> ListeningPort { ListeningPort { ports: Left(8008) } }
 
 fn default_unix_socket_perms() -> u32 { 660 }
 
+fn default_listener_routes() -> BTreeSet<RouteGroup> { RouteGroup::DEFAULT.into() }
+
 fn default_database_backups_to_keep() -> i16 { 1 }
 
 fn default_db_write_buffer_capacity_mb() -> f64 { 48.0 + parallelism_scaled_f64(4.0) }
@@ -2513,6 +2893,8 @@ fn default_stateinfo_cache_capacity() -> u32 { parallelism_scaled_u32(100) }
 
 fn default_roomid_spacehierarchy_cache_capacity() -> u32 { parallelism_scaled_u32(1000) }
 
+fn default_userroomid_leftcount_cache_capacity() -> u32 { parallelism_scaled_u32(1000) }
+
 fn default_dns_cache_entries() -> u32 { 32768 }
 
 fn default_dns_min_ttl() -> u64 { 60 * 180 }
@@ -2543,6 +2925,8 @@ fn default_well_known_conn_timeout() -> u64 { 6 }
 
 fn default_well_known_timeout() -> u64 { 10 }
 
+fn default_livekit_token_ttl() -> u64 { 3600 }
+
 fn default_federation_timeout() -> u64 { 25 }
 
 fn default_federation_idle_timeout() -> u64 { 25 }
@@ -2599,10 +2983,16 @@ fn default_notification_push_path() -> String { "/_matrix/push/v1/notify".to_own
 
 fn default_openid_token_ttl() -> u64 { 60 * 60 }
 
+fn default_max_event_delay() -> u64 { 60 * 60 * 24 }
+
+fn default_max_delayed_events_per_user() -> usize { 100 }
+
 fn default_login_token_ttl() -> u64 { 2 * 60 * 1000 }
 
 fn default_turn_ttl() -> u64 { 60 * 60 * 24 }
 
+fn default_remote_directory_cache_ttl() -> u64 { 5 * 60 }
+
 fn default_presence_idle_timeout_s() -> u64 { 5 * 60 }
 
 fn default_presence_offline_timeout_s() -> u64 { 30 * 60 }
@@ -2707,6 +3097,8 @@ fn default_admin_log_capture() -> String {
 
 fn default_admin_room_tag() -> String { "m.server_notice".to_owned() }
 
+fn default_server_notices_displayname() -> String { "Server Notices".to_owned() }
+
 #[allow(clippy::as_conversions, clippy::cast_precision_loss)]
 fn parallelism_scaled_f64(val: f64) -> f64 { val * (sys::available_parallelism() as f64) }
 
@@ -2757,6 +3149,16 @@ fn default_blurhash_x_component() -> u32 { 4 }
 
 fn default_blurhash_y_component() -> u32 { 3 }
 
+fn default_acme_directory() -> Url {
+	Url::parse("https://acme-v02.api.letsencrypt.org/directory").expect("valid url")
+}
+
+fn default_acme_challenge() -> String { "tls-alpn-01".to_owned() }
+
+fn default_acme_http01_port() -> u16 { 80 }
+
+fn default_acme_renew_days() -> u64 { 30 }
+
 fn default_ldap_search_filter() -> String { "(objectClass=*)".to_owned() }
 
 fn default_ldap_uid_attribute() -> String { String::from("uid") }
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 
 use ruma::MilliSecondsSinceUnixEpoch;
+use serde::Serialize;
 use serde_json::value::{RawValue as RawJsonValue, Value as JsonValue, to_raw_value};
 
 use super::Pdu;
@@ -47,8 +48,13 @@ pub fn add_age(&mut self) -> Result {
 	Ok(())
 }
 
+/// Sets the bundled aggregation of one relation type in the event's
+/// `unsigned.m.relations`.
 #[implement(Pdu)]
-pub fn add_relation(&mut self, name: &str, pdu: Option<&Pdu>) -> Result {
+pub fn add_relation<T>(&mut self, name: &str, relation: &T) -> Result
+where
+	T: Serialize + ?Sized,
+{
 	use serde_json::Map;
 
 	let mut unsigned: Map<String, JsonValue> = self
@@ -58,16 +64,13 @@ pub fn add_relation(&mut self, name: &str, pdu: Option<&Pdu>) -> Result {
 		.map_or_else(|| Ok(Map::new()), serde_json::from_str)
 		.map_err(|e| err!(Database("Invalid unsigned in pdu event: {e}")))?;
 
-	let pdu = pdu
-		.map(serde_json::to_value)
-		.transpose()?
-		.unwrap_or_else(|| JsonValue::Object(Map::new()));
+	let relation = serde_json::to_value(relation)?;
 
 	unsigned
 		.entry("m.relations")
 		.or_insert(JsonValue::Object(Map::new()))
 		.as_object_mut()
-		.map(|object| object.insert(name.to_owned(), pdu));
+		.map(|object| object.insert(name.to_owned(), relation));
 
 	self.unsigned = Some(to_raw_value(&unsigned)?);
 
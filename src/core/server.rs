@@ -11,6 +11,10 @@
 
 use crate::{Err, Result, config, config::Config, log::Log, metrics::Metrics};
 
+/// Localpart of the server user, which sends admin room responses and acts on
+/// behalf of the server.
+pub const SERVER_USER_LOCALPART: &str = "conduit";
+
 /// Server runtime state; public portion
 pub struct Server {
 	/// Configured name of server. This is the same as the one in the config
@@ -1,12 +1,17 @@
-use std::{ffi::OsString, path::PathBuf};
+use std::{ffi::OsString, path::PathBuf, time::SystemTime};
 
-use rocksdb::backup::{BackupEngine, BackupEngineOptions};
+use rocksdb::{
+	Env,
+	backup::{BackupEngine, BackupEngineInfo, BackupEngineOptions, RestoreOptions},
+};
 use tuwunel_core::{
-	Err, Result, error, implement, info, utils::time::rfc2822_from_seconds, warn,
+	Err, Result, Server, err, error, implement, info,
+	utils::time::{duration_since_epoch, rfc2822_from_seconds},
+	warn,
 };
 
 use super::Engine;
-use crate::util::map_err;
+use crate::{Context, util::map_err};
 
 #[implement(Engine)]
 #[tracing::instrument(skip(self))]
@@ -71,18 +76,133 @@ pub fn backup_count(&self) -> Result<usize> {
 	Ok(info.len())
 }
 
+/// Verify the integrity of a backup, or all backups when no id is given.
+/// Returns a line for each backup describing the outcome.
+#[implement(Engine)]
+pub fn backup_verify(&self, backup_id: Option<u32>) -> Result<Vec<String>> {
+	let engine = self.backup_engine()?;
+	let info = engine.get_backup_info();
+	if info.is_empty() {
+		return Err!("No backups found.");
+	}
+
+	if let Some(backup_id) = backup_id {
+		if !info
+			.iter()
+			.any(|info| info.backup_id == backup_id)
+		{
+			return Err!("Backup #{backup_id} does not exist.");
+		}
+	}
+
+	let results = info
+		.iter()
+		.filter(|info| backup_id.is_none_or(|id| id == info.backup_id))
+		.map(|info| match engine.verify_backup(info.backup_id) {
+			| Ok(()) => format!("#{} verified", info.backup_id),
+			| Err(e) => format!("#{} FAILED verification: {e}", info.backup_id),
+		})
+		.collect();
+
+	Ok(results)
+}
+
+/// Delete backups created before the timepoint. The most recent backup is
+/// always retained. Returns the number of backups removed.
+#[implement(Engine)]
+pub fn backup_purge_before(&self, timepoint: SystemTime) -> Result<usize> {
+	let mut engine = self.backup_engine()?;
+	let info = engine.get_backup_info();
+	let cutoff = i64::try_from(duration_since_epoch(timepoint).as_secs())?;
+
+	let keep = backups_to_keep(&info, cutoff);
+	let purge = info.len().saturating_sub(keep);
+	if purge > 0 {
+		engine.purge_old_backups(keep).map_err(map_err)?;
+		info!(
+			"Purged {purge} database backups created before {}",
+			rfc2822_from_seconds(cutoff)
+		);
+	}
+
+	Ok(purge)
+}
+
+/// Restore a backup into the database path. This must be called prior to
+/// opening the database. The `backup` argument is a backup id or "latest".
+#[tracing::instrument(skip(ctx))]
+pub(super) fn restore(ctx: &Context, backup: &str) -> Result {
+	let config = &ctx.server.config;
+	let path = &config.database_path;
+	let mut engine = open_engine(&ctx.server, &*ctx.env.lock()?)?;
+	let info = engine.get_backup_info();
+	let backup_id = select_backup(&info, backup)?;
+
+	info!("Verifying database backup #{backup_id}...");
+	engine
+		.verify_backup(backup_id)
+		.map_err(|e| err!(Database("Backup #{backup_id} failed verification: {e}")))?;
+
+	warn!("Restoring database backup #{backup_id} into {path:?}. This may take a long time...");
+	let mut opts = RestoreOptions::default();
+	opts.set_keep_log_files(false);
+	engine
+		.restore_from_backup(path, path, &opts, backup_id)
+		.map_err(map_err)?;
+
+	info!("Database backup #{backup_id} restored.");
+	Ok(())
+}
+
+/// Number of the newest backups to retain so that those created at or after
+/// the cutoff are kept. The most recent backup is always kept.
+fn backups_to_keep(info: &[BackupEngineInfo], cutoff: i64) -> usize {
+	// Backups are ordered by id and therefore by time; purge_old_backups() retains
+	// the newest N so count how many are young enough to keep.
+	info.iter()
+		.filter(|info| info.timestamp >= cutoff)
+		.count()
+		.max(1)
+}
+
+fn select_backup(info: &[BackupEngineInfo], backup: &str) -> Result<u32> {
+	if backup.eq_ignore_ascii_case("latest") {
+		return info
+			.last()
+			.map(|info| info.backup_id)
+			.ok_or_else(|| err!(Config("database_backup_path", "No backups found.")));
+	}
+
+	let backup_id: u32 = backup
+		.trim_start_matches('#')
+		.parse()
+		.map_err(|e| {
+			err!(Config("database_backup_restore", "Invalid backup id {backup:?}: {e}"))
+		})?;
+
+	if !info
+		.iter()
+		.any(|info| info.backup_id == backup_id)
+	{
+		return Err!(Config("database_backup_restore", "Backup #{backup_id} does not exist."));
+	}
+
+	Ok(backup_id)
+}
+
 #[implement(Engine)]
 fn backup_engine(&self) -> Result<BackupEngine> {
-	let path = self.backup_path()?;
+	open_engine(&self.ctx.server, &*self.ctx.env.lock()?)
+}
+
+fn open_engine(server: &Server, env: &Env) -> Result<BackupEngine> {
+	let path = backup_path(server)?;
 	let options = BackupEngineOptions::new(path).map_err(map_err)?;
-	BackupEngine::open(&options, &*self.ctx.env.lock()?).map_err(map_err)
+	BackupEngine::open(&options, env).map_err(map_err)
 }
 
-#[implement(Engine)]
-fn backup_path(&self) -> Result<OsString> {
-	let path = self
-		.ctx
-		.server
+fn backup_path(server: &Server) -> Result<OsString> {
+	let path = server
 		.config
 		.database_backup_path
 		.clone()
@@ -95,3 +215,66 @@ fn backup_path(&self) -> Result<OsString> {
 
 	Ok(path)
 }
+
+#[cfg(test)]
+mod tests {
+	use rocksdb::backup::BackupEngineInfo;
+
+	use super::{backups_to_keep, select_backup};
+
+	fn backups(timestamps: &[i64]) -> Vec<BackupEngineInfo> {
+		(1..)
+			.zip(timestamps)
+			.map(|(backup_id, &timestamp)| BackupEngineInfo {
+				timestamp,
+				backup_id,
+				size: 0,
+				num_files: 0,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn select_latest() {
+		let info = backups(&[100, 200, 300]);
+		assert_eq!(select_backup(&info, "latest").unwrap(), 3);
+		assert_eq!(select_backup(&info, "LATEST").unwrap(), 3);
+	}
+
+	#[test]
+	fn select_latest_without_backups() {
+		assert!(select_backup(&[], "latest").is_err());
+	}
+
+	#[test]
+	fn select_by_id() {
+		let info = backups(&[100, 200, 300]);
+		assert_eq!(select_backup(&info, "2").unwrap(), 2);
+		assert_eq!(select_backup(&info, "#1").unwrap(), 1);
+	}
+
+	#[test]
+	fn select_missing_or_invalid() {
+		let info = backups(&[100, 200, 300]);
+		assert!(select_backup(&info, "4").is_err());
+		assert!(select_backup(&info, "0").is_err());
+		assert!(select_backup(&info, "two").is_err());
+		assert!(select_backup(&info, "").is_err());
+	}
+
+	#[test]
+	fn keep_backups_since_cutoff() {
+		let info = backups(&[100, 200, 300, 400]);
+		assert_eq!(backups_to_keep(&info, 0), 4);
+		assert_eq!(backups_to_keep(&info, 200), 3);
+		assert_eq!(backups_to_keep(&info, 201), 2);
+		assert_eq!(backups_to_keep(&info, 400), 1);
+	}
+
+	#[test]
+	fn keep_most_recent_backup() {
+		let info = backups(&[100, 200]);
+		assert_eq!(backups_to_keep(&info, 1000), 1);
+		assert_eq!(backups_to_keep(&[], 1000), 1);
+	}
+}
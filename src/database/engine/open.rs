@@ -9,6 +9,7 @@
 
 use super::{
 	Db, Engine,
+	backup::restore,
 	cf_opts::cf_options,
 	db_opts::db_options,
 	descriptor::{self, Descriptor},
@@ -23,6 +24,11 @@ pub async fn open(ctx: Arc<Context>, desc: &[Descriptor]) -> Result<Arc<Self>> {
 	let config = &server.config;
 	let path = &config.database_path;
 
+	// Restoring must precede discovery of the existing column families.
+	if let Some(backup) = config.database_backup_restore.as_deref() {
+		restore(&ctx, backup)?;
+	}
+
 	let db_opts = db_options(
 		config,
 		&ctx.env.lock().expect("environment locked"),
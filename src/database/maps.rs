@@ -33,6 +33,14 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "aliasid_alias",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "appservicedevicechangeid_userid",
+		..descriptor::SEQUENTIAL_SMALL
+	},
+	Descriptor {
+		name: "auditid_admincommand",
+		..descriptor::SEQUENTIAL_SMALL
+	},
 	Descriptor {
 		name: "backupid_algorithm",
 		..descriptor::RANDOM_SMALL
@@ -49,6 +57,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "bannedroomids",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "delayid_delayedevent",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "disabledroomids",
 		..descriptor::RANDOM_SMALL
@@ -149,6 +161,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "referencedevents",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "roomeventid_pendingrelation",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomid_knockedcount",
 		..descriptor::RANDOM_SMALL
@@ -223,10 +239,18 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomuserid_privateread",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomuserid_privatereadreceipt",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomuseroncejoinedids",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "roomuserthread_readreceiptcount",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomusertype_roomuserdataid",
 		..descriptor::RANDOM_SMALL
@@ -235,6 +259,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "senderkey_pusher",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "server_signedkeys",
+		..descriptor::RANDOM
+	},
 	Descriptor {
 		name: "server_signingkeys",
 		..descriptor::RANDOM
@@ -342,6 +370,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "url_previews",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "userdevicealgorithm_fallbackkey",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userdeviceid_metadata",
 		..descriptor::RANDOM_SMALL
@@ -358,6 +390,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userdevicesessionid_uiaainfo",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userdevicestreamid_syncposition",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userdevicetxnid_response",
 		..descriptor::RANDOM_SMALL
@@ -366,6 +402,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userfilterid_filter",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_adminroles",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_avatarurl",
 		..descriptor::RANDOM_SMALL
@@ -374,6 +414,14 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userid_blurhash",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_dehydrateddevice",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userid_delayid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_devicelistversion",
 		..descriptor::RANDOM_SMALL
@@ -406,6 +454,10 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userid_selfsigningkeyid",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_servernoticeroom",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_usersigningkeyid",
 		..descriptor::RANDOM_SMALL
@@ -438,4 +490,12 @@ pub fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userroomid_notificationcount",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "userroomthreadid_highlightcount",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userroomthreadid_notificationcount",
+		..descriptor::RANDOM_SMALL
+	},
 ];
@@ -35,6 +35,16 @@ pub struct Args {
 	#[arg(long)]
 	pub maintenance: bool,
 
+	/// Restore a database backup at startup. The value is a backup number
+	/// (see `!admin server list-backups`) or 'latest'.
+	#[arg(long, value_name = "ID|latest")]
+	pub restore_backup: Option<String>,
+
+	/// Restore the backup into this directory instead of the configured
+	/// database_path. The server then opens the restored database from here.
+	#[arg(long, requires = "restore_backup", value_name = "PATH")]
+	pub restore_backup_to: Option<PathBuf>,
+
 	#[cfg(feature = "console")]
 	/// Activate admin command console automatically after startup.
 	#[arg(long, num_args(0))]
@@ -163,6 +173,14 @@ pub fn update(mut config: Figment, args: &Args) -> Result<Figment> {
 		config = config.join(("listening", false));
 	}
 
+	if let Some(backup) = &args.restore_backup {
+		config = config.merge(("database_backup_restore", backup));
+	}
+
+	if let Some(path) = &args.restore_backup_to {
+		config = config.merge(("database_path", path));
+	}
+
 	#[cfg(feature = "console")]
 	// Indicate the admin console should be spawned automatically if the
 	// configuration file hasn't already.
@@ -1,4 +1,4 @@
-use std::{any::Any, sync::Arc, time::Duration};
+use std::{any::Any, collections::BTreeSet, sync::Arc, time::Duration};
 
 use axum::{
 	Router,
@@ -20,7 +20,7 @@
 };
 use tracing::Level;
 use tuwunel_api::router::state::Guard;
-use tuwunel_core::{Result, Server, debug, error};
+use tuwunel_core::{Result, Server, config::RouteGroup, debug, error};
 use tuwunel_service::Services;
 
 use crate::{request, router};
@@ -35,7 +35,7 @@
 
 const TUWUNEL_PERMISSIONS_POLICY: &[&str; 2] = &["interest-cohort=()", "browsing-topics=()"];
 
-pub fn build(services: &Arc<Services>) -> Result<(Router, Guard)> {
+pub fn build(services: &Arc<Services>, routes: &BTreeSet<RouteGroup>) -> Result<(Router, Guard)> {
 	let server = &services.server;
 	let layers = ServiceBuilder::new();
 
@@ -96,7 +96,7 @@ pub fn build(services: &Arc<Services>) -> Result<(Router, Guard)> {
 		.layer(body_limit_layer(server))
 		.layer(CatchPanicLayer::custom(move |panic| catch_panic(panic, services_.clone())));
 
-	let (router, guard) = router::build(services);
+	let (router, guard) = router::build(services, routes);
 	Ok((router.layer(layers), guard))
 }
 
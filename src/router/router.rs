@@ -1,16 +1,16 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 
 use axum::{Router, response::IntoResponse, routing::get};
 use http::{StatusCode, Uri};
 use ruma::api::client::error::ErrorKind;
 use tuwunel_api::router::{state, state::Guard};
-use tuwunel_core::Error;
+use tuwunel_core::{Error, config::RouteGroup};
 use tuwunel_service::Services;
 
-pub fn build(services: &Arc<Services>) -> (Router, Guard) {
+pub fn build(services: &Arc<Services>, routes: &BTreeSet<RouteGroup>) -> (Router, Guard) {
 	let router = Router::<state::State>::new();
 	let (state, guard) = state::create(services.clone());
-	let router = tuwunel_api::router::build(router, &services.server)
+	let router = tuwunel_api::router::build(router, &services.server, routes)
 		.route("/", get(it_works))
 		.fallback(not_found)
 		.with_state(state);
@@ -0,0 +1,462 @@
+//! Automatic certificate provisioning for the built-in TLS listener using
+//! ACME (RFC 8555) with the HTTP-01 or TLS-ALPN-01 (RFC 8737) challenges.
+
+use std::{
+	collections::{BTreeSet, HashMap},
+	ffi::OsStr,
+	net::SocketAddr,
+	path::{Path as FsPath, PathBuf},
+	sync::{Arc, RwLock},
+	time::Duration,
+};
+
+use axum::{
+	Router,
+	extract::{Path, State},
+	http::StatusCode,
+	routing::get,
+};
+use axum_server::{Handle as ServerHandle, bind};
+use instant_acme::{
+	Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+	NewOrder, Order, OrderStatus,
+};
+use rcgen::{CertificateParams, CustomExtension, DistinguishedName, KeyPair};
+use rustls::{
+	ServerConfig,
+	crypto::aws_lc_rs::sign::any_supported_type,
+	pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+	server::{ClientHello, ResolvesServerCert},
+	sign::CertifiedKey,
+};
+use tokio::{fs, time::sleep};
+use tuwunel_core::{
+	Err, Result, Server, debug, debug_warn, err, error, info,
+	utils::{time::now_secs, timepoint_from_now},
+};
+
+/// ALPN protocol identifier for the TLS-ALPN-01 challenge.
+const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// Interval between checks of the certificate's remaining validity.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60 * 12);
+
+/// Delay before retrying after a failed order.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 15);
+
+/// Maximum number of polls while waiting for the CA to process an order.
+const POLL_ATTEMPTS: usize = 30;
+
+const ACCOUNT_FILE: &str = "account.json";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+pub(super) struct Acme {
+	server: Arc<Server>,
+	resolver: Arc<Resolver>,
+	dir: PathBuf,
+}
+
+/// Certificate resolver installed into the rustls ServerConfig. The active
+/// certificate is replaced in-place on renewal; existing connections are
+/// unaffected and new handshakes pick up the new certificate.
+#[derive(Debug, Default)]
+struct Resolver {
+	cert: RwLock<Option<Arc<CertifiedKey>>>,
+
+	/// TLS-ALPN-01 challenge certificates by domain.
+	alpn_challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+
+	/// HTTP-01 key authorizations by token.
+	http_challenges: RwLock<HashMap<String, String>>,
+}
+
+impl Acme {
+	pub(super) fn new(server: &Arc<Server>) -> Arc<Self> {
+		let config = &server.config;
+		let dir = config
+			.tls
+			.acme_state_dir
+			.clone()
+			.unwrap_or_else(|| default_state_dir(&config.database_path));
+
+		Arc::new(Self {
+			server: server.clone(),
+			resolver: Default::default(),
+			dir,
+		})
+	}
+
+	/// ServerConfig resolving certificates through this instance.
+	pub(super) fn server_config(&self) -> Arc<ServerConfig> {
+		let mut config = ServerConfig::builder()
+			.with_no_client_auth()
+			.with_cert_resolver(self.resolver.clone());
+
+		config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+		if self.use_tls_alpn() {
+			config.alpn_protocols.push(ACME_TLS_ALPN.to_vec());
+		}
+
+		Arc::new(config)
+	}
+
+	/// Spawn plaintext listeners answering HTTP-01 challenges when that
+	/// challenge type is configured. The listeners bind the hosts of the given
+	/// addresses on the configured HTTP-01 port.
+	pub(super) fn serve_http01(self: &Arc<Self>, handle: &ServerHandle, addrs: &[SocketAddr]) {
+		if self.use_tls_alpn() {
+			return;
+		}
+
+		let port = self.server.config.tls.acme_http01_port;
+		let app = Router::new()
+			.route("/.well-known/acme-challenge/{token}", get(http01_challenge))
+			.with_state(self.resolver.clone())
+			.into_make_service();
+
+		let addrs: BTreeSet<_> = addrs
+			.iter()
+			.map(|addr| SocketAddr::new(addr.ip(), port))
+			.collect();
+
+		for addr in &addrs {
+			self.server.runtime().spawn(
+				bind(*addr)
+					.handle(handle.clone())
+					.serve(app.clone()),
+			);
+		}
+
+		info!("Listening on {addrs:?} for ACME HTTP-01 challenges");
+	}
+
+	/// Load any stored certificate then keep it renewed until shutdown.
+	pub(super) async fn run(self: Arc<Self>) {
+		if let Err(e) = fs::create_dir_all(&self.dir).await {
+			error!("Failed to create ACME state directory {:?}: {e}", self.dir);
+			return;
+		}
+
+		match self.load_cert().await {
+			| Ok(()) => debug!("Loaded stored ACME certificate from {:?}", self.dir),
+			| Err(e) => debug_warn!("No usable stored ACME certificate: {e}"),
+		}
+
+		while self.server.running() {
+			let interval = match self.renew_if_needed().await {
+				| Ok(()) => CHECK_INTERVAL,
+				| Err(e) => {
+					error!("ACME certificate renewal failed: {e}");
+					RETRY_INTERVAL
+				},
+			};
+
+			tokio::select! {
+				() = sleep(interval) => {},
+				() = self.server.until_shutdown() => break,
+			}
+		}
+	}
+
+	async fn renew_if_needed(&self) -> Result {
+		let renew_before = self
+			.server
+			.config
+			.tls
+			.acme_renew_days
+			.saturating_mul(86_400);
+
+		let expires = self.cert_expires().await.unwrap_or(0);
+		let remaining = expires.saturating_sub(now_secs());
+		if remaining > renew_before {
+			debug!(remaining, "ACME certificate does not require renewal");
+			return Ok(());
+		}
+
+		let domains = &self.server.config.tls.acme_domains;
+		info!(?domains, "Requesting ACME certificate...");
+		let (cert_pem, key_pem) = self.order().await?;
+
+		fs::write(self.dir.join(CERT_FILE), &cert_pem).await?;
+		fs::write(self.dir.join(KEY_FILE), &key_pem).await?;
+		self.load_cert().await?;
+
+		info!(?domains, "Installed new ACME certificate.");
+		Ok(())
+	}
+
+	async fn order(&self) -> Result<(String, String)> {
+		let account = self.account().await?;
+		let identifiers: Vec<_> = self
+			.server
+			.config
+			.tls
+			.acme_domains
+			.iter()
+			.cloned()
+			.map(Identifier::Dns)
+			.collect();
+
+		let mut order = account
+			.new_order(&NewOrder { identifiers: &identifiers })
+			.await
+			.map_err(|e| err!("ACME order failed: {e}"))?;
+
+		let result = self.authorize(&mut order).await;
+		self.clear_challenges();
+		result?;
+
+		let domains = self.server.config.tls.acme_domains.clone();
+		let mut params = CertificateParams::new(domains)
+			.map_err(|e| err!("Failed to build certificate parameters: {e}"))?;
+		params.distinguished_name = DistinguishedName::new();
+
+		let key = KeyPair::generate().map_err(|e| err!("Failed to generate key: {e}"))?;
+		let csr = params
+			.serialize_request(&key)
+			.map_err(|e| err!("Failed to create CSR: {e}"))?;
+
+		order
+			.finalize(csr.der())
+			.await
+			.map_err(|e| err!("ACME finalize failed: {e}"))?;
+
+		for _ in 0..POLL_ATTEMPTS {
+			match order.certificate().await {
+				| Ok(Some(cert_pem)) => return Ok((cert_pem, key.serialize_pem())),
+				| Ok(None) => sleep(Duration::from_secs(2)).await,
+				| Err(e) => return Err!("ACME certificate download failed: {e}"),
+			}
+		}
+
+		Err!("Timed out waiting for ACME certificate issuance.")
+	}
+
+	async fn authorize(&self, order: &mut Order) -> Result {
+		let authorizations = order
+			.authorizations()
+			.await
+			.map_err(|e| err!("Failed to fetch ACME authorizations: {e}"))?;
+
+		let challenge_type = if self.use_tls_alpn() {
+			ChallengeType::TlsAlpn01
+		} else {
+			ChallengeType::Http01
+		};
+
+		for authz in &authorizations {
+			match authz.status {
+				| AuthorizationStatus::Pending => {},
+				| AuthorizationStatus::Valid => continue,
+				| status => return Err!("Unexpected ACME authorization status {status:?}"),
+			}
+
+			let Identifier::Dns(domain) = &authz.identifier;
+			let challenge = authz
+				.challenges
+				.iter()
+				.find(|challenge| challenge.r#type == challenge_type)
+				.ok_or_else(|| err!("CA did not offer a {challenge_type:?} challenge"))?;
+
+			let key_auth = order.key_authorization(challenge);
+			if self.use_tls_alpn() {
+				let cert = alpn_challenge_cert(domain, key_auth.digest().as_ref())?;
+				self.resolver
+					.alpn_challenges
+					.write()?
+					.insert(domain.clone(), cert);
+			} else {
+				self.resolver
+					.http_challenges
+					.write()?
+					.insert(challenge.token.clone(), key_auth.as_str().to_owned());
+			}
+
+			order
+				.set_challenge_ready(&challenge.url)
+				.await
+				.map_err(|e| err!("Failed to set ACME challenge ready: {e}"))?;
+		}
+
+		for _ in 0..POLL_ATTEMPTS {
+			sleep(Duration::from_secs(2)).await;
+			let state = order
+				.refresh()
+				.await
+				.map_err(|e| err!("Failed to refresh ACME order: {e}"))?;
+
+			match state.status {
+				| OrderStatus::Ready | OrderStatus::Valid => return Ok(()),
+				| OrderStatus::Invalid => return Err!("ACME order is invalid: {state:?}"),
+				| OrderStatus::Pending | OrderStatus::Processing => {},
+			}
+		}
+
+		Err!("Timed out waiting for ACME challenge validation.")
+	}
+
+	async fn account(&self) -> Result<Account> {
+		let path = self.dir.join(ACCOUNT_FILE);
+		if let Ok(json) = fs::read(&path).await {
+			let credentials: AccountCredentials = serde_json::from_slice(&json)?;
+			return Account::from_credentials(credentials)
+				.await
+				.map_err(|e| err!("Failed to load ACME account: {e}"));
+		}
+
+		let tls = &self.server.config.tls;
+		let contact: Vec<_> = tls
+			.acme_contact
+			.iter()
+			.map(String::as_str)
+			.collect();
+		let (account, credentials) = Account::create(
+			&NewAccount {
+				contact: &contact,
+				terms_of_service_agreed: true,
+				only_return_existing: false,
+			},
+			tls.acme_directory.as_str(),
+			None,
+		)
+		.await
+		.map_err(|e| err!("Failed to create ACME account: {e}"))?;
+
+		fs::write(&path, serde_json::to_vec(&credentials)?).await?;
+		info!("Created ACME account at {}", tls.acme_directory);
+
+		Ok(account)
+	}
+
+	async fn load_cert(&self) -> Result {
+		let cert_pem = fs::read(self.dir.join(CERT_FILE)).await?;
+		let key_pem = fs::read(self.dir.join(KEY_FILE)).await?;
+
+		let certs = CertificateDer::pem_slice_iter(&cert_pem)
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| err!("Invalid ACME certificate: {e}"))?;
+
+		let key = PrivateKeyDer::from_pem_slice(&key_pem)
+			.map_err(|e| err!("Invalid ACME private key: {e}"))?;
+
+		let key = any_supported_type(&key).map_err(|e| err!("Unsupported private key: {e}"))?;
+
+		self.resolver
+			.cert
+			.write()?
+			.replace(Arc::new(CertifiedKey::new(certs, key)));
+
+		Ok(())
+	}
+
+	/// Expiration of the active certificate in seconds since the epoch.
+	async fn cert_expires(&self) -> Result<u64> {
+		let cert = self
+			.resolver
+			.cert
+			.read()?
+			.clone()
+			.ok_or_else(|| err!("No certificate loaded"))?;
+
+		let leaf = cert
+			.cert
+			.first()
+			.ok_or_else(|| err!("Empty certificate chain"))?;
+
+		let (_, parsed) = x509_parser::parse_x509_certificate(leaf.as_ref())
+			.map_err(|e| err!("Failed to parse certificate: {e}"))?;
+
+		let expires = parsed.validity().not_after.timestamp();
+		Ok(u64::try_from(expires).unwrap_or(0))
+	}
+
+	fn clear_challenges(&self) {
+		if let Ok(mut challenges) = self.resolver.alpn_challenges.write() {
+			challenges.clear();
+		}
+
+		if let Ok(mut challenges) = self.resolver.http_challenges.write() {
+			challenges.clear();
+		}
+	}
+
+	fn use_tls_alpn(&self) -> bool { self.server.config.tls.acme_challenge != "http-01" }
+}
+
+impl ResolvesServerCert for Resolver {
+	fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+		let is_challenge = hello
+			.alpn()
+			.is_some_and(|mut alpn| alpn.any(|proto| proto == ACME_TLS_ALPN));
+
+		if is_challenge {
+			let domain = hello.server_name()?;
+			return self
+				.alpn_challenges
+				.read()
+				.ok()?
+				.get(domain)
+				.cloned();
+		}
+
+		let cert = self.cert.read().ok()?.clone();
+		if cert.is_none() {
+			debug_warn!("TLS handshake attempted before an ACME certificate was obtained.");
+		}
+
+		cert
+	}
+}
+
+/// Directory beside the database named after it, e.g. `/var/lib/tuwunel-acme`
+/// for a database at `/var/lib/tuwunel`, keeping our files out of the
+/// directory RocksDB manages.
+fn default_state_dir(database_path: &FsPath) -> PathBuf {
+	let mut name = database_path
+		.file_name()
+		.unwrap_or_else(|| OsStr::new("tuwunel"))
+		.to_os_string();
+
+	name.push("-acme");
+	database_path.with_file_name(name)
+}
+
+async fn http01_challenge(
+	State(resolver): State<Arc<Resolver>>,
+	Path(token): Path<String>,
+) -> Result<String, StatusCode> {
+	resolver
+		.http_challenges
+		.read()
+		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+		.get(&token)
+		.cloned()
+		.ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Self-signed certificate carrying the acmeIdentifier extension required by
+/// RFC 8737.
+fn alpn_challenge_cert(domain: &str, digest: &[u8]) -> Result<Arc<CertifiedKey>> {
+	let mut params = CertificateParams::new(vec![domain.to_owned()])
+		.map_err(|e| err!("Failed to build challenge certificate: {e}"))?;
+
+	params
+		.custom_extensions
+		.push(CustomExtension::new_acme_identifier(digest));
+
+	// Challenge certificates are only presented to the CA for a short time.
+	params.not_after = timepoint_from_now(Duration::from_secs(86_400))?.into();
+
+	let key = KeyPair::generate().map_err(|e| err!("Failed to generate key: {e}"))?;
+	let cert = params
+		.self_signed(&key)
+		.map_err(|e| err!("Failed to sign challenge certificate: {e}"))?;
+
+	let key_der = PrivateKeyDer::try_from(key.serialize_der())
+		.map_err(|e| err!("Invalid challenge key: {e}"))?;
+
+	let key = any_supported_type(&key_der).map_err(|e| err!("Unsupported challenge key: {e}"))?;
+
+	Ok(Arc::new(CertifiedKey::new(vec![cert.der().clone()], key)))
+}
@@ -1,17 +1,26 @@
+#[cfg(feature = "direct_tls")]
+mod acme;
 mod plain;
 #[cfg(feature = "direct_tls")]
 mod tls;
 mod unix;
 
-use std::sync::Arc;
+use std::sync::{Arc, atomic::Ordering};
 
+use axum::Router;
 use axum_server::Handle as ServerHandle;
-use tokio::sync::broadcast;
-use tuwunel_core::{Result, err};
+use tokio::{sync::broadcast, task::JoinSet};
+use tuwunel_core::{Result, Server, config::ListenerConfig, debug_info, err, error};
 use tuwunel_service::Services;
 
 use super::layers;
 
+#[cfg(feature = "direct_tls")]
+type Acme = Option<Arc<acme::Acme>>;
+
+#[cfg(not(feature = "direct_tls"))]
+type Acme = Option<()>;
+
 /// Serve clients
 pub async fn serve(
 	services: Arc<Services>,
@@ -27,13 +36,105 @@ pub async fn serve(
 			.map_err(|e| err!(error!("channel error: {e}")));
 	}
 
-	let addrs = config.get_bind_addrs();
-	let (app, _guard) = layers::build(&services)?;
-	if cfg!(unix) && config.unix_socket_path.is_some() {
-		unix::serve(server, app, shutdown).await
-	} else if config.tls.certs.is_some() {
+	#[cfg(feature = "direct_tls")]
+	let acme: Acme = (!config.tls.acme_domains.is_empty()).then(|| acme::Acme::new(server));
+
+	#[cfg(not(feature = "direct_tls"))]
+	let acme: Acme = None;
+
+	let listeners = config.get_listeners();
+	let mut guards = Vec::with_capacity(listeners.len());
+	let mut acme_addrs = Vec::new();
+	let mut join_set = JoinSet::new();
+	for listener in listeners {
+		if listener.tls && listener.tls_certs.is_none() {
+			acme_addrs.extend(listener.get_bind_addrs());
+		}
+
+		let (app, guard) = layers::build(&services, &listener.routes)?;
+		guards.push(guard);
+		let name = listener.name().to_owned();
+		let serve = serve_listener(
+			server.clone(),
+			listener,
+			app,
+			handle.clone(),
+			shutdown.resubscribe(),
+			acme.clone(),
+		);
+
+		join_set.spawn_on(async move { (name, serve.await) }, server.runtime());
+	}
+
+	#[cfg(feature = "direct_tls")]
+	let renewal = acme.map(|acme| {
+		acme.serve_http01(&handle, &acme_addrs);
+		server.runtime().spawn(acme.run())
+	});
+
+	// A failed listener is logged while the others keep serving; only when
+	// every listener failed is the error returned.
+	let listeners = join_set.len();
+	let mut failed = 0_usize;
+	let mut result: Result = Ok(());
+	while let Some(joined) = join_set.join_next().await {
+		let error = match joined {
+			| Ok((_, Ok(()))) => continue,
+			| Ok((name, Err(e))) => err!(error!("Listener {name:?} failed: {e}")),
+			| Err(e) => err!(error!("Listener task failed: {e}")),
+		};
+
+		failed = failed.saturating_add(1);
+		if failed == listeners {
+			result = Err(error);
+		}
+	}
+
+	#[cfg(feature = "direct_tls")]
+	if let Some(renewal) = renewal {
+		renewal.abort();
+		_ = renewal.await;
+	}
+
+	result?;
+
+	let handle_active = server
+		.metrics
+		.requests_handle_active
+		.load(Ordering::Relaxed);
+	debug_info!(
+		handle_finished = server
+			.metrics
+			.requests_handle_finished
+			.load(Ordering::Relaxed),
+		panics = server
+			.metrics
+			.requests_panic
+			.load(Ordering::Relaxed),
+		handle_active,
+		"Stopped listening",
+	);
+
+	debug_assert!(handle_active == 0, "active request handles still pending");
+
+	Ok(())
+}
+
+#[allow(unused_variables)]
+async fn serve_listener(
+	server: Arc<Server>,
+	listener: ListenerConfig,
+	app: Router,
+	handle: ServerHandle,
+	shutdown: broadcast::Receiver<()>,
+	acme: Acme,
+) -> Result {
+	let addrs = listener.get_bind_addrs();
+	if cfg!(unix) && listener.unix_socket_path.is_some() {
+		unix::serve(&server, &listener, app, shutdown).await
+	} else if listener.is_tls() {
 		#[cfg(feature = "direct_tls")]
-		return tls::serve(server, app, handle, addrs).await;
+		return tls::serve(&server, &listener, app, handle, addrs, acme).await;
 
 		#[cfg(not(feature = "direct_tls"))]
 		return tuwunel_core::Err!(Config(
@@ -41,6 +142,6 @@ pub async fn serve(
 			"tuwunel was not built with direct TLS support (\"direct_tls\")"
 		));
 	} else {
-		plain::serve(server, app, handle, addrs).await
+		plain::serve(&server, &listener, app, handle, addrs).await
 	}
 }
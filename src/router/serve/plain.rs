@@ -1,19 +1,18 @@
-use std::{
-	net::SocketAddr,
-	sync::{Arc, atomic::Ordering},
-};
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::Router;
 use axum_server::{Handle as ServerHandle, bind};
 use tokio::task::JoinSet;
-use tuwunel_core::{Result, Server, debug_info, info};
+use tuwunel_core::{Result, Server, config::ListenerConfig, debug_info, info};
 
 pub async fn serve(
 	server: &Arc<Server>,
+	listener: &ListenerConfig,
 	app: Router,
 	handle: ServerHandle,
 	addrs: Vec<SocketAddr>,
 ) -> Result {
+	let name = listener.name();
 	let app = app.into_make_service_with_connect_info::<SocketAddr>();
 	let mut join_set = JoinSet::new();
 	for addr in &addrs {
@@ -25,27 +24,10 @@ pub async fn serve(
 		);
 	}
 
-	info!("Listening on {addrs:?}");
+	info!(listener = name, routes = ?listener.routes, "Listening on {addrs:?}");
 	while join_set.join_next().await.is_some() {}
 
-	let handle_active = server
-		.metrics
-		.requests_handle_active
-		.load(Ordering::Relaxed);
-	debug_info!(
-		handle_finished = server
-			.metrics
-			.requests_handle_finished
-			.load(Ordering::Relaxed),
-		panics = server
-			.metrics
-			.requests_panic
-			.load(Ordering::Relaxed),
-		handle_active,
-		"Stopped listening on {addrs:?}",
-	);
-
-	debug_assert!(handle_active == 0, "active request handles still pending");
+	debug_info!(listener = name, "Stopped listening on {addrs:?}");
 
 	Ok(())
 }
@@ -1,7 +1,4 @@
-use std::{
-	net::SocketAddr,
-	sync::{Arc, atomic::Ordering},
-};
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::Router;
 use axum_server::Handle as ServerHandle;
@@ -10,41 +7,66 @@
 	axum_server::{bind_rustls, tls_rustls::RustlsConfig},
 };
 use tokio::task::JoinSet;
-use tuwunel_core::{Result, Server, debug, debug_info, err, info, warn};
+use tuwunel_core::{
+	Err, Result, Server, config::ListenerConfig, debug, debug_info, err, info, warn,
+};
+
+use super::acme::Acme;
 
 pub async fn serve(
 	server: &Arc<Server>,
+	listener: &ListenerConfig,
 	app: Router,
 	handle: ServerHandle,
 	addrs: Vec<SocketAddr>,
+	acme: Option<Arc<Acme>>,
 ) -> Result {
+	let name = listener.name();
 	let tls = &server.config.tls;
-	let certs = tls.certs.as_ref().ok_or_else(|| {
-		err!(Config("tls.certs", "Missing required value in tls config section"))
-	})?;
-	let key = tls
-		.key
-		.as_ref()
-		.ok_or_else(|| err!(Config("tls.key", "Missing required value in tls config section")))?;
 
 	// we use ring for ruma and hashing state, but aws-lc-rs is the new default.
-	// without this, TLS mode will panic.
-	rustls::crypto::aws_lc_rs::default_provider()
-		.install_default()
-		.expect("failed to initialise aws-lc-rs rustls crypto provider");
+	// without this, TLS mode will panic. Other listeners may have installed it.
+	_ = rustls::crypto::aws_lc_rs::default_provider().install_default();
 
 	info!(
 		"Note: It is strongly recommended that you use a reverse proxy instead of running \
 		 tuwunel directly with TLS."
 	);
-	debug!("Using direct TLS. Certificate path {certs} and certificate private key path {key}",);
-	let conf = RustlsConfig::from_pem_file(certs, key)
-		.await
-		.map_err(|e| err!(Config("tls", "Failed to load certificates or key: {e}")))?;
 
+	let static_files = listener
+		.tls_certs
+		.as_ref()
+		.zip(listener.tls_key.as_ref())
+		.or_else(|| tls.certs.as_ref().zip(tls.key.as_ref()));
+
+	let (conf, certs) = match (static_files, acme) {
+		| (Some((certs, key)), _) => {
+			debug!(
+				listener = name,
+				"Using direct TLS. Certificate path {certs} and certificate private key path \
+				 {key}",
+			);
+			let conf = RustlsConfig::from_pem_file(certs, key)
+				.await
+				.map_err(|e| err!(Config("tls", "Failed to load certificates or key: {e}")))?;
+
+			(conf, certs.clone())
+		},
+		| (None, Some(acme)) => {
+			debug!(listener = name, domains = ?tls.acme_domains, "Using direct TLS with ACME.");
+			let conf = RustlsConfig::from_config(acme.server_config());
+
+			(conf, format!("from ACME for {:?}", tls.acme_domains))
+		},
+		| (None, None) => {
+			return Err!(Config("tls.certs", "Missing required value in tls config section"));
+		},
+	};
+
+	let dual_protocol = listener.tls_dual_protocol;
 	let mut join_set = JoinSet::new();
 	let app = app.into_make_service_with_connect_info::<SocketAddr>();
-	if tls.dual_protocol {
+	if dual_protocol {
 		for addr in &addrs {
 			join_set.spawn_on(
 				axum_server_dual_protocol::bind_dual_protocol(*addr, conf.clone())
@@ -65,35 +87,24 @@ pub async fn serve(
 		}
 	}
 
-	if tls.dual_protocol {
+	if dual_protocol {
 		warn!(
+			listener = name,
+			routes = ?listener.routes,
 			"Listening on {addrs:?} with TLS certificate {certs} and supporting plain text \
 			 (HTTP) connections too (insecure!)",
 		);
 	} else {
-		info!("Listening on {addrs:?} with TLS certificate {certs}");
+		info!(
+			listener = name,
+			routes = ?listener.routes,
+			"Listening on {addrs:?} with TLS certificate {certs}"
+		);
 	}
 
 	while join_set.join_next().await.is_some() {}
 
-	let handle_active = server
-		.metrics
-		.requests_handle_active
-		.load(Ordering::Relaxed);
-	debug_info!(
-		handle_finished = server
-			.metrics
-			.requests_handle_finished
-			.load(Ordering::Relaxed),
-		panics = server
-			.metrics
-			.requests_panic
-			.load(Ordering::Relaxed),
-		handle_active,
-		"Stopped listening on {addrs:?}",
-	);
-
-	debug_assert!(handle_active == 0, "active request handles still pending");
+	debug_info!(listener = name, "Stopped listening on {addrs:?}");
 
 	Ok(())
 }
@@ -25,7 +25,8 @@
 };
 use tower::{Service, ServiceExt};
 use tuwunel_core::{
-	Err, Result, Server, debug, debug_error, info, result::UnwrapInfallible, trace, warn,
+	Err, Result, Server, config::ListenerConfig, debug, debug_error, info,
+	result::UnwrapInfallible, trace, warn,
 };
 
 type MakeService = IntoMakeServiceWithConnectInfo<Router, net::SocketAddr>;
@@ -36,6 +37,7 @@
 #[tracing::instrument(skip_all, level = "debug")]
 pub async fn serve(
 	server: &Arc<Server>,
+	listener: &ListenerConfig,
 	app: Router,
 	mut shutdown: broadcast::Receiver<()>,
 ) -> Result {
@@ -43,7 +45,7 @@ pub async fn serve(
 	let executor = TokioExecutor::new();
 	let app = app.into_make_service_with_connect_info::<net::SocketAddr>();
 	let builder = server::conn::auto::Builder::new(executor);
-	let listener = init(server).await?;
+	let listener = init(listener).await?;
 	while server.running() {
 		let app = app.clone();
 		let builder = builder.clone();
@@ -112,10 +114,9 @@ async fn accepted(
 	};
 }
 
-async fn init(server: &Arc<Server>) -> Result<UnixListener> {
+async fn init(config: &ListenerConfig) -> Result<UnixListener> {
 	use std::os::unix::fs::PermissionsExt;
 
-	let config = &server.config;
 	let path = config
 		.unix_socket_path
 		.as_ref()
@@ -147,7 +148,7 @@ async fn init(server: &Arc<Server>) -> Result<UnixListener> {
 		return Err!("Failed to set socket {path:?} permissions: {e}");
 	}
 
-	info!("Listening at {path:?}");
+	info!(listener = config.name(), routes = ?config.routes, "Listening at {path:?}");
 
 	Ok(listener.unwrap())
 }
@@ -0,0 +1,63 @@
+use futures::{Stream, StreamExt};
+use ruma::OwnedUserId;
+use serde::{Deserialize, Serialize};
+use tuwunel_core::{implement, utils::stream::TryIgnore};
+use tuwunel_database::{Ignore, Json};
+
+use super::CommandOrigin;
+
+/// Log target audit records are emitted to with `admin_audit_log`.
+const AUDIT_TARGET: &str = "admin_audit";
+
+/// Record of an admin command which was executed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuditRecord {
+	/// Time in milliseconds since the epoch the command was issued.
+	pub ts: u64,
+
+	/// User who issued the command from the admin room.
+	pub sender: Option<OwnedUserId>,
+
+	pub origin: CommandOrigin,
+
+	/// The whole command input, including any code block following the
+	/// command line.
+	pub command: String,
+
+	/// Time in milliseconds the command took.
+	pub duration: u64,
+
+	pub success: bool,
+}
+
+/// Persists the record of an executed admin command.
+#[implement(super::Service)]
+pub fn audit(&self, record: &AuditRecord) {
+	let count = self.services.globals.next_count();
+	self.db
+		.auditid_admincommand
+		.put(*count, Json(record));
+
+	if self.services.server.config.admin_audit_log {
+		tracing::info!(
+			target: AUDIT_TARGET,
+			ts = record.ts,
+			sender = ?record.sender,
+			origin = %record.origin,
+			duration = record.duration,
+			success = record.success,
+			"{}",
+			record.command,
+		);
+	}
+}
+
+/// Records of executed admin commands, most recent first.
+#[implement(super::Service)]
+pub fn audit_log(&self) -> impl Stream<Item = AuditRecord> + Send + '_ {
+	self.db
+		.auditid_admincommand
+		.rev_stream()
+		.ignore_err()
+		.map(|(_, record): (Ignore, AuditRecord)| record)
+}
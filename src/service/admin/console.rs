@@ -12,6 +12,8 @@
 use tokio::task::JoinHandle;
 use tuwunel_core::{Server, debug, defer, error, log, log::is_systemd_mode};
 
+use super::CommandOrigin;
+
 pub struct Console {
 	server: Arc<Server>,
 	services: Arc<crate::services::OnceServices>,
@@ -178,7 +180,7 @@ async fn process(self: Arc<Self>, line: String) {
 		match self
 			.services
 			.admin
-			.command_in_place(line, None)
+			.command_in_place(line, None, None, CommandOrigin::Console)
 			.await
 		{
 			| Ok(Some(ref content)) => self.output(content),
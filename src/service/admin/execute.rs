@@ -2,6 +2,8 @@
 use tokio::time::{Duration, sleep};
 use tuwunel_core::{Err, Result, debug, debug_info, error, implement, info};
 
+use super::CommandOrigin;
+
 pub const SIGNAL: &str = "SIGUSR2";
 
 /// Possibly spawn the terminal console at startup if configured.
@@ -48,7 +50,10 @@ pub async fn startup_execute(&self) -> Result {
 	sleep(Duration::from_millis(500)).await;
 
 	for (i, command) in commands.iter().enumerate() {
-		if let Err(e) = self.execute_command(i, command.clone()).await {
+		if let Err(e) = self
+			.execute_command(i, command.clone(), CommandOrigin::Startup)
+			.await
+		{
 			if !errors {
 				return Err(e);
 			}
@@ -90,7 +95,10 @@ pub async fn signal_execute(&self) -> Result {
 		.admin_execute_errors_ignore;
 
 	for (i, command) in commands.iter().enumerate() {
-		if let Err(e) = self.execute_command(i, command.clone()).await {
+		if let Err(e) = self
+			.execute_command(i, command.clone(), CommandOrigin::Signal)
+			.await
+		{
 			if !ignore_errors {
 				return Err(e);
 			}
@@ -104,10 +112,13 @@ pub async fn signal_execute(&self) -> Result {
 
 /// Execute one admin command after startup or signal
 #[implement(super::Service)]
-async fn execute_command(&self, i: usize, command: String) -> Result {
+async fn execute_command(&self, i: usize, command: String, origin: CommandOrigin) -> Result {
 	debug!("Execute command #{i}: executing {command:?}");
 
-	match self.command_in_place(command, None).await {
+	match self
+		.command_in_place(command, None, None, origin)
+		.await
+	{
 		| Ok(Some(output)) => Self::execute_command_output(i, &output),
 		| Err(output) => Self::execute_command_error(i, &output),
 		| Ok(None) => {
@@ -225,6 +225,7 @@ pub async fn revoke_admin(&self, user_id: &UserId) -> Result {
 		},
 	};
 
+	self.revoke_roles(user_id);
 	self.services
 		.timeline
 		.build_and_append_pdu(
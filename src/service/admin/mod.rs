@@ -1,24 +1,32 @@
+mod audit;
 pub mod console;
 pub mod create;
 mod execute;
 mod grant;
+mod roles;
 
 use std::{
+	fmt,
 	pin::Pin,
+	str::FromStr,
 	sync::{Arc, RwLock as StdRwLock},
 };
 
 use async_trait::async_trait;
+pub use audit::AuditRecord;
 pub use create::create_admin_room;
 use futures::{Future, FutureExt, TryFutureExt};
+pub use roles::AdminRole;
 use ruma::{
-	OwnedEventId, OwnedRoomId, RoomId, UserId,
+	OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
 	events::room::message::{Relation, RoomMessageEventContent},
 };
+use serde::{Deserialize, Serialize};
 use tokio::sync::{RwLock, mpsc};
 use tuwunel_core::{
 	Err, Error, Event, Result, debug, err, error, error::default_log, pdu::PduBuilder,
 };
+use tuwunel_database::Map;
 
 use crate::rooms::state::RoomMutexGuard;
 
@@ -29,13 +37,62 @@ pub struct Service {
 	pub complete: StdRwLock<Option<Completer>>,
 	#[cfg(feature = "console")]
 	pub console: Arc<console::Console>,
+	db: Data,
 }
 
-/// Inputs to a command are a multi-line string and optional reply_id.
+struct Data {
+	auditid_admincommand: Arc<Map>,
+	userid_adminroles: Arc<Map>,
+}
+
+/// Inputs to a command are a multi-line string and optional reply_id, along
+/// with who issued it from where for the audit log and the format the caller
+/// wants the response in.
 #[derive(Clone, Debug, Default)]
 pub struct CommandInput {
 	pub command: String,
 	pub reply_id: Option<OwnedEventId>,
+	pub sender: Option<OwnedUserId>,
+	pub origin: CommandOrigin,
+	pub format: OutputFormat,
+}
+
+/// Format of the response to a command.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+	/// Markdown rendered for humans
+	#[default]
+	Markdown,
+
+	/// JSON object with the outcome, the typed data of commands providing it
+	/// and the rendered output. Requested by the caller, the object is carried
+	/// as the data of a response with the [`JSON_MSGTYPE`] message type;
+	/// requested on the command line, it is rendered in a code block.
+	Json,
+}
+
+/// Message type of responses carrying the JSON outcome of a command.
+pub const JSON_MSGTYPE: &str = "org.matrix.tuwunel.admin.outcome";
+
+/// Where an admin command was issued from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandOrigin {
+	/// The admin room
+	#[default]
+	Room,
+
+	/// The terminal console
+	Console,
+
+	/// `admin_execute` at startup
+	Startup,
+
+	/// `admin_signal_execute` on a signal
+	Signal,
+
+	/// The admin HTTP API
+	Api,
 }
 
 /// Prototype of the tab-completer. The input is buffered text when tab
@@ -71,6 +128,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			complete: StdRwLock::new(None),
 			#[cfg(feature = "console")]
 			console: console::Console::new(&args),
+			db: Data {
+				auditid_admincommand: args.db["auditid_admincommand"].clone(),
+				userid_adminroles: args.db["userid_adminroles"].clone(),
+			},
 		}))
 	}
 
@@ -146,10 +207,15 @@ pub async fn send_message(&self, message_content: RoomMessageEventContent) -> Re
 			.await
 	}
 
-	/// Posts a command to the command processor queue and returns. Processing
-	/// will take place on the service worker's task asynchronously. Errors if
-	/// the queue is full.
-	pub async fn command(&self, command: String, reply_id: Option<OwnedEventId>) -> Result {
+	/// Posts a command from the admin room to the command processor queue and
+	/// returns. Processing will take place on the service worker's task
+	/// asynchronously. Errors if the queue is full.
+	pub async fn command(
+		&self,
+		command: String,
+		reply_id: Option<OwnedEventId>,
+		sender: &UserId,
+	) -> Result {
 		let Some(sender) = self
 			.channel
 			.read()
@@ -160,7 +226,13 @@ pub async fn command(&self, command: String, reply_id: Option<OwnedEventId>) ->
 		};
 
 		sender
-			.send(CommandInput { command, reply_id })
+			.send(CommandInput {
+				command,
+				reply_id,
+				sender: Some(sender.to_owned()),
+				origin: CommandOrigin::Room,
+				..Default::default()
+			})
 			.await
 			.map_err(|e| err!("Failed to enqueue admin command: {e:?}"))
 	}
@@ -171,9 +243,17 @@ pub async fn command_in_place(
 		&self,
 		command: String,
 		reply_id: Option<OwnedEventId>,
+		sender: Option<&UserId>,
+		origin: CommandOrigin,
 	) -> ProcessorResult {
-		self.process_command(CommandInput { command, reply_id })
-			.await
+		self.process_command(CommandInput {
+			command,
+			reply_id,
+			sender: sender.map(ToOwned::to_owned),
+			origin,
+			..Default::default()
+		})
+		.await
 	}
 
 	/// Invokes the tab-completer to complete the command. When unavailable,
@@ -204,7 +284,9 @@ async fn handle_command(&self, command: CommandInput) {
 		}
 	}
 
-	async fn process_command(&self, command: CommandInput) -> ProcessorResult {
+	/// Dispatches a command input to the processor on the current task and
+	/// waits for completion.
+	pub async fn process_command(&self, command: CommandInput) -> ProcessorResult {
 		let handle = &self
 			.handle
 			.read()
@@ -392,3 +474,38 @@ pub async fn is_admin_room(&self, room_id_: &RoomId) -> bool {
 			.unwrap_or(false)
 	}
 }
+
+impl fmt::Display for OutputFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			| Self::Markdown => "markdown",
+			| Self::Json => "json",
+		})
+	}
+}
+
+impl FromStr for OutputFormat {
+	type Err = Error;
+
+	fn from_str(format: &str) -> Result<Self> {
+		match format {
+			| "markdown" => Ok(Self::Markdown),
+			| "json" => Ok(Self::Json),
+			| _ => Err!(Request(InvalidParam(
+				"Unknown format {format:?}; expected markdown or json."
+			))),
+		}
+	}
+}
+
+impl fmt::Display for CommandOrigin {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			| Self::Room => "room",
+			| Self::Console => "console",
+			| Self::Startup => "startup",
+			| Self::Signal => "signal",
+			| Self::Api => "api",
+		})
+	}
+}
@@ -0,0 +1,239 @@
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use futures::{Stream, StreamExt};
+use ruma::{OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use tuwunel_core::{Err, Error, Result, implement, utils::stream::TryIgnore};
+use tuwunel_database::{Deserialized, Json};
+
+/// Role delegating a subset of the admin commands to a member of the admin
+/// room.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdminRole {
+	/// Every command
+	Superadmin,
+
+	/// Users, rooms, federation and media moderation
+	Moderator,
+
+	/// Media management
+	MediaAdmin,
+
+	/// Account help such as creating users and resetting passwords
+	Support,
+}
+
+impl AdminRole {
+	pub const ALL: [Self; 4] =
+		[Self::Superadmin, Self::Moderator, Self::MediaAdmin, Self::Support];
+}
+
+/// Whether the sender may run a command which any of `allowed` grants.
+///
+/// Members of the admin room holding no role are superadmins; granting roles
+/// to a member restricts it to the commands of those roles. Commands without a
+/// sender come from the console or configuration and are always permitted.
+#[implement(super::Service)]
+pub async fn may_run(&self, sender: Option<&UserId>, allowed: &[AdminRole]) -> bool {
+	let Some(sender) = sender else {
+		return true;
+	};
+
+	if self.services.globals.server_user == sender {
+		return true;
+	}
+
+	roles_permit(&self.user_roles(sender).await, allowed)
+}
+
+/// Whether the sender may act on the account of `target`, such as resetting
+/// its password or deactivating it. Members of the admin room are only managed
+/// by superadmins.
+#[implement(super::Service)]
+pub async fn may_manage(&self, sender: Option<&UserId>, target: &UserId) -> bool {
+	let Some(sender) = sender else {
+		return true;
+	};
+
+	if self.services.globals.server_user == sender {
+		return true;
+	}
+
+	manage_permits(&self.user_roles(sender).await, self.user_is_admin(target).await)
+}
+
+#[implement(super::Service)]
+pub async fn user_roles(&self, user_id: &UserId) -> BTreeSet<AdminRole> {
+	self.db
+		.userid_adminroles
+		.get(user_id)
+		.await
+		.deserialized()
+		.unwrap_or_default()
+}
+
+/// Users holding roles, with their roles.
+#[implement(super::Service)]
+pub fn role_holders(&self) -> impl Stream<Item = (OwnedUserId, BTreeSet<AdminRole>)> + Send + '_ {
+	self.db
+		.userid_adminroles
+		.stream()
+		.ignore_err()
+		.map(|(user_id, roles): (&UserId, BTreeSet<AdminRole>)| (user_id.to_owned(), roles))
+}
+
+/// Grants a role to a user, returning false if it already held the role.
+#[implement(super::Service)]
+pub async fn grant_role(&self, user_id: &UserId, role: AdminRole) -> bool {
+	let mut roles = self.user_roles(user_id).await;
+	if !roles.insert(role) {
+		return false;
+	}
+
+	self.db
+		.userid_adminroles
+		.put(user_id, Json(&roles));
+
+	true
+}
+
+/// Revokes a role from a user, returning false if it did not hold the role.
+#[implement(super::Service)]
+pub async fn revoke_role(&self, user_id: &UserId, role: AdminRole) -> bool {
+	let mut roles = self.user_roles(user_id).await;
+	if !roles.remove(&role) {
+		return false;
+	}
+
+	if roles.is_empty() {
+		self.db.userid_adminroles.remove(user_id);
+	} else {
+		self.db
+			.userid_adminroles
+			.put(user_id, Json(&roles));
+	}
+
+	true
+}
+
+/// Revokes every role of a user.
+#[implement(super::Service)]
+pub fn revoke_roles(&self, user_id: &UserId) { self.db.userid_adminroles.remove(user_id); }
+
+/// Whether a member of the admin room holding `roles` may run a command which
+/// any of `allowed` grants.
+fn roles_permit(roles: &BTreeSet<AdminRole>, allowed: &[AdminRole]) -> bool {
+	roles.is_empty()
+		|| roles
+			.iter()
+			.any(|role| *role == AdminRole::Superadmin || allowed.contains(role))
+}
+
+/// Whether a member of the admin room holding `roles` may act on an account,
+/// given whether that account is itself a member of the admin room.
+fn manage_permits(roles: &BTreeSet<AdminRole>, target_is_admin: bool) -> bool {
+	!target_is_admin || roles_permit(roles, &[])
+}
+
+impl fmt::Display for AdminRole {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			| Self::Superadmin => "superadmin",
+			| Self::Moderator => "moderator",
+			| Self::MediaAdmin => "media-admin",
+			| Self::Support => "support",
+		})
+	}
+}
+
+impl FromStr for AdminRole {
+	type Err = Error;
+
+	fn from_str(role: &str) -> Result<Self> {
+		match Self::ALL
+			.into_iter()
+			.find(|known| known.to_string() == role)
+		{
+			| Some(role) => Ok(role),
+			| None => Err!(Request(InvalidParam(
+				"Unknown role {role:?}; expected superadmin, moderator, media-admin or support."
+			))),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeSet;
+
+	use super::{AdminRole, manage_permits, roles_permit};
+
+	#[test]
+	fn role_from_str_round_trip() {
+		for role in AdminRole::ALL {
+			assert_eq!(role.to_string().parse::<AdminRole>().ok(), Some(role));
+		}
+
+		assert!("media_admin".parse::<AdminRole>().is_err());
+		assert!("admin".parse::<AdminRole>().is_err());
+	}
+
+	#[test]
+	fn role_serde_round_trip() {
+		for role in AdminRole::ALL {
+			let json = serde_json::to_string(&role).expect("serializes");
+			assert_eq!(json, format!("\"{role}\""));
+			assert_eq!(serde_json::from_str::<AdminRole>(&json).ok(), Some(role));
+		}
+	}
+
+	#[test]
+	fn may_run_matrix() {
+		use AdminRole::*;
+
+		let none = BTreeSet::new();
+		let roles = |roles: &[AdminRole]| roles.iter().copied().collect::<BTreeSet<_>>();
+
+		// members holding no role are superadmins
+		assert!(roles_permit(&none, &[]));
+		assert!(roles_permit(&none, &[Moderator]));
+
+		// superadmins run every command, including those no other role grants
+		assert!(roles_permit(&roles(&[Superadmin]), &[]));
+		assert!(roles_permit(&roles(&[Superadmin]), &[Support]));
+
+		for role in [Moderator, MediaAdmin, Support] {
+			assert!(!roles_permit(&roles(&[role]), &[]));
+			for allowed in [Moderator, MediaAdmin, Support] {
+				assert_eq!(roles_permit(&roles(&[role]), &[allowed]), role == allowed);
+			}
+		}
+
+		assert!(roles_permit(&roles(&[Support, MediaAdmin]), &[Moderator, MediaAdmin]));
+		assert!(!roles_permit(&roles(&[Support, MediaAdmin]), &[Moderator]));
+	}
+
+	#[test]
+	fn manage_admin_members() {
+		use AdminRole::*;
+
+		let none = BTreeSet::new();
+		let roles = |roles: &[AdminRole]| roles.iter().copied().collect::<BTreeSet<_>>();
+
+		// ordinary users may be managed by any role granting the command
+		for role in AdminRole::ALL {
+			assert!(manage_permits(&roles(&[role]), false));
+		}
+
+		// members of the admin room only by superadmins
+		assert!(manage_permits(&none, true));
+		assert!(manage_permits(&roles(&[Superadmin]), true));
+		assert!(manage_permits(&roles(&[Support, Superadmin]), true));
+		for role in [Moderator, MediaAdmin, Support] {
+			assert!(!manage_permits(&roles(&[role]), true));
+		}
+
+		assert!(!manage_permits(&roles(&[Moderator, Support]), true));
+	}
+}
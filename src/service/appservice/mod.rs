@@ -9,10 +9,17 @@
 
 use async_trait::async_trait;
 use futures::{Future, FutureExt, Stream, StreamExt, TryStreamExt};
-use ruma::{RoomAliasId, RoomId, UserId, api::appservice::Registration};
+use ruma::{
+	OwnedRoomId, OwnedUserId, RoomAliasId, RoomId, UserId, api::appservice::Registration,
+};
+use serde::Deserialize;
 use tokio::sync::{RwLock, RwLockReadGuard};
-use tuwunel_core::{Err, Result, debug, err, utils::stream::IterStream};
-use tuwunel_database::Map;
+use tuwunel_core::{
+	Err, Result, debug, err,
+	result::LogErr,
+	utils::stream::{IterStream, ReadyExt, TryIgnore},
+};
+use tuwunel_database::{Interfix, Map};
 
 pub use self::{namespace_regex::NamespaceRegex, registration_info::RegistrationInfo};
 
@@ -23,11 +30,19 @@ pub struct Service {
 }
 
 struct Data {
+	appservicedevicechangeid_userid: Arc<Map>,
 	id_appserviceregistrations: Arc<Map>,
 }
 
 type Registrations = BTreeMap<String, RegistrationInfo>;
 
+/// Registration keys which ruma's `Registration` does not model.
+#[derive(Default, Deserialize)]
+struct Extensions {
+	#[serde(default, rename = "org.matrix.msc3202")]
+	msc3202: bool,
+}
+
 #[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
@@ -35,6 +50,8 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			registration_info: RwLock::new(BTreeMap::new()),
 			services: args.services.clone(),
 			db: Data {
+				appservicedevicechangeid_userid: args.db["appservicedevicechangeid_userid"]
+					.clone(),
 				id_appserviceregistrations: args.db["id_appserviceregistrations"].clone(),
 			},
 		}))
@@ -67,18 +84,26 @@ async fn init_registrations(&self) -> Result {
 				reg.sender_localpart
 					.get_or_insert_with(|| id.clone());
 
-				Ok((id, reg))
+				let msc3202 = reg.msc3202;
+				Ok((id, reg.into(), msc3202))
 			});
 
 		// Registrations from database
 		self.iter_db_ids()
-			.chain(confs.map_ok(|(id, reg)| (id, reg.into())))
-			.try_for_each(async |(id, reg): (_, Registration)| {
-				debug!(?id, ?reg, "appservice registration");
+			.and_then(async |(id, reg)| {
+				let msc3202 = self.get_db_extensions(&id).await.msc3202;
+				Ok((id, reg, msc3202))
+			})
+			.chain(confs)
+			.try_for_each(async |(id, reg, msc3202): (_, Registration, _)| {
+				debug!(?id, ?reg, ?msc3202, "appservice registration");
+				let mut info: RegistrationInfo = reg.try_into()?;
+				info.msc3202 = msc3202;
+
 				self.registration_info
 					.write()
 					.await
-					.insert(id.clone(), reg.try_into()?)
+					.insert(id.clone(), info)
 					.map_or(Ok(()), |_| Err!("Conflicting Appservice ID: {id:?}"))
 			})
 			.await
@@ -110,10 +135,15 @@ pub async fn register_appservice(
 		appservice_config_body: &str,
 	) -> Result {
 		//TODO: Check for collisions between exclusive appservice namespaces
+		let mut info: RegistrationInfo = registration.clone().try_into()?;
+		info.msc3202 = serde_yaml::from_str::<Extensions>(appservice_config_body)
+			.unwrap_or_default()
+			.msc3202;
+
 		self.registration_info
 			.write()
 			.await
-			.insert(registration.id.clone(), registration.clone().try_into()?);
+			.insert(registration.id.clone(), info);
 
 		self.db
 			.id_appserviceregistrations
@@ -157,6 +187,14 @@ pub async fn get_registration(&self, id: &str) -> Option<Registration> {
 			.map(|info| info.registration)
 	}
 
+	pub async fn get_registration_info(&self, id: &str) -> Option<RegistrationInfo> {
+		self.registration_info
+			.read()
+			.await
+			.get(id)
+			.cloned()
+	}
+
 	pub async fn find_from_access_token(&self, token: &str) -> Result<RegistrationInfo> {
 		self.read()
 			.await
@@ -174,6 +212,153 @@ pub async fn is_exclusive_user_id(&self, user_id: &UserId) -> bool {
 			.any(|info| info.is_exclusive_user_match(user_id))
 	}
 
+	/// Registrations whose user namespace includes the given user
+	pub async fn find_user_registrations(&self, user_id: &UserId) -> Vec<RegistrationInfo> {
+		self.read()
+			.await
+			.values()
+			.filter(|info| info.is_user_match(user_id))
+			.cloned()
+			.collect()
+	}
+
+	/// Records a device or key change of the user for appservices receiving
+	/// device lists and key counts (MSC3202) and wakes their sender so the
+	/// change is pushed without waiting for other traffic. Only appservices
+	/// including the user or one of the user's room members are concerned.
+	pub async fn notify_device_changes(&self, user_id: &UserId) {
+		let (mut interested, mut pending): (Vec<_>, Vec<_>) = self
+			.read()
+			.await
+			.values()
+			.filter(|info| info.msc3202)
+			.cloned()
+			.partition(|info| info.is_user_match(user_id));
+
+		let rooms: Vec<OwnedRoomId> = self
+			.services
+			.state_cache
+			.rooms_joined(user_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		for room_id in &rooms {
+			if pending.is_empty() {
+				break;
+			}
+
+			let members: Vec<OwnedUserId> = self
+				.services
+				.state_cache
+				.room_members(room_id)
+				.map(ToOwned::to_owned)
+				.collect()
+				.await;
+
+			let (matched, unmatched): (Vec<_>, Vec<_>) = pending.into_iter().partition(|info| {
+				members
+					.iter()
+					.any(|member| info.is_user_match(member))
+			});
+
+			interested.extend(matched);
+			pending = unmatched;
+		}
+
+		for info in interested {
+			self.record_device_change(&info.registration.id, user_id);
+			self.services
+				.sending
+				.flush_appservice(info.registration.id)
+				.log_err()
+				.ok();
+		}
+	}
+
+	/// Records the members who may have left the view of appservices receiving
+	/// device lists (MSC3202) when the user left the room: the user for
+	/// appservices still in the room, and every member for the appservice
+	/// including the user.
+	pub async fn notify_member_left(&self, user_id: &UserId, room_id: &RoomId) {
+		let registrations: Vec<_> = self
+			.read()
+			.await
+			.values()
+			.filter(|info| info.msc3202)
+			.cloned()
+			.collect();
+
+		for info in registrations {
+			let id = &info.registration.id;
+			if info.is_user_match(user_id) {
+				self.services
+					.state_cache
+					.room_members(room_id)
+					.ready_for_each(|member| self.record_device_change(id, member))
+					.await;
+			} else if self
+				.services
+				.state_cache
+				.appservice_in_room(room_id, &info)
+				.await
+			{
+				self.record_device_change(id, user_id);
+			} else {
+				continue;
+			}
+
+			self.services
+				.sending
+				.flush_appservice(id.clone())
+				.log_err()
+				.ok();
+		}
+	}
+
+	/// Users with device changes recorded for the appservice within the window.
+	pub fn device_changes<'a>(
+		&'a self,
+		appservice_id: &'a str,
+		since: u64,
+		to: u64,
+	) -> impl Stream<Item = &'a UserId> + Send + 'a {
+		type KeyVal<'a> = ((&'a str, u64), &'a UserId);
+
+		let start = (appservice_id, since.saturating_add(1));
+		self.db
+			.appservicedevicechangeid_userid
+			.stream_from(&start)
+			.ignore_err()
+			.ready_take_while(move |((id, count), _): &KeyVal<'_>| {
+				*id == appservice_id && *count <= to
+			})
+			.map(|(_, user_id): KeyVal<'_>| user_id)
+	}
+
+	/// Removes the device changes recorded for the appservice up to `until`
+	/// once they were pushed.
+	pub async fn remove_device_changes(&self, appservice_id: &str, until: u64) {
+		type Key<'a> = (&'a str, u64);
+
+		self.db
+			.appservicedevicechangeid_userid
+			.keys_prefix(&(appservice_id, Interfix))
+			.ignore_err()
+			.ready_take_while(|(_, count): &Key<'_>| *count <= until)
+			.ready_for_each(|key: Key<'_>| {
+				self.db.appservicedevicechangeid_userid.del(key);
+			})
+			.await;
+	}
+
+	fn record_device_change(&self, appservice_id: &str, user_id: &UserId) {
+		let count = self.services.globals.next_count();
+		self.db
+			.appservicedevicechangeid_userid
+			.put_raw((appservice_id, *count), user_id);
+	}
+
 	/// Checks if a given room alias matches any exclusive appservice regex
 	pub async fn is_exclusive_alias(&self, alias: &RoomAliasId) -> bool {
 		self.read()
@@ -219,6 +404,17 @@ pub async fn get_db_registration(&self, id: &str) -> Result<Registration> {
 			.map_err(|e| err!(Database("Invalid appservice {id:?} registration: {e:?}")))
 	}
 
+	/// Registration keys which are not part of ruma's registration
+	async fn get_db_extensions(&self, id: &str) -> Extensions {
+		self.db
+			.id_appserviceregistrations
+			.get(id)
+			.await
+			.ok()
+			.and_then(|bytes| serde_yaml::from_slice(&bytes).ok())
+			.unwrap_or_default()
+	}
+
 	pub fn read(&self) -> impl Future<Output = RwLockReadGuard<'_, Registrations>> + Send {
 		self.registration_info.read()
 	}
@@ -10,6 +10,9 @@ pub struct RegistrationInfo {
 	pub users: NamespaceRegex,
 	pub aliases: NamespaceRegex,
 	pub rooms: NamespaceRegex,
+
+	/// Device lists and key counts are pushed in transactions (MSC3202).
+	pub msc3202: bool,
 }
 
 impl RegistrationInfo {
@@ -36,6 +39,7 @@ fn try_from(value: Registration) -> Result<Self, regex::Error> {
 			aliases: value.namespaces.aliases.clone().try_into()?,
 			rooms: value.namespaces.rooms.clone().try_into()?,
 			registration: value,
+			msc3202: false,
 		})
 	}
 }
@@ -0,0 +1,377 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::{FutureExt, Stream, StreamExt, stream::FuturesUnordered};
+use loole::{Receiver, Sender};
+use ruma::{OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId, serde::Raw};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue as RawJsonValue;
+use tokio::time::sleep;
+use tuwunel_core::{
+	Err, Result, debug, err, error, implement,
+	matrix::pdu::PduBuilder,
+	result::LogErr,
+	utils::{
+		self,
+		stream::{ReadyExt, TryIgnore},
+		time::now_millis,
+	},
+};
+use tuwunel_database::{Deserialized, Ignore, Interfix, Json, Map};
+
+/// generated delay ID length
+const DELAY_ID_LENGTH: usize = 24;
+
+pub struct Service {
+	timer_channel: (Sender<TimerType>, Receiver<TimerType>),
+	services: Arc<crate::services::OnceServices>,
+	db: Data,
+}
+
+struct Data {
+	delayid_delayedevent: Arc<Map>,
+	userid_delayid: Arc<Map>,
+}
+
+/// An event scheduled to be sent once its delay elapses (MSC4140).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DelayedEvent {
+	pub delay_id: String,
+	pub sender: OwnedUserId,
+	pub room_id: OwnedRoomId,
+	#[serde(rename = "type")]
+	pub event_type: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub state_key: Option<String>,
+
+	/// Delay in milliseconds.
+	pub delay: u64,
+
+	/// Time in milliseconds since the epoch the delay last (re)started.
+	pub running_since: u64,
+
+	pub content: Box<RawJsonValue>,
+}
+
+type TimerType = (String, Duration);
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			timer_channel: loole::unbounded(),
+			services: args.services.clone(),
+			db: Data {
+				delayid_delayedevent: args.db["delayid_delayedevent"].clone(),
+				userid_delayid: args.db["userid_delayid"].clone(),
+			},
+		}))
+	}
+
+	async fn worker(self: Arc<Self>) -> Result {
+		self.db
+			.delayid_delayedevent
+			.stream()
+			.ignore_err()
+			.ready_for_each(|(_, event): (Ignore, DelayedEvent)| self.start_timer(&event))
+			.await;
+
+		let receiver = self.timer_channel.1.clone();
+
+		let mut timers = FuturesUnordered::new();
+		while !receiver.is_closed() {
+			tokio::select! {
+				Some(delay_id) = timers.next() => {
+					self.process_timer(&delay_id).await.log_err().ok();
+				},
+				event = receiver.recv_async() => match event {
+					Err(_) => break,
+					Ok((delay_id, timeout)) => {
+						debug!("Adding timer {}: {delay_id} timeout:{timeout:?}", timers.len());
+						timers.push(delay_timer(delay_id, timeout));
+					},
+				},
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn interrupt(&self) {
+		let (timer_sender, _) = &self.timer_channel;
+		if !timer_sender.is_closed() {
+			timer_sender.close();
+		}
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Schedules an event from the user to be sent into the room after the delay.
+/// Returns the delay id the event can be managed with.
+#[implement(Service)]
+#[tracing::instrument(skip(self, content), level = "debug")]
+pub async fn schedule(
+	&self,
+	sender: &UserId,
+	room_id: &RoomId,
+	event_type: String,
+	state_key: Option<String>,
+	content: Box<RawJsonValue>,
+	delay: Duration,
+) -> Result<String> {
+	let max_delay = Duration::from_secs(self.services.server.config.max_event_delay);
+	if max_delay.is_zero() {
+		return Err!(Request(Unrecognized("Delayed events are not enabled on this server.")));
+	}
+
+	if delay > max_delay {
+		let max_delay = max_delay.as_millis();
+		return Err!(Request(InvalidParam("The maximum delay is {max_delay} milliseconds.")));
+	}
+
+	if !self
+		.services
+		.state_cache
+		.is_joined(sender, room_id)
+		.await
+	{
+		return Err!(Request(Forbidden("You are not joined to this room.")));
+	}
+
+	let max_events = self
+		.services
+		.server
+		.config
+		.max_delayed_events_per_user;
+
+	let scheduled = self
+		.db
+		.userid_delayid
+		.keys_prefix_raw(&(sender, Interfix))
+		.ignore_err()
+		.count()
+		.await;
+
+	if scheduled >= max_events {
+		return Err!(Request(Forbidden(
+			"You may not schedule more than {max_events} delayed events."
+		)));
+	}
+
+	let event = DelayedEvent {
+		delay_id: utils::random_string(DELAY_ID_LENGTH),
+		sender: sender.to_owned(),
+		room_id: room_id.to_owned(),
+		event_type,
+		state_key,
+		delay: delay.as_millis().try_into()?,
+		running_since: now_millis(),
+		content,
+	};
+
+	self.db
+		.userid_delayid
+		.put_raw((sender, &event.delay_id), []);
+
+	self.put(&event);
+	self.start_timer(&event);
+
+	Ok(event.delay_id)
+}
+
+/// Restarts the delay of the event from now.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn restart(&self, delay_id: &str) -> Result {
+	let mut event = self.get(delay_id).await?;
+	event.running_since = now_millis();
+
+	self.put(&event);
+	self.start_timer(&event);
+
+	Ok(())
+}
+
+/// Cancels the event.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn cancel(&self, delay_id: &str) -> Result {
+	let event = self.get(delay_id).await?;
+	self.remove(&event);
+
+	Ok(())
+}
+
+/// Sends the event now instead of when its delay elapses.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn send(&self, delay_id: &str) -> Result<OwnedEventId> {
+	let event = self.get(delay_id).await?;
+	self.remove(&event);
+
+	self.send_event(event).boxed().await
+}
+
+/// Delayed events scheduled by the user.
+#[implement(Service)]
+pub fn delayed_events_for<'a>(
+	&'a self,
+	user_id: &'a UserId,
+) -> impl Stream<Item = DelayedEvent> + Send + 'a {
+	let prefix = (user_id, Interfix);
+	self.db
+		.userid_delayid
+		.keys_prefix(&prefix)
+		.ignore_err()
+		.filter_map(async |(_, delay_id): (Ignore, &str)| self.get(delay_id).await.ok())
+}
+
+#[implement(Service)]
+pub async fn get(&self, delay_id: &str) -> Result<DelayedEvent> {
+	self.db
+		.delayid_delayedevent
+		.get(delay_id)
+		.await
+		.deserialized()
+		.map_err(|_| err!(Request(NotFound("Delayed event not found."))))
+}
+
+#[implement(Service)]
+async fn process_timer(&self, delay_id: &str) -> Result {
+	let Ok(event) = self.get(delay_id).await else {
+		return Ok(());
+	};
+
+	// the delay was restarted; a later timer sends the event
+	if due_in(&event) > Duration::ZERO {
+		return Ok(());
+	}
+
+	self.remove(&event);
+	self.send_event(event).boxed().await?;
+
+	Ok(())
+}
+
+/// Sends the event with the authority of its sender. State events are
+/// checked again against the room as it is now.
+#[implement(Service)]
+async fn send_event(&self, event: DelayedEvent) -> Result<OwnedEventId> {
+	let content = serde_json::from_str(event.content.get())
+		.map_err(|e| err!(Database("Invalid delayed event content: {e}")))?;
+
+	if let Some(state_key) = &event.state_key {
+		self.services
+			.state
+			.allowed_to_send_state_event(
+				&event.room_id,
+				&event.event_type.as_str().into(),
+				state_key,
+				&Raw::from_json(event.content.clone()),
+			)
+			.await?;
+	}
+
+	let state_lock = self
+		.services
+		.state
+		.mutex
+		.lock(&event.room_id)
+		.await;
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				event_type: event.event_type.into(),
+				content,
+				state_key: event.state_key.map(Into::into),
+				..Default::default()
+			},
+			&event.sender,
+			&event.room_id,
+			&state_lock,
+		)
+		.await
+}
+
+#[implement(Service)]
+fn start_timer(&self, event: &DelayedEvent) {
+	self.timer_channel
+		.0
+		.send((event.delay_id.clone(), due_in(event)))
+		.unwrap_or_else(|e| error!("Failed to add delayed event timer: {e}"));
+}
+
+#[implement(Service)]
+fn put(&self, event: &DelayedEvent) {
+	self.db
+		.delayid_delayedevent
+		.raw_put(&event.delay_id, Json(event));
+}
+
+#[implement(Service)]
+fn remove(&self, event: &DelayedEvent) {
+	self.db
+		.delayid_delayedevent
+		.remove(&event.delay_id);
+
+	self.db
+		.userid_delayid
+		.del((&event.sender, &event.delay_id));
+}
+
+fn due_in(event: &DelayedEvent) -> Duration {
+	let due = event.running_since.saturating_add(event.delay);
+
+	Duration::from_millis(due.saturating_sub(now_millis()))
+}
+
+async fn delay_timer(delay_id: String, timeout: Duration) -> String {
+	sleep(timeout).await;
+	delay_id
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use ruma::{owned_room_id, owned_user_id};
+	use serde_json::value::to_raw_value;
+	use tuwunel_core::utils::time::now_millis;
+
+	use super::{DelayedEvent, due_in};
+
+	fn event(delay: u64, running_since: u64) -> DelayedEvent {
+		DelayedEvent {
+			delay_id: "delay".to_owned(),
+			sender: owned_user_id!("@alice:example.com"),
+			room_id: owned_room_id!("!room:example.com"),
+			event_type: "m.room.message".to_owned(),
+			state_key: None,
+			delay,
+			running_since,
+			content: to_raw_value(&serde_json::json!({})).expect("valid json"),
+		}
+	}
+
+	#[test]
+	fn due_in_pending() {
+		let remaining = due_in(&event(60_000, now_millis()));
+		assert!(remaining > Duration::from_secs(50));
+		assert!(remaining <= Duration::from_secs(60));
+	}
+
+	#[test]
+	fn due_in_elapsed() {
+		let running_since = now_millis().saturating_sub(120_000);
+		assert_eq!(due_in(&event(60_000, running_since)), Duration::ZERO);
+	}
+
+	#[test]
+	fn due_in_saturates() {
+		assert!(due_in(&event(u64::MAX, u64::MAX)) > Duration::ZERO);
+		assert_eq!(due_in(&event(0, 0)), Duration::ZERO);
+	}
+}
@@ -12,7 +12,7 @@
 use data::Data;
 use regex::RegexSet;
 use ruma::{OwnedEventId, OwnedRoomAliasId, OwnedServerName, OwnedUserId, ServerName, UserId};
-use tuwunel_core::{Result, Server, error, utils::bytes::pretty};
+use tuwunel_core::{Result, Server, error, server::SERVER_USER_LOCALPART, utils::bytes::pretty};
 
 use crate::service;
 
@@ -69,7 +69,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			admin_alias: OwnedRoomAliasId::try_from(format!("#admins:{}", &args.server.name))
 				.expect("#admins:server_name is valid alias name"),
 			server_user: UserId::parse_with_server_name(
-				String::from("conduit"),
+				String::from(SERVER_USER_LOCALPART),
 				&args.server.name,
 			)
 			.expect("@conduit:server_name is valid"),
@@ -0,0 +1,185 @@
+use std::collections::{HashSet, VecDeque};
+
+use futures::FutureExt;
+use ruma::{
+	OwnedRoomId, OwnedServerName, RoomId, UserId,
+	room::{JoinRuleSummary, RoomType},
+};
+use tuwunel_core::{Error, Result, err, error, implement, info, warn};
+
+use super::Service;
+use crate::{
+	appservice::RegistrationInfo,
+	rooms::spaces::{Identifier, SummaryAccessibility, get_parent_children_via},
+};
+
+/// Upper bound on the rooms joined through one space of `auto_join_rooms`.
+const MAX_SPACE_ROOMS: usize = 256;
+
+/// Joins a newly registered user to the `auto_join_rooms`, returning the
+/// joins which failed. Spaces are joined along with their children the user
+/// may join, descending into subspaces in the background; failures joining
+/// those children are only logged.
+#[implement(Service)]
+#[tracing::instrument(skip(self, appservice_info), level = "debug")]
+pub async fn auto_join(
+	&self,
+	user_id: &UserId,
+	appservice_info: &Option<RegistrationInfo>,
+) -> Vec<Error> {
+	let mut failures = Vec::new();
+	for room in &self.services.server.config.auto_join_rooms {
+		let Ok(room_id) = self.services.alias.resolve(room).await else {
+			failures.push(err!(
+				"Failed to resolve room alias to room ID when attempting to auto join {room}"
+			));
+			continue;
+		};
+
+		if !self
+			.services
+			.state_cache
+			.server_in_room(self.services.globals.server_name(), &room_id)
+			.await
+		{
+			warn!("Skipping room {room} to automatically join as we have never joined before.");
+			continue;
+		}
+
+		let servers: Vec<OwnedServerName> = Some(self.services.globals.server_name())
+			.into_iter()
+			.chain(room.server_name())
+			.map(ToOwned::to_owned)
+			.collect();
+
+		if let Err(e) = self
+			.auto_join_room(user_id, &room_id, &servers, appservice_info)
+			.await
+		{
+			failures.push(e);
+			continue;
+		}
+
+		let is_space = self
+			.services
+			.state_accessor
+			.get_room_type(&room_id)
+			.await
+			.is_ok_and(|room_type| room_type == RoomType::Space);
+
+		if is_space {
+			let membership = self.services.membership.clone();
+			let (user_id, appservice_info) = (user_id.to_owned(), appservice_info.clone());
+			self.services.server.runtime().spawn(async move {
+				membership
+					.auto_join_space(&user_id, &room_id, &appservice_info)
+					.await;
+			});
+		}
+	}
+
+	failures
+}
+
+/// Joins the user to the children of the space it joined which are public or
+/// restricted to members of a room it is in, following the `via` servers of
+/// the `m.space.child` events.
+#[implement(Service)]
+async fn auto_join_space(
+	&self,
+	user_id: &UserId,
+	space_id: &RoomId,
+	appservice_info: &Option<RegistrationInfo>,
+) {
+	let suggested_only = self
+		.services
+		.server
+		.config
+		.auto_join_suggested_only;
+	let identifier = Identifier::UserId(user_id);
+
+	let mut seen: HashSet<OwnedRoomId> = HashSet::from([space_id.to_owned()]);
+	let mut queue: VecDeque<OwnedRoomId> = VecDeque::from([space_id.to_owned()]);
+	while let Some(parent_id) = queue.pop_front() {
+		let Ok(Some(SummaryAccessibility::Accessible(parent))) = self
+			.services
+			.spaces
+			.get_summary_and_children_local(&parent_id, &identifier)
+			.await
+		else {
+			continue;
+		};
+
+		for (child_id, via) in get_parent_children_via(&parent, suggested_only) {
+			if seen.len() >= MAX_SPACE_ROOMS || !seen.insert(child_id.clone()) {
+				continue;
+			}
+
+			let via: Vec<_> = via.collect();
+			let Ok(Some(SummaryAccessibility::Accessible(child))) = self
+				.services
+				.spaces
+				.get_summary_and_children_client(&child_id, suggested_only, user_id, &via)
+				.await
+			else {
+				continue;
+			};
+
+			// restricted rooms are only accessible while in one of the allowed rooms
+			if !matches!(
+				child.summary.join_rule,
+				JoinRuleSummary::Public | JoinRuleSummary::Restricted(_)
+			) {
+				continue;
+			}
+
+			if let Err(e) = self
+				.auto_join_room(user_id, &child_id, &via, appservice_info)
+				.await
+			{
+				error!("{e}");
+				continue;
+			}
+
+			if child.summary.room_type == Some(RoomType::Space) {
+				queue.push_back(child_id);
+			}
+		}
+	}
+}
+
+#[implement(Service)]
+async fn auto_join_room(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+	servers: &[OwnedServerName],
+	appservice_info: &Option<RegistrationInfo>,
+) -> Result {
+	if self
+		.services
+		.state_cache
+		.is_joined(user_id, room_id)
+		.await
+	{
+		return Ok(());
+	}
+
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+
+	self.join(
+		user_id,
+		room_id,
+		Some("Automatically joining this room upon registration".to_owned()),
+		servers,
+		appservice_info,
+		&state_lock,
+	)
+	.boxed()
+	.await
+	.map_err(|e| err!("Failed to automatically join room {room_id} for user {user_id}: {e}"))?;
+
+	info!("Automatically joined room {room_id} for user {user_id}");
+
+	Ok(())
+}
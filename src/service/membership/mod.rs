@@ -1,3 +1,4 @@
+mod auto_join;
 mod ban;
 mod invite;
 mod join;
@@ -13,6 +13,7 @@
 pub mod client;
 pub mod config;
 pub mod deactivate;
+pub mod delayed_events;
 pub mod emergency;
 pub mod federation;
 pub mod globals;
@@ -25,6 +26,7 @@
 pub mod rooms;
 pub mod sending;
 pub mod server_keys;
+pub mod server_notices;
 pub mod sync;
 pub mod transaction_ids;
 pub mod uiaa;
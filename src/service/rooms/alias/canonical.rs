@@ -0,0 +1,197 @@
+use futures::{FutureExt, StreamExt};
+use ruma::{
+	OwnedRoomAliasId, OwnedUserId, RoomAliasId, RoomId, UserId,
+	events::{StateEventType, room::canonical_alias::RoomCanonicalAliasEventContent},
+};
+use tuwunel_core::{
+	Err, Result, debug, err, implement, matrix::pdu::PduBuilder, utils::IterStream,
+};
+
+/// Removes the alias from the room's `m.room.canonical_alias` event. The new
+/// event is sent by the requesting user when permitted, otherwise by the
+/// server user. Returns false when the event did not contain the alias or
+/// neither can change it.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn remove_canonical_alias(
+	&self,
+	room_id: &RoomId,
+	alias: &RoomAliasId,
+	user_id: Option<&UserId>,
+) -> Result<bool> {
+	self.retain_canonical_aliases(room_id, user_id, |canonical| canonical != alias)
+		.await
+}
+
+/// Removes the local aliases from the room's `m.room.canonical_alias` event
+/// which no longer resolve to the room, sent by the server user. Returns false
+/// when there were none or the server user cannot change the event.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn remove_dangling_canonical_aliases(&self, room_id: &RoomId) -> Result<bool> {
+	let dangling = self.dangling_canonical_aliases(room_id).await;
+	if dangling.is_empty() {
+		return Ok(false);
+	}
+
+	self.retain_canonical_aliases(room_id, None, |alias| !dangling.iter().any(|d| d == alias))
+		.await
+}
+
+/// Local aliases in the room's `m.room.canonical_alias` event which do not
+/// exist or point to another room.
+#[implement(super::Service)]
+pub async fn dangling_canonical_aliases(&self, room_id: &RoomId) -> Vec<OwnedRoomAliasId> {
+	let Ok(content) = self.canonical_alias_content(room_id).await else {
+		return Vec::new();
+	};
+
+	content
+		.alias
+		.into_iter()
+		.chain(content.alt_aliases)
+		.filter(|alias| {
+			self.services
+				.globals
+				.server_is_ours(alias.server_name())
+		})
+		.stream()
+		.filter_map(async |alias| {
+			let resolves = self
+				.resolve_local_alias(&alias)
+				.await
+				.is_ok_and(|alias_room_id| alias_room_id == room_id);
+
+			(!resolves).then_some(alias)
+		})
+		.collect()
+		.await
+}
+
+/// Checks that the aliases a new `m.room.canonical_alias` event adds to the
+/// room resolve to it. Aliases already in the current event are not checked
+/// again so dangling ones can still be removed.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self, content), level = "debug")]
+pub async fn check_canonical_aliases(
+	&self,
+	room_id: &RoomId,
+	content: &RoomCanonicalAliasEventContent,
+) -> Result {
+	let current = self.canonical_alias_content(room_id).await.ok();
+	let is_current = |alias: &RoomAliasId| {
+		current.as_ref().is_some_and(|current| {
+			current.alias.as_deref() == Some(alias)
+				|| current
+					.alt_aliases
+					.iter()
+					.any(|current| current == alias)
+		})
+	};
+
+	for alias in content.alias.iter().chain(&content.alt_aliases) {
+		if is_current(alias) {
+			continue;
+		}
+
+		let (alias_room_id, _servers) = self
+			.resolve_alias(alias, None)
+			.await
+			.map_err(|e| err!(Request(BadAlias("Failed resolving alias \"{alias}\": {e}"))))?;
+
+		if alias_room_id != room_id {
+			return Err!(Request(BadAlias(
+				"Room alias {alias} does not belong to room {room_id}"
+			)));
+		}
+	}
+
+	Ok(())
+}
+
+#[implement(super::Service)]
+async fn retain_canonical_aliases<F>(
+	&self,
+	room_id: &RoomId,
+	user_id: Option<&UserId>,
+	retain: F,
+) -> Result<bool>
+where
+	F: Fn(&RoomAliasId) -> bool + Send,
+{
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+
+	let Ok(mut content) = self.canonical_alias_content(room_id).await else {
+		return Ok(false);
+	};
+
+	let alt_aliases = content.alt_aliases.len();
+	content.alt_aliases.retain(|alias| retain(alias));
+
+	let alias = content.alias.take_if(|alias| !retain(alias));
+	if alias.is_none() && alt_aliases == content.alt_aliases.len() {
+		return Ok(false);
+	}
+
+	let Some(sender) = self
+		.canonical_alias_sender(room_id, user_id)
+		.await
+	else {
+		debug!(%room_id, "No user may update the canonical alias");
+		return Ok(false);
+	};
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(String::new(), &content),
+			&sender,
+			room_id,
+			&state_lock,
+		)
+		.boxed()
+		.await?;
+
+	Ok(true)
+}
+
+/// The requesting user when joined and permitted to send the event, else the
+/// server user when it is.
+#[implement(super::Service)]
+async fn canonical_alias_sender(
+	&self,
+	room_id: &RoomId,
+	user_id: Option<&UserId>,
+) -> Option<OwnedUserId> {
+	let power_levels = self
+		.services
+		.state_accessor
+		.get_power_levels(room_id)
+		.await
+		.ok()?;
+
+	let server_user = self.services.globals.server_user.as_ref();
+	for sender in user_id.into_iter().chain([server_user]) {
+		if self
+			.services
+			.state_cache
+			.is_joined(sender, room_id)
+			.await && power_levels.user_can_send_state(sender, StateEventType::RoomCanonicalAlias)
+		{
+			return Some(sender.to_owned());
+		}
+	}
+
+	None
+}
+
+#[implement(super::Service)]
+async fn canonical_alias_content(
+	&self,
+	room_id: &RoomId,
+) -> Result<RoomCanonicalAliasEventContent> {
+	self.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomCanonicalAlias, "")
+		.await
+}
@@ -1,3 +1,4 @@
+mod canonical;
 mod remote;
 
 use std::sync::Arc;
@@ -79,21 +80,25 @@ pub async fn remove_alias(&self, alias: &RoomAliasId, user_id: &UserId) -> Resul
 			return Err!(Request(Forbidden("User is not permitted to remove this alias.")));
 		}
 
-		let alias = alias.alias();
-		let Ok(room_id) = self.db.alias_roomid.get(&alias).await else {
+		let Ok(room_id) = self.db.alias_roomid.get(alias.alias()).await else {
 			return Err!(Request(NotFound("Alias does not exist or is invalid.")));
 		};
 
 		let prefix = (&room_id, Interfix);
 		self.db
 			.aliasid_alias
-			.keys_prefix_raw(&prefix)
+			.stream_prefix_raw(&prefix)
 			.ignore_err()
-			.ready_for_each(|key| self.db.aliasid_alias.remove(key))
+			.ready_filter(|(_, val)| *val == alias.as_bytes())
+			.ready_for_each(|(key, _)| self.db.aliasid_alias.remove(key))
 			.await;
 
-		self.db.alias_roomid.remove(alias.as_bytes());
-		self.db.alias_userid.remove(alias.as_bytes());
+		self.db
+			.alias_roomid
+			.remove(alias.alias().as_bytes());
+		self.db
+			.alias_userid
+			.remove(alias.alias().as_bytes());
 
 		Ok(())
 	}
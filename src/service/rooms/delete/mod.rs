@@ -1,10 +1,14 @@
 use std::{sync::Arc, time::Duration};
 
 use futures::{FutureExt, StreamExt, pin_mut};
-use ruma::RoomId;
+use ruma::{
+	OwnedUserId, RoomId, UserId,
+	events::room::member::{MembershipState, RoomMemberEventContent},
+};
 use tokio::time::sleep;
 use tuwunel_core::{
-	Result, debug,
+	Result, debug, debug_warn,
+	matrix::pdu::PduBuilder,
 	result::LogErr,
 	trace,
 	utils::{ReadyExt, future::BoolExt},
@@ -61,13 +65,32 @@ pub async fn delete_if_empty_local(&self, room_id: &RoomId, state_lock: RoomMute
 
 		self.services
 			.delete
-			.delete_room(room_id, state_lock)
+			.delete_room(room_id, false, None, state_lock)
 			.boxed()
 			.await
 			.expect("unhandled error during room deletion");
 	}
 
-	pub async fn delete_room(&self, room_id: &RoomId, state_lock: RoomMutexGuard) -> Result {
+	/// Deletes the room from our database.
+	///
+	/// - With `block` the room stays banned and federation with it disabled
+	///   afterwards so local users cannot join it again
+	/// - With an `evacuate_reason` local users are first kicked from the room
+	///   with that reason
+	pub async fn delete_room(
+		&self,
+		room_id: &RoomId,
+		block: bool,
+		evacuate_reason: Option<String>,
+		state_lock: RoomMutexGuard,
+	) -> Result {
+		if let Some(reason) = evacuate_reason {
+			debug!("Evacuating local users from {room_id} prior to deletion.");
+			self.evacuate(room_id, &reason, &state_lock)
+				.boxed()
+				.await;
+		}
+
 		// ban the room locally so new users cannot join while we're in the process of
 		// deleting it
 		debug!("Banning room {room_id} prior to deletion.");
@@ -201,13 +224,93 @@ pub async fn delete_room(&self, room_id: &RoomId, state_lock: RoomMutexGuard) ->
 			.log_err()
 			.ok();
 
-		// TODO: add option to keep a room banned (`--block` or `--ban`)
-		self.services.metadata.enable_room(room_id);
-		self.services.metadata.unban_room(room_id);
+		if block {
+			debug!("Keeping room {room_id} banned and disabled");
+		} else {
+			self.services.metadata.enable_room(room_id);
+			self.services.metadata.unban_room(room_id);
+		}
 
 		drop(state_lock);
 
 		debug!("Successfully deleted room {room_id} from our database");
 		Ok(())
 	}
+
+	/// Kicks the local users out of the room with the reason, sent by the
+	/// server user when it is joined with the power to kick them. Users it
+	/// cannot kick are made to leave with the reason instead.
+	async fn evacuate(&self, room_id: &RoomId, reason: &str, state_lock: &RoomMutexGuard) {
+		let server_user = &self.services.globals.server_user;
+		let server_joined = self
+			.services
+			.state_cache
+			.is_joined(server_user, room_id)
+			.await;
+
+		let power_levels = self
+			.services
+			.state_accessor
+			.get_power_levels(room_id)
+			.await
+			.ok()
+			.filter(|_| server_joined);
+
+		let users: Vec<OwnedUserId> = self
+			.services
+			.state_cache
+			.local_users_in_room(room_id)
+			.ready_filter(|user_id| user_id != server_user)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		for user_id in &users {
+			let kick = RoomMemberEventContent {
+				reason: Some(reason.to_owned()),
+				..RoomMemberEventContent::new(MembershipState::Leave)
+			};
+
+			let kicked = power_levels.as_ref().is_some_and(|power_levels| {
+				power_levels.user_can_kick_user(server_user, user_id)
+			}) && self
+				.services
+				.timeline
+				.build_and_append_pdu(
+					PduBuilder::state(user_id.to_string(), &kick),
+					server_user,
+					room_id,
+					state_lock,
+				)
+				.await
+				.inspect_err(|e| debug_warn!(%user_id, "Failed to kick from {room_id}: {e}"))
+				.is_ok();
+
+			if !kicked {
+				self.leave(user_id, room_id, reason, state_lock)
+					.await;
+			}
+		}
+
+		if server_joined {
+			self.leave(server_user, room_id, reason, state_lock)
+				.await;
+		}
+	}
+
+	async fn leave(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		reason: &str,
+		state_lock: &RoomMutexGuard,
+	) {
+		self.services
+			.membership
+			.leave(user_id, room_id, Some(reason.to_owned()), state_lock)
+			.boxed()
+			.await
+			.log_err()
+			.ok();
+	}
 }
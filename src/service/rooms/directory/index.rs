@@ -0,0 +1,397 @@
+use std::{
+	cmp::Reverse,
+	collections::{BTreeSet, HashMap},
+	fmt,
+	str::FromStr,
+};
+
+use futures::{
+	FutureExt, StreamExt, TryFutureExt,
+	future::{join, join4, join5},
+};
+use ruma::{
+	OwnedRoomId, RoomAliasId, RoomId, UInt,
+	api::client::directory::get_public_rooms_filtered,
+	directory::{Filter, PublicRoomsChunk, RoomTypeFilter},
+	events::{
+		StateEventType,
+		room::join_rules::{JoinRule, RoomJoinRulesEventContent},
+	},
+	uint,
+};
+use tuwunel_core::{
+	Err, Error, Result, err, implement,
+	utils::{IterStream, TryFutureExtExt, result::FlatOk, stream::ReadyExt},
+};
+
+/// Public rooms of this server ordered by joined members, most first, kept
+/// up to date as their state and members change.
+#[derive(Default)]
+pub(super) struct Index {
+	order: BTreeSet<IndexKey>,
+	rooms: HashMap<OwnedRoomId, Entry>,
+}
+
+struct Entry {
+	key: IndexKey,
+	chunk: PublicRoomsChunk,
+
+	/// Lowercased name, topic and canonical alias matched by searches.
+	search_text: String,
+}
+
+type IndexKey = (Reverse<UInt>, OwnedRoomId);
+
+/// Position in the directory a page starts after (`n`) or ends before (`p`).
+enum PageToken {
+	Next(IndexKey),
+	Prev(IndexKey),
+}
+
+/// Page of the public rooms of this server matching the filter.
+///
+/// - Rooms are ordered by the number of joined members
+/// - The words of the search term must all appear in the room's name, topic or
+///   canonical alias; room ids are matched as well when enabled
+/// - The `since` tokens refer to a room rather than an offset so pages remain
+///   consistent as rooms are added or removed
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn public_rooms_page(
+	&self,
+	filter: &Filter,
+	since: Option<&str>,
+	limit: usize,
+) -> Result<get_public_rooms_filtered::v3::Response> {
+	let since = since.map(str::parse::<PageToken>).transpose()?;
+
+	let search_term = filter
+		.generic_search_term
+		.as_deref()
+		.map(str::to_lowercase);
+
+	let terms: Vec<&str> = search_term
+		.as_deref()
+		.map(str::split_whitespace)
+		.into_iter()
+		.flatten()
+		.collect();
+
+	let search_room_id = filter
+		.generic_search_term
+		.as_deref()
+		.filter(|_| {
+			self.services
+				.server
+				.config
+				.allow_public_room_search_by_id
+		})
+		.filter(|s| s.starts_with('!'))
+		.filter(|s| s.len() > 5); // require some characters to limit scope.
+
+	let unlisted: Vec<Entry> = search_room_id
+		.filter(|_| {
+			self.services
+				.server
+				.config
+				.allow_unlisted_room_search_by_id
+		})
+		.map(|prefix| self.services.metadata.public_ids_prefix(prefix))
+		.into_iter()
+		.stream()
+		.flatten()
+		.ready_filter(|room_id| {
+			!self
+				.index
+				.read()
+				.expect("locked")
+				.contains(room_id)
+		})
+		.filter_map(async |room_id| {
+			(!self.services.metadata.is_banned(&room_id).await).then_some(room_id)
+		})
+		.then(|room_id| self.public_room_chunk(room_id))
+		.map(Entry::new)
+		.collect()
+		.await;
+
+	let matches = |entry: &Entry| {
+		let room_type = RoomTypeFilter::from(entry.chunk.room_type.clone());
+		let by_room_id =
+			search_room_id.is_some_and(|id| entry.chunk.room_id.as_str().contains(id));
+
+		(filter.room_types.is_empty() || filter.room_types.contains(&room_type))
+			&& (by_room_id
+				|| terms
+					.iter()
+					.all(|term| entry.search_text.contains(term)))
+	};
+
+	let index = self.index.read().expect("locked");
+	let mut entries: Vec<&Entry> = index
+		.order
+		.iter()
+		.filter_map(|(_, room_id)| index.rooms.get(room_id))
+		.chain(&unlisted)
+		.filter(|entry| matches(entry))
+		.collect();
+
+	if !unlisted.is_empty() {
+		entries.sort_by(|a, b| a.key.cmp(&b.key));
+	}
+
+	let total = entries.len();
+	let (start, end) = match &since {
+		| None => (0, limit.min(total)),
+		| Some(PageToken::Next(key)) => {
+			let start = entries.partition_point(|entry| entry.key <= *key);
+			(start, start.saturating_add(limit).min(total))
+		},
+		| Some(PageToken::Prev(key)) => {
+			let end = entries.partition_point(|entry| entry.key < *key);
+			(end.saturating_sub(limit), end)
+		},
+	};
+
+	let page = entries.get(start..end).unwrap_or_default();
+
+	let prev_batch = page
+		.first()
+		.filter(|_| start > 0)
+		.map(|entry| PageToken::Prev(entry.key.clone()).to_string());
+
+	let next_batch = page
+		.last()
+		.filter(|_| end < total)
+		.map(|entry| PageToken::Next(entry.key.clone()).to_string());
+
+	Ok(get_public_rooms_filtered::v3::Response {
+		chunk: page
+			.iter()
+			.map(|entry| entry.chunk.clone())
+			.collect(),
+		prev_batch,
+		next_batch,
+		total_room_count_estimate: UInt::try_from(total).ok(),
+	})
+}
+
+/// Updates the entry of a public room in the index after its state or members
+/// changed.
+#[implement(super::Service)]
+pub async fn update_room(&self, room_id: &RoomId) {
+	if !self
+		.index
+		.read()
+		.expect("locked")
+		.contains(room_id)
+	{
+		return;
+	}
+
+	self.index_room(room_id).await;
+}
+
+#[implement(super::Service)]
+pub(super) async fn index_room(&self, room_id: &RoomId) {
+	if !self.is_public_room(room_id).await || self.services.metadata.is_banned(room_id).await {
+		self.index
+			.write()
+			.expect("locked")
+			.remove(room_id);
+
+		return;
+	}
+
+	let chunk = self.public_room_chunk(room_id.to_owned()).await;
+	self.index
+		.write()
+		.expect("locked")
+		.insert(Entry::new(chunk));
+}
+
+#[implement(super::Service)]
+async fn public_room_chunk(&self, room_id: OwnedRoomId) -> PublicRoomsChunk {
+	let state_accessor = &self.services.state_accessor;
+
+	let name = state_accessor.get_name(&room_id).ok();
+
+	let room_type = state_accessor.get_room_type(&room_id).ok();
+
+	let canonical_alias = state_accessor.get_canonical_alias(&room_id).ok();
+
+	let avatar_url = state_accessor.get_avatar(&room_id);
+
+	let topic = state_accessor.get_room_topic(&room_id).ok();
+
+	let world_readable = state_accessor.is_world_readable(&room_id);
+
+	let join_rule = state_accessor
+		.room_state_get_content(&room_id, &StateEventType::RoomJoinRules, "")
+		.map_ok(|c: RoomJoinRulesEventContent| match c.join_rule {
+			| JoinRule::Public => "public".into(),
+			| JoinRule::Knock => "knock".into(),
+			| JoinRule::KnockRestricted(_) => "knock_restricted".into(),
+			| _ => "invite".into(),
+		});
+
+	let guest_can_join = state_accessor.guest_can_join(&room_id);
+
+	let num_joined_members = self
+		.services
+		.state_cache
+		.room_joined_count(&room_id);
+
+	let (
+		(avatar_url, canonical_alias, guest_can_join, join_rule, name),
+		(num_joined_members, room_type, topic, world_readable),
+	) = join(
+		join5(avatar_url, canonical_alias, guest_can_join, join_rule, name),
+		join4(num_joined_members, room_type, topic, world_readable),
+	)
+	.boxed()
+	.await;
+
+	PublicRoomsChunk {
+		avatar_url: avatar_url.into_option().unwrap_or_default().url,
+		canonical_alias,
+		guest_can_join,
+		join_rule: join_rule.unwrap_or_default(),
+		name,
+		num_joined_members: num_joined_members
+			.map(TryInto::try_into)
+			.map(Result::ok)
+			.flat_ok()
+			.unwrap_or_else(|| uint!(0)),
+		room_id,
+		room_type,
+		topic,
+		world_readable,
+	}
+}
+
+impl Index {
+	pub(super) fn len(&self) -> usize { self.rooms.len() }
+
+	fn contains(&self, room_id: &RoomId) -> bool { self.rooms.contains_key(room_id) }
+
+	fn insert(&mut self, entry: Entry) {
+		self.remove(&entry.chunk.room_id);
+		self.order.insert(entry.key.clone());
+		self.rooms
+			.insert(entry.chunk.room_id.clone(), entry);
+	}
+
+	pub(super) fn remove(&mut self, room_id: &RoomId) {
+		if let Some(entry) = self.rooms.remove(room_id) {
+			self.order.remove(&entry.key);
+		}
+	}
+}
+
+impl Entry {
+	fn new(chunk: PublicRoomsChunk) -> Self {
+		let search_text = [
+			chunk.name.as_deref(),
+			chunk.topic.as_deref(),
+			chunk
+				.canonical_alias
+				.as_deref()
+				.map(RoomAliasId::as_str),
+		]
+		.into_iter()
+		.flatten()
+		.collect::<Vec<_>>()
+		.join("\n")
+		.to_lowercase();
+
+		Self {
+			key: (Reverse(chunk.num_joined_members), chunk.room_id.clone()),
+			chunk,
+			search_text,
+		}
+	}
+}
+
+impl FromStr for PageToken {
+	type Err = Error;
+
+	fn from_str(token: &str) -> Result<Self> {
+		let invalid = || err!(Request(InvalidParam("Invalid `since` token.")));
+		let (members, room_id) = token
+			.get(1..)
+			.and_then(|key| key.split_once('_'))
+			.ok_or_else(invalid)?;
+
+		let members = members.parse().map_err(|_| invalid())?;
+		let room_id = RoomId::parse(room_id).map_err(|_| invalid())?;
+		let key = (Reverse(members), room_id);
+
+		match token.chars().next() {
+			| Some('n') => Ok(Self::Next(key)),
+			| Some('p') => Ok(Self::Prev(key)),
+			| _ => Err!(Request(InvalidParam("Invalid `since` token."))),
+		}
+	}
+}
+
+impl fmt::Display for PageToken {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (direction, (Reverse(members), room_id)) = match self {
+			| Self::Next(key) => ('n', key),
+			| Self::Prev(key) => ('p', key),
+		};
+
+		write!(f, "{direction}{members}_{room_id}")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cmp::Reverse;
+
+	use ruma::{room_id, uint};
+
+	use super::PageToken;
+
+	#[test]
+	fn page_token_display() {
+		let key = (Reverse(uint!(42)), room_id!("!room:example.com").to_owned());
+
+		assert_eq!(PageToken::Next(key.clone()).to_string(), "n42_!room:example.com");
+		assert_eq!(PageToken::Prev(key).to_string(), "p42_!room:example.com");
+	}
+
+	#[test]
+	fn page_token_round_trip() {
+		for token in ["n0_!room:example.com", "p1000_!a_b:example.com"] {
+			let parsed: PageToken = token.parse().expect("valid token");
+			assert_eq!(parsed.to_string(), token);
+		}
+	}
+
+	#[test]
+	fn page_token_parse() {
+		let Ok(PageToken::Prev((Reverse(members), room_id))) = "p7_!a_b:example.com".parse()
+		else {
+			panic!("expected a previous page token");
+		};
+
+		assert_eq!(members, uint!(7));
+		assert_eq!(room_id, "!a_b:example.com");
+	}
+
+	#[test]
+	fn page_token_rejects_malformed() {
+		for token in [
+			"",
+			"n",
+			"x1_!room:example.com",
+			"n_!room:example.com",
+			"nx_!room:example.com",
+			"n1_room",
+		] {
+			assert!(token.parse::<PageToken>().is_err(), "{token}");
+		}
+	}
+}
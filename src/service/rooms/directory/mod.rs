@@ -1,35 +1,90 @@
-use std::sync::Arc;
+mod index;
+mod remote;
 
-use futures::Stream;
+use std::{
+	fmt::Write,
+	sync::{Arc, Mutex, RwLock},
+};
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use lru_cache::LruCache;
 use ruma::{RoomId, api::client::room::Visibility};
 use tuwunel_core::{Result, implement, utils::stream::TryIgnore};
 use tuwunel_database::Map;
 
+use self::{index::Index, remote::RemoteCache};
+
+/// Capacity of the cache of other servers' room directories.
+const REMOTE_CACHE_CAPACITY: usize = 256;
+
 pub struct Service {
+	services: Arc<crate::services::OnceServices>,
 	db: Data,
+	index: RwLock<Index>,
+	remote_cache: Mutex<RemoteCache>,
 }
 
 struct Data {
 	publicroomids: Arc<Map>,
 }
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
+			services: args.services.clone(),
 			db: Data {
 				publicroomids: args.db["publicroomids"].clone(),
 			},
+			index: RwLock::default(),
+			remote_cache: LruCache::new(REMOTE_CACHE_CAPACITY).into(),
 		}))
 	}
 
+	async fn worker(self: Arc<Self>) -> Result {
+		let room_ids: Vec<_> = self
+			.public_rooms()
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		for room_id in room_ids {
+			self.index_room(&room_id).await;
+		}
+
+		Ok(())
+	}
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let index = self.index.read().expect("locked").len();
+		let remote_cache = self.remote_cache.lock().expect("locked").len();
+
+		writeln!(out, "public_rooms_index: {index}")?;
+		writeln!(out, "remote_directory_cache: {remote_cache}")?;
+
+		Ok(())
+	}
+
+	async fn clear_cache(&self) { self.remote_cache.lock().expect("locked").clear(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 #[implement(Service)]
-pub fn set_public(&self, room_id: &RoomId) { self.db.publicroomids.insert(room_id, []); }
+pub async fn set_public(&self, room_id: &RoomId) {
+	self.db.publicroomids.insert(room_id, []);
+	self.update_room(room_id).await;
+}
 
 #[implement(Service)]
-pub fn set_not_public(&self, room_id: &RoomId) { self.db.publicroomids.remove(room_id); }
+pub fn set_not_public(&self, room_id: &RoomId) {
+	self.db.publicroomids.remove(room_id);
+	self.index
+		.write()
+		.expect("locked")
+		.remove(room_id);
+}
 
 #[implement(Service)]
 pub fn public_rooms(&self) -> impl Stream<Item = &RoomId> + Send {
@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use lru_cache::LruCache;
+use ruma::{
+	OwnedServerName, ServerName, UInt,
+	api::federation::directory::get_public_rooms_filtered,
+	directory::{Filter, RoomNetwork},
+};
+use tuwunel_core::{Result, implement};
+
+pub(super) type RemoteCache =
+	LruCache<(OwnedServerName, String), (Instant, get_public_rooms_filtered::v1::Response)>;
+
+/// Public rooms of another server's directory. Responses are cached for
+/// `remote_directory_cache_ttl` per server and query.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn remote_public_rooms(
+	&self,
+	server: &ServerName,
+	limit: Option<UInt>,
+	since: Option<&str>,
+	filter: &Filter,
+) -> Result<get_public_rooms_filtered::v1::Response> {
+	let ttl = Duration::from_secs(
+		self.services
+			.server
+			.config
+			.remote_directory_cache_ttl,
+	);
+	let query =
+		format!("{limit:?}|{since:?}|{:?}|{:?}", filter.generic_search_term, filter.room_types);
+
+	let key = (server.to_owned(), query);
+	if let Some((fetched, response)) = self
+		.remote_cache
+		.lock()
+		.expect("locked")
+		.get_mut(&key)
+	{
+		if fetched.elapsed() < ttl {
+			return Ok(response.clone());
+		}
+	}
+
+	let response = self
+		.services
+		.sending
+		.send_federation_request(server, get_public_rooms_filtered::v1::Request {
+			limit,
+			since: since.map(ToOwned::to_owned),
+			filter: Filter {
+				generic_search_term: filter.generic_search_term.clone(),
+				room_types: filter.room_types.clone(),
+			},
+			room_network: RoomNetwork::Matrix,
+		})
+		.await?;
+
+	if !ttl.is_zero() {
+		self.remote_cache
+			.lock()
+			.expect("locked")
+			.insert(key, (Instant::now(), response.clone()));
+	}
+
+	Ok(response)
+}
@@ -17,4 +17,5 @@
 pub mod threads;
 pub mod timeline;
 pub mod typing;
+pub mod upgrade;
 pub mod user;
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use futures::{StreamExt, future::try_join};
+use ruma::{
+	OwnedEventId, UInt, UserId,
+	api::Direction,
+	events::{AnyMessageLikeEvent, TimelineEventType, relation::BundledThread},
+	serde::Raw,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tuwunel_core::{
+	Result, implement,
+	matrix::{Event, PduCount, PduEvent},
+};
+
+/// Upper bound on the relations of one event considered for its bundled
+/// aggregations. The newest are considered so the latest edit and thread reply
+/// are always found; only counts are affected past the bound.
+const MAX_BUNDLED_RELATIONS: usize = 1024;
+
+#[derive(Deserialize)]
+struct ExtractRelatesTo {
+	#[serde(rename = "m.relates_to")]
+	relates_to: RelatesTo,
+}
+
+#[derive(Deserialize)]
+struct RelatesTo {
+	rel_type: String,
+	event_id: OwnedEventId,
+	key: Option<String>,
+}
+
+/// Adds the bundled aggregations of the event's relations to its
+/// `unsigned.m.relations` as seen by the user: the latest edit, reaction
+/// counts, references and a summary of its thread.
+///
+/// - Relations from ignored users and redacted relations are not counted
+/// - Redacted events are left without aggregations
+#[implement(super::Service)]
+#[tracing::instrument(skip_all, fields(event_id = %pdu.event_id), level = "debug")]
+pub async fn bundle_aggregations(&self, user_id: &UserId, pdu: &mut PduEvent) -> Result {
+	if pdu.is_redacted() {
+		return Ok(());
+	}
+
+	let shortroomid = self.services.short.get_shortroomid(&pdu.room_id);
+	let target = self
+		.services
+		.timeline
+		.get_pdu_count(&pdu.event_id);
+	let (shortroomid, target) = try_join(shortroomid, target).await?;
+
+	let relations: Vec<_> = self
+		.db
+		.get_relations(
+			user_id,
+			shortroomid,
+			target.into_unsigned(),
+			PduCount::max(),
+			Direction::Backward,
+		)
+		.take(MAX_BUNDLED_RELATIONS)
+		.collect()
+		.await;
+
+	let mut replace: Option<&PduEvent> = None;
+	let mut annotations: BTreeMap<(&TimelineEventType, String), u64> = BTreeMap::new();
+	let mut references: Vec<&OwnedEventId> = Vec::new();
+	let mut thread: Option<(PduCount, &PduEvent)> = None;
+	let mut thread_count: u64 = 0;
+	let mut thread_participated = pdu.sender == user_id;
+
+	for (count, relation) in &relations {
+		let relation = relation.as_pdu();
+		let Ok(ExtractRelatesTo { relates_to }) = relation.get_content() else {
+			continue;
+		};
+
+		if relates_to.event_id != pdu.event_id
+			|| self
+				.services
+				.users
+				.user_is_ignored(&relation.sender, user_id)
+				.await
+		{
+			continue;
+		}
+
+		match relates_to.rel_type.as_str() {
+			| "m.replace" if relation.sender == pdu.sender => {
+				let newer = |latest: &PduEvent| {
+					(latest.origin_server_ts, &latest.event_id)
+						< (relation.origin_server_ts, &relation.event_id)
+				};
+
+				if replace.is_none_or(newer) {
+					replace = Some(relation);
+				}
+			},
+			| "m.annotation" =>
+				if let Some(key) = relates_to.key {
+					let reactions = annotations
+						.entry((&relation.kind, key))
+						.or_default();
+
+					*reactions = reactions.saturating_add(1);
+				},
+			| "m.reference" => references.push(&relation.event_id),
+			| "m.thread" => {
+				thread_count = thread_count.saturating_add(1);
+				thread_participated |= relation.sender == user_id;
+				if thread.is_none_or(|(latest, _)| *count > latest) {
+					thread = Some((*count, relation));
+				}
+			},
+			| _ => {},
+		}
+	}
+
+	if let Some(replace) = replace {
+		pdu.add_relation("m.replace", &replace.to_format::<Raw<AnyMessageLikeEvent>>())?;
+	}
+
+	if !annotations.is_empty() {
+		let mut chunk: Vec<_> = annotations.into_iter().collect();
+		chunk.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+		let chunk: Vec<_> = chunk
+			.into_iter()
+			.map(|((kind, key), count)| json!({ "type": kind, "key": key, "count": count }))
+			.collect();
+
+		pdu.add_relation("m.annotation", &json!({ "chunk": chunk }))?;
+	}
+
+	if !references.is_empty() {
+		let chunk: Vec<_> = references
+			.into_iter()
+			.map(|event_id| json!({ "event_id": event_id }))
+			.collect();
+
+		pdu.add_relation("m.reference", &json!({ "chunk": chunk }))?;
+	}
+
+	if let Some((_, latest)) = thread {
+		pdu.add_relation("m.thread", &BundledThread {
+			latest_event: latest.to_format(),
+			count: UInt::new_saturating(thread_count),
+			current_user_participated: thread_participated,
+		})?;
+	}
+
+	Ok(())
+}
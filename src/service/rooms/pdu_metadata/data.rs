@@ -14,7 +14,7 @@
 		u64_from_u8,
 	},
 };
-use tuwunel_database::{Interfix, Map};
+use tuwunel_database::{Ignore, Interfix, Map};
 
 use crate::rooms::{
 	short::{ShortEventId, ShortRoomId},
@@ -24,6 +24,7 @@
 pub struct Data {
 	tofrom_relation: Arc<Map>,
 	referencedevents: Arc<Map>,
+	roomeventid_pendingrelation: Arc<Map>,
 	softfailedeventids: Arc<Map>,
 	services: Arc<crate::services::OnceServices>,
 }
@@ -34,6 +35,7 @@ pub fn new(args: &crate::Args<'_>) -> Self {
 		Self {
 			tofrom_relation: db["tofrom_relation"].clone(),
 			referencedevents: db["referencedevents"].clone(),
+			roomeventid_pendingrelation: db["roomeventid_pendingrelation"].clone(),
 			softfailedeventids: db["softfailedeventids"].clone(),
 			services: args.services.clone(),
 		}
@@ -48,6 +50,32 @@ pub fn add_relation(&self, from: u64, to: u64) {
 			.aput_raw::<BUFSIZE, _, _>(key, []);
 	}
 
+	#[inline]
+	pub fn add_pending_relation(&self, room_id: &RoomId, to: &EventId, from: u64) {
+		let key = (room_id, to, from);
+		self.roomeventid_pendingrelation.put_raw(key, []);
+	}
+
+	pub async fn take_pending_relations(&self, room_id: &RoomId, to: &EventId) -> Vec<u64> {
+		type Key = (Ignore, Ignore, u64);
+
+		let prefix = (room_id, to, Interfix);
+		let pending: Vec<u64> = self
+			.roomeventid_pendingrelation
+			.keys_prefix(&prefix)
+			.ignore_err()
+			.map(|(Ignore, Ignore, from): Key| from)
+			.collect()
+			.await;
+
+		for from in &pending {
+			self.roomeventid_pendingrelation
+				.del((room_id, to, *from));
+		}
+
+		pending
+	}
+
 	pub fn get_relations<'a>(
 		&'a self,
 		user_id: &'a UserId,
@@ -142,6 +170,12 @@ pub async fn delete_all_referenced_for_room(&self, room_id: &RoomId) -> Result {
 			})
 			.await;
 
+		self.roomeventid_pendingrelation
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| self.roomeventid_pendingrelation.remove(key))
+			.await;
+
 		Ok(())
 	}
 }
@@ -1,3 +1,4 @@
+mod aggregate;
 mod data;
 use std::sync::Arc;
 
@@ -29,12 +30,27 @@ fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 impl Service {
 	#[tracing::instrument(skip(self, from, to), level = "debug")]
 	pub fn add_relation(&self, from: PduCount, to: PduCount) {
-		match (from, to) {
-			| (PduCount::Normal(f), PduCount::Normal(t)) => self.db.add_relation(f, t),
-			| _ => {
-				// TODO: Relations with backfilled pdus
-			},
-		}
+		self.db
+			.add_relation(from.into_unsigned(), to.into_unsigned());
+	}
+
+	/// Remembers a relation to an event we don't have yet, e.g. a reaction
+	/// backfilled before the message it reacts to.
+	#[tracing::instrument(skip(self, from), level = "debug")]
+	pub fn add_pending_relation(&self, room_id: &RoomId, to: &EventId, from: PduCount) {
+		self.db
+			.add_pending_relation(room_id, to, from.into_unsigned());
+	}
+
+	/// Removes and returns the relations which were waiting for the event.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn take_pending_relations(&self, room_id: &RoomId, to: &EventId) -> Vec<PduCount> {
+		self.db
+			.take_pending_relations(room_id, to)
+			.await
+			.into_iter()
+			.map(PduCount::from_unsigned)
+			.collect()
 	}
 
 	#[allow(clippy::too_many_arguments)]
@@ -56,15 +72,9 @@ pub async fn get_relations<'a>(
 			return Vec::new();
 		};
 
-		let target = match target {
-			| PduCount::Normal(c) => c,
-			// TODO: Support backfilled relations
-			| _ => 0, // This will result in an empty iterator
-		};
-
 		let mut pdus: Vec<_> = self
 			.db
-			.get_relations(user_id, room_id, target, from, dir)
+			.get_relations(user_id, room_id, target.into_unsigned(), from, dir)
 			.collect()
 			.await;
 
@@ -75,12 +85,7 @@ pub async fn get_relations<'a>(
 			.collect();
 
 		'limit: while let Some(stack_pdu) = stack.pop() {
-			let target = match stack_pdu.0.0 {
-				| PduCount::Normal(c) => c,
-				// TODO: Support backfilled relations
-				| PduCount::Backfilled(_) => 0, // This will result in an empty iterator
-			};
-
+			let target = stack_pdu.0.0.into_unsigned();
 			let relations: Vec<_> = self
 				.db
 				.get_relations(user_id, room_id, target, from, dir)
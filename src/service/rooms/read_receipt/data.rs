@@ -3,7 +3,10 @@
 use futures::{Stream, StreamExt};
 use ruma::{
 	CanonicalJsonObject, RoomId, UserId,
-	events::{AnySyncEphemeralRoomEvent, receipt::ReceiptEvent},
+	events::{
+		AnySyncEphemeralRoomEvent,
+		receipt::{Receipt, ReceiptEvent, ReceiptThread},
+	},
 	serde::Raw,
 };
 use tuwunel_core::{
@@ -14,9 +17,11 @@
 
 pub struct Data {
 	roomuserid_privateread: Arc<Map>,
+	roomuserid_privatereadreceipt: Arc<Map>,
 	roomuserid_lastprivatereadupdate: Arc<Map>,
 	services: Arc<crate::services::OnceServices>,
 	readreceiptid_readreceipt: Arc<Map>,
+	roomuserthread_readreceiptcount: Arc<Map>,
 }
 
 pub type ReceiptItem<'a> = (&'a UserId, u64, Raw<AnySyncEphemeralRoomEvent>);
@@ -26,9 +31,11 @@ pub fn new(args: &crate::Args<'_>) -> Self {
 		let db = &args.db;
 		Self {
 			roomuserid_privateread: db["roomuserid_privateread"].clone(),
+			roomuserid_privatereadreceipt: db["roomuserid_privatereadreceipt"].clone(),
 			roomuserid_lastprivatereadupdate: db["roomuserid_lastprivatereadupdate"].clone(),
 			readreceiptid_readreceipt: db["readreceiptid_readreceipt"].clone(),
 			services: args.services.clone(),
+			roomuserthread_readreceiptcount: db["roomuserthread_readreceiptcount"].clone(),
 		}
 	}
 
@@ -39,20 +46,41 @@ pub async fn readreceipt_update(
 		room_id: &RoomId,
 		event: &ReceiptEvent,
 	) {
-		// Remove old entry
-		let last_possible_key = (room_id, u64::MAX);
-		self.readreceiptid_readreceipt
-			.rev_keys_from_raw(&last_possible_key)
-			.ignore_err()
-			.ready_take_while(|key| key.starts_with(room_id.as_bytes()))
-			.ready_filter_map(|key| key.ends_with(user_id.as_bytes()).then_some(key))
-			.ready_for_each(|key| self.readreceiptid_readreceipt.del(key))
-			.await;
+		// Remove old entry of the same thread; the user keeps one receipt per thread
+		let thread = receipt_thread(event);
+		let user_thread = (room_id, user_id, thread_key(thread));
+		match self
+			.roomuserthread_readreceiptcount
+			.qry(&user_thread)
+			.await
+			.deserialized::<u64>()
+		{
+			| Ok(last_count) => {
+				let last_id = (room_id, last_count, user_id);
+				self.readreceiptid_readreceipt.del(last_id);
+			},
+			| Err(_) => {
+				// Receipts stored before they were indexed by user are searched for.
+				let prefix = (room_id, Interfix);
+				self.readreceiptid_readreceipt
+					.stream_prefix_raw(&prefix)
+					.ignore_err()
+					.ready_filter(|(key, _)| key.ends_with(user_id.as_bytes()))
+					.ready_filter(|(_, val)| {
+						serde_json::from_slice::<ReceiptEvent>(val)
+							.is_ok_and(|old| receipt_thread(&old) == thread)
+					})
+					.ready_for_each(|(key, _)| self.readreceiptid_readreceipt.remove(key))
+					.await;
+			},
+		}
 
 		let count = self.services.globals.next_count();
 		let latest_id = (room_id, *count, user_id);
 		self.readreceiptid_readreceipt
 			.put(latest_id, Json(event));
+		self.roomuserthread_readreceiptcount
+			.put(user_thread, *count);
 	}
 
 	#[inline]
@@ -85,11 +113,19 @@ pub fn readreceipts_since<'a>(
 	}
 
 	#[inline]
-	pub fn private_read_set(&self, room_id: &RoomId, user_id: &UserId, pdu_count: u64) {
+	pub fn private_read_set(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+		pdu_count: u64,
+		receipt: &Receipt,
+	) {
 		let key = (room_id, user_id);
 		let next_count = self.services.globals.next_count();
 
 		self.roomuserid_privateread.put(key, pdu_count);
+		self.roomuserid_privatereadreceipt
+			.put(key, Json(receipt));
 		self.roomuserid_lastprivatereadupdate
 			.put(key, *next_count);
 	}
@@ -107,6 +143,19 @@ pub async fn private_read_get_count(
 			.deserialized()
 	}
 
+	#[inline]
+	pub async fn private_read_get_receipt(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+	) -> Result<Receipt> {
+		let key = (room_id, user_id);
+		self.roomuserid_privatereadreceipt
+			.qry(&key)
+			.await
+			.deserialized()
+	}
+
 	#[inline]
 	pub async fn last_privateread_update(
 		&self,
@@ -134,6 +183,15 @@ pub async fn delete_all_read_receipts(&self, room_id: &RoomId) -> Result {
 			})
 			.await;
 
+		self.roomuserid_privatereadreceipt
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| {
+				trace!("Removing key: {key:?}");
+				self.roomuserid_privatereadreceipt.remove(key);
+			})
+			.await;
+
 		self.roomuserid_lastprivatereadupdate
 			.keys_prefix_raw(&prefix)
 			.ignore_err()
@@ -152,6 +210,36 @@ pub async fn delete_all_read_receipts(&self, room_id: &RoomId) -> Result {
 			})
 			.await;
 
+		self.roomuserthread_readreceiptcount
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| {
+				trace!("Removing key: {key:?}");
+				self.roomuserthread_readreceiptcount.remove(key);
+			})
+			.await;
+
 		Ok(())
 	}
 }
+
+/// Thread of the (single) receipt carried by one of our receipt events.
+fn receipt_thread(event: &ReceiptEvent) -> Option<&ReceiptThread> {
+	event
+		.content
+		.0
+		.values()
+		.flat_map(|receipts| receipts.values())
+		.flat_map(|users| users.values())
+		.map(|receipt| &receipt.thread)
+		.next()
+}
+
+/// Key part of a receipt thread in the index of the users' receipts.
+fn thread_key(thread: Option<&ReceiptThread>) -> &str {
+	match thread {
+		| Some(ReceiptThread::Main) => "main",
+		| Some(ReceiptThread::Thread(event_id)) => event_id.as_str(),
+		| _ => "",
+	}
+}
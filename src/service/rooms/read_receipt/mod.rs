@@ -7,7 +7,9 @@
 	OwnedEventId, OwnedUserId, RoomId, UserId,
 	events::{
 		AnySyncEphemeralRoomEvent, SyncEphemeralRoomEvent,
-		receipt::{ReceiptEvent, ReceiptEventContent, Receipts},
+		receipt::{
+			Receipt, ReceiptEvent, ReceiptEventContent, ReceiptThread, ReceiptType, Receipts,
+		},
 	},
 	serde::Raw,
 };
@@ -17,6 +19,7 @@
 		Event,
 		pdu::{PduCount, PduId, RawPduId},
 	},
+	utils::ReadyExt,
 	warn,
 };
 
@@ -39,7 +42,7 @@ fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 impl Service {
-	/// Replaces the previous read receipt.
+	/// Replaces the previous read receipt of the user in the same thread.
 	pub async fn readreceipt_update(
 		&self,
 		user_id: &UserId,
@@ -88,16 +91,22 @@ pub async fn private_read_get(
 			.get_pdu_from_id(&pdu_id)
 			.await?;
 
+		let receipt = self
+			.db
+			.private_read_get_receipt(room_id, user_id)
+			.await
+			.unwrap_or(Receipt {
+				ts: None, // set before timestamps were stored
+				thread: ReceiptThread::Unthreaded,
+			});
+
 		let event_id: OwnedEventId = pdu.event_id().to_owned();
 		let user_id: OwnedUserId = user_id.to_owned();
 		let content: BTreeMap<OwnedEventId, Receipts> = BTreeMap::from_iter([(
 			event_id,
 			BTreeMap::from_iter([(
-				ruma::events::receipt::ReceiptType::ReadPrivate,
-				BTreeMap::from_iter([(user_id, ruma::events::receipt::Receipt {
-					ts: None, // TODO: start storing the timestamp so we can return one
-					thread: ruma::events::receipt::ReceiptThread::Unthreaded,
-				})]),
+				ReceiptType::ReadPrivate,
+				BTreeMap::from_iter([(user_id, receipt)]),
 			)]),
 		)]);
 		let receipt_event_content = ReceiptEventContent(content);
@@ -121,10 +130,49 @@ pub fn readreceipts_since<'a>(
 		self.db.readreceipts_since(room_id, since, to)
 	}
 
+	/// Returns the public read receipts in the room grouped by the event they
+	/// point at, i.e. which users have read up to each event. A user appears
+	/// once per thread they hold a receipt in.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub async fn read_by(
+		&self,
+		room_id: &RoomId,
+	) -> BTreeMap<OwnedEventId, BTreeMap<OwnedUserId, Receipt>> {
+		self.readreceipts_since(room_id, 0, None)
+			.ready_fold(BTreeMap::new(), |mut read_by, (_, _, event)| {
+				let Ok(event) = serde_json::from_str::<SyncEphemeralRoomEvent<ReceiptEventContent>>(
+					event.json().get(),
+				) else {
+					return read_by;
+				};
+
+				for (event_id, mut receipts) in event.content {
+					let Some(users) = receipts.remove(&ReceiptType::Read) else {
+						continue;
+					};
+
+					read_by
+						.entry(event_id)
+						.or_insert_with(BTreeMap::new)
+						.extend(users);
+				}
+
+				read_by
+			})
+			.await
+	}
+
 	/// Sets a private read marker at PDU `count`.
 	#[tracing::instrument(skip(self), level = "debug")]
-	pub fn private_read_set(&self, room_id: &RoomId, user_id: &UserId, count: u64) {
-		self.db.private_read_set(room_id, user_id, count);
+	pub fn private_read_set(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+		count: u64,
+		receipt: &Receipt,
+	) {
+		self.db
+			.private_read_set(room_id, user_id, count, receipt);
 	}
 
 	/// Returns the private read marker PDU count.
@@ -163,8 +211,14 @@ pub fn pack_receipts<I>(receipts: I) -> Raw<SyncEphemeralRoomEvent<ReceiptEventC
 		);
 		match receipt {
 			| Ok(value) =>
-				for (event, receipt) in value.content {
-					json.insert(event, receipt);
+				for (event, receipts) in value.content {
+					let merged: &mut Receipts = json.entry(event).or_default();
+					for (receipt_type, users) in receipts {
+						merged
+							.entry(receipt_type)
+							.or_default()
+							.extend(users);
+					}
 				},
 			| _ => {
 				debug!("failed to parse receipt: {:?}", receipt);
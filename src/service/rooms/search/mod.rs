@@ -17,7 +17,7 @@
 
 use crate::rooms::{
 	short::ShortRoomId,
-	timeline::{PduId, RawPduId},
+	timeline::{PduId, PdusIterItem, RawPduId},
 };
 
 pub struct Service {
@@ -36,6 +36,10 @@ pub struct RoomQuery<'a> {
 	pub criteria: &'a Criteria,
 	pub limit: usize,
 	pub skip: usize,
+
+	/// Only events up to this point are searched, e.g. when the user left the
+	/// room.
+	pub until: Option<PduCount>,
 }
 
 type TokenId = ArrayVec<u8, TOKEN_ID_MAX_LEN>;
@@ -91,8 +95,17 @@ pub fn deindex_pdu(&self, shortroomid: ShortRoomId, pdu_id: &RawPduId, message_b
 pub async fn search_pdus<'a>(
 	&'a self,
 	query: &'a RoomQuery<'a>,
-) -> Result<(usize, impl Stream<Item = impl Event + use<>> + Send + '_)> {
-	let pdu_ids: Vec<_> = self.search_pdu_ids(query).await?.collect().await;
+) -> Result<(usize, impl Stream<Item = PdusIterItem> + Send + '_)> {
+	let pdu_ids: Vec<_> = self
+		.search_pdu_ids(query)
+		.await?
+		.ready_filter(|pdu_id| {
+			query
+				.until
+				.is_none_or(|until| pdu_id.pdu_count() <= until)
+		})
+		.collect()
+		.await;
 
 	let filter = &query.criteria.filter;
 	let count = pdu_ids.len();
@@ -104,16 +117,17 @@ pub async fn search_pdus<'a>(
 				.timeline
 				.get_pdu_from_id(&result_pdu_id)
 				.await
+				.map(|pdu| (result_pdu_id.pdu_count(), pdu))
 				.ok()
 		})
-		.ready_filter(|pdu| !pdu.is_redacted())
-		.ready_filter(move |pdu| filter.matches(pdu))
-		.wide_filter_map(async |pdu| {
+		.ready_filter(|(_, pdu)| !pdu.is_redacted())
+		.ready_filter(move |(_, pdu)| filter.matches(pdu))
+		.wide_filter_map(async |(count, pdu)| {
 			self.services
 				.state_accessor
 				.user_can_see_event(query.user_id?, pdu.room_id(), pdu.event_id())
 				.await
-				.then_some(pdu)
+				.then_some((count, pdu))
 		})
 		.skip(query.skip)
 		.take(query.limit);
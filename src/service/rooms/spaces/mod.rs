@@ -1,3 +1,4 @@
+mod moderation;
 mod pagination_token;
 #[cfg(test)]
 mod tests;
@@ -31,7 +32,7 @@
 	},
 };
 
-pub use self::pagination_token::PaginationToken;
+pub use self::{moderation::SpaceAction, pagination_token::PaginationToken};
 
 pub struct Service {
 	services: Arc<crate::services::OnceServices>,
@@ -0,0 +1,384 @@
+use std::collections::{HashSet, VecDeque};
+
+use futures::{FutureExt, StreamExt};
+use ruma::{
+	Int, OwnedRoomId, OwnedServerName, OwnedUserId, RoomId, UserId,
+	events::{
+		StateEventContent, StateEventType,
+		room::{
+			join_rules::JoinRule,
+			power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent, UserPowerLevel},
+			server_acl::RoomServerAclEventContent,
+		},
+		space::child::SpaceChildEventContent,
+	},
+};
+use serde::{Deserialize, Serialize};
+use tuwunel_core::{
+	Err, Event, Result, err, implement,
+	matrix::pdu::PduBuilder,
+	utils::{IterStream, stream::ReadyExt},
+};
+
+use super::Service;
+use crate::rooms::state::RoomMutexGuard;
+
+/// Upper bound on the rooms of a space subtree an action is applied to.
+const MAX_SPACE_ROOMS: usize = 1024;
+
+/// Action applied to every room of a space subtree.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SpaceAction {
+	Ban {
+		user_id: OwnedUserId,
+		reason: Option<String>,
+	},
+	Kick {
+		user_id: OwnedUserId,
+		reason: Option<String>,
+	},
+	Unban {
+		user_id: OwnedUserId,
+		reason: Option<String>,
+	},
+	SetPowerLevel {
+		user_id: OwnedUserId,
+		level: Int,
+	},
+	/// Adds the server to the deny list of `m.room.server_acl`.
+	DenyServer {
+		server_name: OwnedServerName,
+	},
+	/// Removes the server from the deny list of `m.room.server_acl`.
+	UndenyServer {
+		server_name: OwnedServerName,
+	},
+	/// Joins the local user to every joinable room not joined yet.
+	ForceJoin {
+		user_id: OwnedUserId,
+	},
+}
+
+/// Rooms of the space subtree known to this server, starting with the space
+/// itself, along with the servers its parent suggests joining them through.
+///
+/// Children are followed through the local `m.space.child` state of the rooms
+/// this server is in, up to `MAX_SPACE_ROOMS` rooms.
+#[implement(Service)]
+pub async fn space_rooms(&self, space_id: &RoomId) -> Vec<(OwnedRoomId, Vec<OwnedServerName>)> {
+	let mut rooms = Vec::new();
+	let mut seen: HashSet<OwnedRoomId> = HashSet::from([space_id.to_owned()]);
+	let mut queue: VecDeque<_> = VecDeque::from([(space_id.to_owned(), Vec::new())]);
+
+	while let Some((room_id, via)) = queue.pop_front() {
+		let children: Vec<_> = self
+			.get_space_child_events(&room_id)
+			.collect()
+			.await;
+
+		rooms.push((room_id, via));
+		if rooms.len() >= MAX_SPACE_ROOMS {
+			break;
+		}
+
+		for pdu in children {
+			let Some(Ok(child_id)) = pdu.state_key().map(RoomId::parse) else {
+				continue;
+			};
+
+			if seen.insert(child_id.clone()) {
+				let via = pdu
+					.get_content::<SpaceChildEventContent>()
+					.map(|content| content.via)
+					.unwrap_or_default();
+
+				queue.push_back((child_id, via));
+			}
+		}
+	}
+
+	rooms
+}
+
+/// Applies the action to every room of the space subtree, returning the
+/// outcome for each room.
+///
+/// - With a sender the action is taken by that user alone, which must be joined
+///   and have enough power in each room
+/// - Otherwise it is taken by the server user or the most powerful local member
+///   with enough power in each room
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn apply_to_space(
+	&self,
+	space_id: &RoomId,
+	action: &SpaceAction,
+	sender: Option<&UserId>,
+) -> Result<Vec<(OwnedRoomId, Result)>> {
+	match action {
+		| SpaceAction::DenyServer { server_name }
+			if self.services.globals.server_is_ours(server_name) =>
+			return Err!(Request(InvalidParam("Refusing to deny this server."))),
+		| SpaceAction::ForceJoin { user_id } if !self.services.globals.user_is_local(user_id) =>
+			return Err!(Request(InvalidParam("{user_id} is not a local user."))),
+		| _ => {},
+	}
+
+	let results = self
+		.space_rooms(space_id)
+		.await
+		.into_iter()
+		.stream()
+		.then(async |(room_id, via)| {
+			let result = self
+				.apply_to_room(&room_id, &via, action, sender)
+				.boxed()
+				.await;
+
+			(room_id, result)
+		})
+		.collect()
+		.await;
+
+	Ok(results)
+}
+
+#[implement(Service)]
+async fn apply_to_room(
+	&self,
+	room_id: &RoomId,
+	via: &[OwnedServerName],
+	action: &SpaceAction,
+	sender: Option<&UserId>,
+) -> Result {
+	if let SpaceAction::ForceJoin { user_id } = action {
+		return self.force_join(room_id, via, user_id).await;
+	}
+
+	if !self
+		.services
+		.state_cache
+		.server_in_room(self.services.globals.server_name(), room_id)
+		.await
+	{
+		return Err!(Request(NotFound("This server is not in the room.")));
+	}
+
+	let power_levels = self
+		.services
+		.state_accessor
+		.get_power_levels(room_id)
+		.await?;
+
+	let sender = self
+		.acting_user(room_id, &power_levels, action, sender)
+		.await?;
+
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+	let membership = &self.services.membership;
+	match action {
+		| SpaceAction::Ban { user_id, reason } =>
+			membership
+				.ban(room_id, user_id, reason.as_ref(), &sender, &state_lock)
+				.await,
+		| SpaceAction::Kick { user_id, reason } =>
+			membership
+				.kick(room_id, user_id, reason.as_ref(), &sender, &state_lock)
+				.await,
+		| SpaceAction::Unban { user_id, reason } =>
+			membership
+				.unban(room_id, user_id, reason.as_ref(), &sender, &state_lock)
+				.await,
+		| SpaceAction::SetPowerLevel { user_id, level } => {
+			let mut content: RoomPowerLevelsEventContent = power_levels.try_into()?;
+			content.users.insert(user_id.clone(), *level);
+
+			self.send_state(room_id, &sender, &content, &state_lock)
+				.await
+		},
+		| SpaceAction::DenyServer { server_name } =>
+			self.update_server_acl(room_id, &sender, &state_lock, |deny| {
+				let server_name = server_name.as_str();
+				let denied = deny.iter().any(|denied| denied == server_name);
+				if !denied {
+					deny.push(server_name.to_owned());
+				}
+
+				!denied
+			})
+			.await,
+		| SpaceAction::UndenyServer { server_name } =>
+			self.update_server_acl(room_id, &sender, &state_lock, |deny| {
+				let len = deny.len();
+				deny.retain(|denied| denied != server_name.as_str());
+
+				deny.len() != len
+			})
+			.await,
+		| SpaceAction::ForceJoin { .. } => unreachable!("joins are handled above"),
+	}
+}
+
+/// Joins the local user to the room unless it is already joined or known not
+/// to be joinable without an invite.
+#[implement(Service)]
+async fn force_join(
+	&self,
+	room_id: &RoomId,
+	via: &[OwnedServerName],
+	user_id: &UserId,
+) -> Result {
+	if self
+		.services
+		.state_cache
+		.is_joined(user_id, room_id)
+		.await
+	{
+		return Ok(());
+	}
+
+	let known = self
+		.services
+		.state_cache
+		.server_in_room(self.services.globals.server_name(), room_id)
+		.await;
+
+	if known
+		&& !matches!(
+			self.services
+				.state_accessor
+				.get_join_rules(room_id)
+				.await,
+			JoinRule::Public | JoinRule::Restricted(_) | JoinRule::KnockRestricted(_)
+		) {
+		return Err!(Request(Forbidden("The room is not joinable without an invite.")));
+	}
+
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+
+	self.services
+		.membership
+		.join(user_id, room_id, None, via, &None, &state_lock)
+		.boxed()
+		.await
+}
+
+/// The user the action is taken by in the room.
+#[implement(Service)]
+async fn acting_user(
+	&self,
+	room_id: &RoomId,
+	power_levels: &RoomPowerLevels,
+	action: &SpaceAction,
+	sender: Option<&UserId>,
+) -> Result<OwnedUserId> {
+	if let Some(sender) = sender {
+		if !self
+			.services
+			.state_cache
+			.is_joined(sender, room_id)
+			.await
+		{
+			return Err!(Request(Forbidden("{sender} is not joined to the room.")));
+		}
+
+		if !may_act(power_levels, sender, action) {
+			return Err!(Request(Forbidden("{sender} does not have enough power in the room.")));
+		}
+
+		return Ok(sender.to_owned());
+	}
+
+	let users: Vec<OwnedUserId> = self
+		.services
+		.state_cache
+		.local_users_in_room(room_id)
+		.ready_filter(|user_id| may_act(power_levels, user_id, action))
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	let server_user = &self.services.globals.server_user;
+	if users.contains(server_user) {
+		return Ok(server_user.clone());
+	}
+
+	users
+		.into_iter()
+		.max_by_key(|user_id| power_levels.for_user(user_id))
+		.ok_or_else(|| err!(Request(Forbidden("No local user has enough power in the room."))))
+}
+
+/// Applies the update to the deny list of the room's `m.room.server_acl`,
+/// sending a new event when it returns true. Rooms without an ACL get one
+/// allowing all other servers.
+#[implement(Service)]
+async fn update_server_acl<F>(
+	&self,
+	room_id: &RoomId,
+	sender: &UserId,
+	state_lock: &RoomMutexGuard,
+	update: F,
+) -> Result
+where
+	F: FnOnce(&mut Vec<String>) -> bool + Send,
+{
+	let mut content: RoomServerAclEventContent = self
+		.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomServerAcl, "")
+		.await
+		.unwrap_or_else(|_| {
+			RoomServerAclEventContent::new(true, vec!["*".to_owned()], Vec::new())
+		});
+
+	if !update(&mut content.deny) {
+		return Ok(());
+	}
+
+	self.send_state(room_id, sender, &content, state_lock)
+		.await
+}
+
+#[implement(Service)]
+async fn send_state<T>(
+	&self,
+	room_id: &RoomId,
+	sender: &UserId,
+	content: &T,
+	state_lock: &RoomMutexGuard,
+) -> Result
+where
+	T: StateEventContent + Send + Sync,
+{
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(String::new(), content),
+			sender,
+			room_id,
+			state_lock,
+		)
+		.boxed()
+		.await?;
+
+	Ok(())
+}
+
+fn may_act(power_levels: &RoomPowerLevels, user_id: &UserId, action: &SpaceAction) -> bool {
+	match action {
+		| SpaceAction::Ban { user_id: target, .. } =>
+			power_levels.user_can_ban_user(user_id, target),
+		| SpaceAction::Kick { user_id: target, .. } =>
+			power_levels.user_can_kick_user(user_id, target),
+		| SpaceAction::Unban { user_id: target, .. } =>
+			power_levels.user_can_unban_user(user_id, target),
+		| SpaceAction::SetPowerLevel { user_id: target, level } =>
+			power_levels.user_can_change_user_power_level(user_id, target)
+				&& power_levels.for_user(user_id) >= UserPowerLevel::Int(*level),
+		| SpaceAction::DenyServer { .. } | SpaceAction::UndenyServer { .. } =>
+			power_levels.user_can_send_state(user_id, StateEventType::RoomServerAcl),
+		| SpaceAction::ForceJoin { .. } => false,
+	}
+}
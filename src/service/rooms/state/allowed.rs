@@ -0,0 +1,208 @@
+use futures::{FutureExt, TryFutureExt};
+use ruma::{
+	RoomId, UserId,
+	events::{
+		AnyStateEventContent, StateEventType,
+		room::{
+			canonical_alias::RoomCanonicalAliasEventContent,
+			history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
+			join_rules::{JoinRule, RoomJoinRulesEventContent},
+			member::{MembershipState, RoomMemberEventContent},
+			server_acl::RoomServerAclEventContent,
+		},
+	},
+	serde::Raw,
+};
+use tuwunel_core::{Err, Result, debug_warn, err, implement, is_false, utils::BoolExt};
+
+/// Rejects state events clients may not send though the auth rules allow
+/// them, such as making the admin room public or ACLs locking this server out.
+#[implement(super::Service)]
+pub async fn allowed_to_send_state_event(
+	&self,
+	room_id: &RoomId,
+	event_type: &StateEventType,
+	state_key: &str,
+	json: &Raw<AnyStateEventContent>,
+) -> Result {
+	match event_type {
+		| StateEventType::RoomCreate => {
+			return Err!(Request(BadJson(debug_warn!(
+				?room_id,
+				"You cannot update m.room.create after a room has been created."
+			))));
+		},
+		| StateEventType::RoomServerAcl => {
+			// prevents common ACL paw-guns as ACL management is difficult and prone to
+			// irreversible mistakes
+			match json.deserialize_as_unchecked::<RoomServerAclEventContent>() {
+				| Ok(acl_content) => {
+					if acl_content.allow_is_empty() {
+						return Err!(Request(BadJson(debug_warn!(
+							?room_id,
+							"Sending an ACL event with an empty allow key will permanently \
+							 brick the room for non-tuwunel's as this equates to no servers \
+							 being allowed to participate in this room."
+						))));
+					}
+
+					if acl_content.deny_contains("*") && acl_content.allow_contains("*") {
+						return Err!(Request(BadJson(debug_warn!(
+							?room_id,
+							"Sending an ACL event with a deny and allow key value of \"*\" will \
+							 permanently brick the room for non-tuwunel's as this equates to no \
+							 servers being allowed to participate in this room."
+						))));
+					}
+
+					if acl_content.deny_contains("*")
+						&& !acl_content.is_allowed(self.services.globals.server_name())
+						&& !acl_content
+							.allow_contains(self.services.globals.server_name().as_str())
+					{
+						return Err!(Request(BadJson(debug_warn!(
+							?room_id,
+							"Sending an ACL event with a deny key value of \"*\" and without \
+							 your own server name in the allow key will result in you being \
+							 unable to participate in this room."
+						))));
+					}
+
+					if !acl_content.allow_contains("*")
+						&& !acl_content.is_allowed(self.services.globals.server_name())
+						&& !acl_content
+							.allow_contains(self.services.globals.server_name().as_str())
+					{
+						return Err!(Request(BadJson(debug_warn!(
+							?room_id,
+							"Sending an ACL event for an allow key without \"*\" and without \
+							 your own server name in the allow key will result in you being \
+							 unable to participate in this room."
+						))));
+					}
+				},
+				| Err(e) => {
+					return Err!(Request(BadJson(debug_warn!(
+						"Room server ACL event is invalid: {e}"
+					))));
+				},
+			}
+		},
+		| StateEventType::RoomEncryption =>
+		// Forbid m.room.encryption if encryption is disabled
+			if !self.services.config.allow_encryption {
+				return Err!(Request(Forbidden("Encryption is disabled on this homeserver.")));
+			},
+		| StateEventType::RoomJoinRules => {
+			// admin room is a sensitive room, it should not ever be made public
+			if let Ok(admin_room_id) = self.services.admin.get_admin_room().await {
+				if admin_room_id == room_id {
+					match json.deserialize_as_unchecked::<RoomJoinRulesEventContent>() {
+						| Ok(join_rule) =>
+							if join_rule.join_rule == JoinRule::Public {
+								return Err!(Request(Forbidden(
+									"Admin room is a sensitive room, it cannot be made public"
+								)));
+							},
+						| Err(e) => {
+							return Err!(Request(BadJson(debug_warn!(
+								"Room join rules event is invalid: {e}"
+							))));
+						},
+					}
+				}
+			}
+		},
+		| StateEventType::RoomHistoryVisibility => {
+			// admin room is a sensitive room, it should not ever be made world readable
+			if let Ok(admin_room_id) = self.services.admin.get_admin_room().await {
+				match json.deserialize_as_unchecked::<RoomHistoryVisibilityEventContent>() {
+					| Ok(visibility_content) => {
+						if admin_room_id == room_id
+							&& visibility_content.history_visibility
+								== HistoryVisibility::WorldReadable
+						{
+							return Err!(Request(Forbidden(
+								"Admin room is a sensitive room, it cannot be made world \
+								 readable (public room history)."
+							)));
+						}
+					},
+					| Err(e) => {
+						return Err!(Request(BadJson(debug_warn!(
+							"Room history visibility event is invalid: {e}"
+						))));
+					},
+				}
+			}
+		},
+		| StateEventType::RoomCanonicalAlias => {
+			match json.deserialize_as_unchecked::<RoomCanonicalAliasEventContent>() {
+				| Ok(canonical_alias_content) => {
+					services
+						.alias
+						.check_canonical_aliases(room_id, &canonical_alias_content)
+						.await?;
+				},
+				| Err(e) => {
+					return Err!(Request(InvalidParam(debug_warn!(
+						"Room canonical alias event is invalid: {e}"
+					))));
+				},
+			}
+		},
+		| StateEventType::RoomMember =>
+			match json.deserialize_as_unchecked::<RoomMemberEventContent>() {
+				| Ok(membership_content) => {
+					let Ok(_state_key) = UserId::parse(state_key) else {
+						return Err!(Request(BadJson(
+							"Membership event has invalid or non-existent state key"
+						)));
+					};
+
+					if let Some(authorising_user) =
+						membership_content.join_authorized_via_users_server
+					{
+						if membership_content.membership != MembershipState::Join {
+							return Err!(Request(BadJson(
+								"join_authorised_via_users_server is only for member joins"
+							)));
+						}
+
+						if !self
+							.services
+							.globals
+							.user_is_local(&authorising_user)
+						{
+							return Err!(Request(InvalidParam(
+								"Authorising user {authorising_user} does not belong to this \
+								 homeserver"
+							)));
+						}
+
+						services
+							.state_cache
+							.is_joined(&authorising_user, room_id)
+							.map(is_false!())
+							.map(BoolExt::into_result)
+							.map_err(|()| {
+								err!(Request(InvalidParam(
+									"Authorising user {authorising_user} is not in the room. \
+									 They cannot authorise the join."
+								)))
+							})
+							.await?;
+					}
+				},
+				| Err(e) => {
+					return Err!(Request(BadJson(
+						"Membership content must have a valid JSON body with at least a valid \
+						 membership state: {e}"
+					)));
+				},
+			},
+		| _ => (),
+	}
+
+	Ok(())
+}
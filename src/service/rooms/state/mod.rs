@@ -1,3 +1,5 @@
+mod allowed;
+
 use std::{collections::HashMap, fmt::Write, iter::once, sync::Arc};
 
 use async_trait::async_trait;
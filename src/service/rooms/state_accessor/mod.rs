@@ -3,12 +3,17 @@
 mod state;
 mod user_can;
 
-use std::sync::Arc;
+use std::{
+	fmt::Write,
+	sync::{Arc, Mutex},
+};
 
 use async_trait::async_trait;
 use futures::{FutureExt, TryFutureExt, future::try_join};
+use lru_cache::LruCache;
 use ruma::{
-	EventEncryptionAlgorithm, JsOption, OwnedRoomAliasId, RoomId, UserId,
+	EventEncryptionAlgorithm, JsOption, OwnedRoomAliasId, OwnedRoomId, OwnedUserId, RoomId,
+	UserId,
 	events::{
 		StateEventType,
 		room::{
@@ -28,31 +33,62 @@
 	room::RoomType,
 };
 use tuwunel_core::{
-	Result, err,
+	PduCount, Result, err,
 	matrix::{Event, room_version, state_res::events::RoomCreateEvent},
+	utils::math::usize_from_f64,
 };
 use tuwunel_database::Map;
 
+use crate::rooms::short::ShortStateHash;
+
 pub struct Service {
 	services: Arc<crate::services::OnceServices>,
 	db: Data,
+	userroomid_leftcount_cache: Mutex<LeftCountCache>,
 }
 
 struct Data {
 	shorteventid_shortstatehash: Arc<Map>,
 }
 
+/// Leave positions of former members along with the room state they were
+/// computed at; entries of an outdated state are recomputed.
+type LeftCountCache = LruCache<(OwnedUserId, OwnedRoomId), (ShortStateHash, Option<PduCount>)>;
+
 #[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		let config = &args.server.config;
+		let cache_size = f64::from(config.userroomid_leftcount_cache_capacity);
+		let cache_size = cache_size * config.cache_capacity_modifier;
 		Ok(Arc::new(Self {
 			services: args.services.clone(),
 			db: Data {
 				shorteventid_shortstatehash: args.db["shorteventid_shortstatehash"].clone(),
 			},
+			userroomid_leftcount_cache: Mutex::new(LruCache::new(usize_from_f64(cache_size)?)),
 		}))
 	}
 
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let userroomid_leftcount_cache = self
+			.userroomid_leftcount_cache
+			.lock()
+			.expect("locked")
+			.len();
+
+		writeln!(out, "userroomid_leftcount_cache: {userroomid_leftcount_cache}")?;
+
+		Ok(())
+	}
+
+	async fn clear_cache(&self) {
+		self.userroomid_leftcount_cache
+			.lock()
+			.expect("locked")
+			.clear();
+	}
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
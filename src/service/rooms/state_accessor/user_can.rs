@@ -1,5 +1,6 @@
+use futures::future::join;
 use ruma::{
-	EventId, RoomId, UserId,
+	EventId, OwnedEventId, RoomId, UserId,
 	events::{
 		StateEventType, TimelineEventType,
 		room::{
@@ -8,7 +9,11 @@
 		},
 	},
 };
-use tuwunel_core::{Err, Result, implement, matrix::Event, pdu::PduBuilder};
+use tuwunel_core::{
+	Err, Result, implement,
+	matrix::Event,
+	pdu::{PduBuilder, PduCount},
+};
 
 use crate::rooms::state::RoomMutexGuard;
 
@@ -112,8 +117,102 @@ pub async fn user_can_see_event(
 				.await
 		},
 		| HistoryVisibility::WorldReadable => true,
-		| HistoryVisibility::Shared | _ => currently_member,
+		| HistoryVisibility::Shared | _ =>
+			currently_member
+				|| self
+					.user_left_after_event(user_id, room_id, event_id)
+					.await,
+	}
+}
+
+/// Whether a former member was still joined when the event was sent; shared
+/// history remains readable to them up to their leave.
+#[implement(super::Service)]
+async fn user_left_after_event(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+	event_id: &EventId,
+) -> bool {
+	let (left_count, event_count) = join(
+		self.user_left_count(user_id, room_id),
+		self.services.timeline.get_pdu_count(event_id),
+	)
+	.await;
+
+	left_count
+		.zip(event_count.ok())
+		.is_some_and(|(left_count, event_count)| event_count <= left_count)
+}
+
+/// Position of the leave of a former member who was joined until then; `None`
+/// for current members and users who never joined the room. Cached until the
+/// room's state changes.
+#[implement(super::Service)]
+pub async fn user_left_count(&self, user_id: &UserId, room_id: &RoomId) -> Option<PduCount> {
+	let Ok(shortstatehash) = self
+		.services
+		.state
+		.get_room_shortstatehash(room_id)
+		.await
+	else {
+		return self.find_user_left_count(user_id, room_id).await;
+	};
+
+	let key = (user_id.to_owned(), room_id.to_owned());
+	if let Some(&mut (cached_shortstatehash, left_count)) = self
+		.userroomid_leftcount_cache
+		.lock()
+		.expect("locked")
+		.get_mut(&key)
+	{
+		if cached_shortstatehash == shortstatehash {
+			return left_count;
+		}
 	}
+
+	let left_count = self.find_user_left_count(user_id, room_id).await;
+	self.userroomid_leftcount_cache
+		.lock()
+		.expect("locked")
+		.insert(key, (shortstatehash, left_count));
+
+	left_count
+}
+
+#[implement(super::Service)]
+async fn find_user_left_count(&self, user_id: &UserId, room_id: &RoomId) -> Option<PduCount> {
+	if self
+		.services
+		.state_cache
+		.is_joined(user_id, room_id)
+		.await
+	{
+		return None;
+	}
+
+	let leave_event_id: OwnedEventId = self
+		.room_state_get_id(room_id, &StateEventType::RoomMember, user_id.as_str())
+		.await
+		.ok()?;
+
+	let leave_shortstatehash = self
+		.pdu_shortstatehash(&leave_event_id)
+		.await
+		.ok()?;
+
+	if !self
+		.user_was_joined(leave_shortstatehash, user_id)
+		.await
+	{
+		return None;
+	}
+
+	self.services
+		.timeline
+		.get_pdu_count(&leave_event_id)
+		.await
+		.ok()
 }
 
 /// Whether a user is allowed to see an event, based on
@@ -147,6 +147,11 @@ pub async fn update_membership(
 		| MembershipState::Leave | MembershipState::Ban => {
 			self.mark_as_left(user_id, room_id);
 
+			self.services
+				.appservice
+				.notify_member_left(user_id, room_id)
+				.await;
+
 			if self.services.globals.user_is_local(user_id)
 				&& (self.services.config.forget_forced_upon_leave
 					|| self.services.metadata.is_banned(room_id).await
@@ -5,10 +5,12 @@
 
 use futures::StreamExt;
 use ruma::{
-	CanonicalJsonObject, CanonicalJsonValue, EventId, OwnedUserId, RoomId, RoomVersionId, UserId,
+	CanonicalJsonObject, CanonicalJsonValue, EventId, MilliSecondsSinceUnixEpoch, OwnedUserId,
+	RoomId, RoomVersionId, UserId,
 	events::{
 		GlobalAccountDataEventType, TimelineEventType,
 		push_rules::PushRulesEvent,
+		receipt::{Receipt, ReceiptThread},
 		room::{
 			encrypted::Relation,
 			member::{MembershipState, RoomMemberEventContent},
@@ -76,6 +78,14 @@ pub async fn append_incoming_pdu<'a, Leafs>(
 		.append_pdu(pdu, pdu_json, new_room_leafs, state_lock)
 		.await?;
 
+	// after append_pdu so the member counts include this event
+	if pdu.state_key().is_some() {
+		self.services
+			.directory
+			.update_room(pdu.room_id())
+			.await;
+	}
+
 	Ok(Some(pdu_id))
 }
 
@@ -169,13 +179,20 @@ pub async fn append_pdu<'a, Leafs>(
 
 	// Mark as read first so the sending client doesn't get a notification even if
 	// appending fails
-	self.services
-		.read_receipt
-		.private_read_set(pdu.room_id(), pdu.sender(), *next_count2);
+	self.services.read_receipt.private_read_set(
+		pdu.room_id(),
+		pdu.sender(),
+		*next_count2,
+		&Receipt {
+			ts: Some(MilliSecondsSinceUnixEpoch::now()),
+			thread: ReceiptThread::Unthreaded,
+		},
+	);
 
 	self.services
 		.user
-		.reset_notification_counts(pdu.sender(), pdu.room_id());
+		.reset_notification_counts(pdu.sender(), pdu.room_id())
+		.await;
 
 	let count = PduCount::Normal(*next_count1);
 	let pdu_id: RawPduId = PduId { shortroomid, shorteventid: count }.into();
@@ -282,7 +299,15 @@ pub async fn append_pdu<'a, Leafs>(
 			.await;
 	}
 
-	self.increment_notification_counts(pdu.room_id(), notifies, highlights);
+	let thread_id = pdu
+		.get_content::<ExtractRelatesTo>()
+		.ok()
+		.and_then(|content| match content.relates_to {
+			| Relation::Thread(thread) => Some(thread.event_id),
+			| _ => None,
+		});
+
+	self.increment_notification_counts(pdu.room_id(), thread_id.as_deref(), notifies, highlights);
 
 	match *pdu.kind() {
 		| TimelineEventType::RoomRedaction => {
@@ -382,7 +407,7 @@ pub async fn append_pdu<'a, Leafs>(
 				{
 					self.services
 						.admin
-						.command(body, Some((pdu.event_id()).into()))
+						.command(body, Some((pdu.event_id()).into()), pdu.sender())
 						.await?;
 				}
 			}
@@ -390,35 +415,14 @@ pub async fn append_pdu<'a, Leafs>(
 		| _ => {},
 	}
 
-	if let Ok(content) = pdu.get_content::<ExtractRelatesToEventId>() {
-		if let Ok(related_pducount) = self
-			.get_pdu_count(&content.relates_to.event_id)
-			.await
-		{
-			self.services
-				.pdu_metadata
-				.add_relation(count, related_pducount);
-		}
-	}
+	self.index_relations(pdu, count).await;
 
 	if let Ok(content) = pdu.get_content::<ExtractRelatesTo>() {
-		match content.relates_to {
-			| Relation::Reply { in_reply_to } => {
-				// We need to do it again here, because replies don't have
-				// event_id as a top level field
-				if let Ok(related_pducount) = self.get_pdu_count(&in_reply_to.event_id).await {
-					self.services
-						.pdu_metadata
-						.add_relation(count, related_pducount);
-				}
-			},
-			| Relation::Thread(thread) => {
-				self.services
-					.threads
-					.add_to_thread(&thread.event_id, pdu)
-					.await?;
-			},
-			| _ => {}, // TODO: Aggregate other types
+		if let Relation::Thread(thread) = content.relates_to {
+			self.services
+				.threads
+				.add_to_thread(&thread.event_id, pdu)
+				.await?;
 		}
 	}
 
@@ -511,6 +515,7 @@ fn append_pdu_json(
 fn increment_notification_counts(
 	&self,
 	room_id: &RoomId,
+	thread_id: Option<&EventId>,
 	notifies: Vec<OwnedUserId>,
 	highlights: Vec<OwnedUserId>,
 ) {
@@ -521,6 +526,12 @@ fn increment_notification_counts(
 		userroom_id.push(0xFF);
 		userroom_id.extend_from_slice(room_id.as_bytes());
 		increment(&self.db.userroomid_notificationcount, &userroom_id);
+
+		if let Some(thread_id) = thread_id {
+			userroom_id.push(0xFF);
+			userroom_id.extend_from_slice(thread_id.as_bytes());
+			increment(&self.db.userroomthreadid_notificationcount, &userroom_id);
+		}
 	}
 
 	for user in highlights {
@@ -528,6 +539,12 @@ fn increment_notification_counts(
 		userroom_id.push(0xFF);
 		userroom_id.extend_from_slice(room_id.as_bytes());
 		increment(&self.db.userroomid_highlightcount, &userroom_id);
+
+		if let Some(thread_id) = thread_id {
+			userroom_id.push(0xFF);
+			userroom_id.extend_from_slice(thread_id.as_bytes());
+			increment(&self.db.userroomthreadid_highlightcount, &userroom_id);
+		}
 	}
 }
 
@@ -537,3 +554,42 @@ fn increment(db: &Arc<Map>, key: &[u8]) {
 	let new = utils::increment(old.ok().as_deref());
 	db.insert(key, new);
 }
+
+/// Indexes the relations of the pdu to the events it relates to, including
+/// replies, so they can be found and aggregated from the related event.
+/// Relations to events we don't have yet are kept until the event arrives.
+#[implement(super::Service)]
+pub(super) async fn index_relations(&self, pdu: &PduEvent, count: PduCount) {
+	let pdu_metadata = &self.services.pdu_metadata;
+	let related_event_id = pdu
+		.get_content::<ExtractRelatesToEventId>()
+		.map(|content| content.relates_to.event_id);
+
+	// Replies don't have event_id as a top level field
+	let in_reply_to = pdu
+		.get_content::<ExtractRelatesTo>()
+		.ok()
+		.and_then(|content| match content.relates_to {
+			| Relation::Reply { in_reply_to } => Some(in_reply_to.event_id),
+			| _ => None,
+		});
+
+	for related_event_id in related_event_id
+		.ok()
+		.into_iter()
+		.chain(in_reply_to)
+	{
+		match self.get_pdu_count(&related_event_id).await {
+			| Ok(related_pducount) => pdu_metadata.add_relation(count, related_pducount),
+			| Err(_) =>
+				pdu_metadata.add_pending_relation(pdu.room_id(), &related_event_id, count),
+		}
+	}
+
+	for pending in pdu_metadata
+		.take_pending_relations(pdu.room_id(), pdu.event_id())
+		.await
+	{
+		pdu_metadata.add_relation(pending, count);
+	}
+}
@@ -206,6 +206,9 @@ pub async fn backfill_pdu(
 	self.prepend_backfill_pdu(&pdu_id, &event_id, &value);
 	drop(insert_lock);
 
+	self.index_relations(&pdu, pdu_id.pdu_count())
+		.await;
+
 	if pdu.kind == TimelineEventType::RoomMessage {
 		let content: ExtractBody = pdu.get_content()?;
 		if let Some(body) = content.body {
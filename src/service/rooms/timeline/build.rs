@@ -140,6 +140,13 @@ pub async fn build_and_append_pdu(
 		.state
 		.set_room_state(pdu.room_id(), statehashid, state_lock);
 
+	if pdu.state_key().is_some() {
+		self.services
+			.directory
+			.update_room(pdu.room_id())
+			.await;
+	}
+
 	let mut servers: HashSet<OwnedServerName> = self
 		.services
 		.state_cache
@@ -48,6 +48,8 @@ struct Data {
 	pduid_pdu: Arc<Map>,
 	userroomid_highlightcount: Arc<Map>,
 	userroomid_notificationcount: Arc<Map>,
+	userroomthreadid_highlightcount: Arc<Map>,
+	userroomthreadid_notificationcount: Arc<Map>,
 	db: Arc<Database>,
 }
 
@@ -88,6 +90,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				pduid_pdu: args.db["pduid_pdu"].clone(),
 				userroomid_highlightcount: args.db["userroomid_highlightcount"].clone(),
 				userroomid_notificationcount: args.db["userroomid_notificationcount"].clone(),
+				userroomthreadid_highlightcount: args.db["userroomthreadid_highlightcount"]
+					.clone(),
+				userroomthreadid_notificationcount: args.db["userroomthreadid_notificationcount"]
+					.clone(),
 				db: args.db.clone(),
 			},
 			mutex_insert: RoomMutexMap::new(),
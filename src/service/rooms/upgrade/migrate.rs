@@ -0,0 +1,527 @@
+use futures::{FutureExt, StreamExt};
+use ruma::{
+	OwnedRoomAliasId, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId, UserId,
+	events::{
+		GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType,
+		push_rules::PushRulesEvent, room::pinned_events::RoomPinnedEventsEventContent,
+	},
+	push::{NewPushRule, NewSimplePushRule, RuleKind},
+};
+use serde_json::value::to_raw_value;
+use tuwunel_core::{
+	Err, Result, implement,
+	matrix::{Event, StateKey, pdu::PduBuilder},
+	utils::stream::ReadyExt,
+	warn,
+};
+
+use super::Service;
+
+/// State carried over to the replacement room in addition to the spec's
+/// recommended transferable state. Pinned events keep referring to events of
+/// the old room, which clients reach through the predecessor.
+const MIGRATED_STATE_EVENTS: &[StateEventType; 4] = &[
+	StateEventType::RoomCanonicalAlias,
+	StateEventType::RoomPinnedEvents,
+	StateEventType::SpaceChild,
+	StateEventType::SpaceParent,
+];
+
+/// Everything an upgrade moves from a room to its replacement. Produced
+/// without side-effects by [`Service::plan`] and filled in by
+/// [`Service::migrate`].
+#[derive(Debug, Default)]
+pub struct Report {
+	pub room_id: OwnedRoomId,
+	pub from_version: Option<RoomVersionId>,
+	pub to_version: Option<RoomVersionId>,
+	pub replacement_room: Option<OwnedRoomId>,
+
+	/// Local user sending the tombstone and creating the replacement.
+	pub sender: Option<OwnedUserId>,
+
+	pub aliases: Vec<OwnedRoomAliasId>,
+	pub canonical_alias: bool,
+	pub published: bool,
+
+	/// Events pinned in the old room, pinned in the replacement as well.
+	pub pinned_events: usize,
+
+	/// Spaces listing this room as a child, re-pointed at the replacement.
+	pub space_parents: Vec<OwnedRoomId>,
+
+	/// Children of this room when it is itself a space.
+	pub space_children: Vec<OwnedRoomId>,
+
+	/// Local members joined to the replacement room.
+	pub members: Vec<OwnedUserId>,
+
+	/// Local members whose room tags are copied.
+	pub tags: Vec<OwnedUserId>,
+
+	/// Local members with a room-specific push rule that is copied.
+	pub push_rules: Vec<OwnedUserId>,
+
+	/// Non-fatal failures after the replacement room was created.
+	pub errors: Vec<String>,
+}
+
+/// Computes what upgrading `room_id` to `new_version` would migrate without
+/// changing anything.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn plan(&self, room_id: &RoomId, new_version: &RoomVersionId) -> Result<Report> {
+	if !self.services.metadata.exists(room_id).await {
+		return Err!(Request(NotFound("Room {room_id} is not known to this server.")));
+	}
+
+	if self.is_tombstoned(room_id).await {
+		return Err!(Request(InvalidParam("Room {room_id} has already been upgraded.")));
+	}
+
+	if !self
+		.services
+		.server
+		.supported_room_version(new_version)
+	{
+		return Err!(Request(UnsupportedRoomVersion(
+			"This server does not support room version {new_version}."
+		)));
+	}
+
+	let from_version = self
+		.services
+		.state
+		.get_room_version(room_id)
+		.await?;
+
+	if from_version == *new_version {
+		return Err!(Request(InvalidParam("Room {room_id} is already version {new_version}.")));
+	}
+
+	let sender = self
+		.local_sender(room_id, StateEventType::RoomTombstone)
+		.await?;
+
+	let aliases = self
+		.services
+		.alias
+		.local_aliases_for_room(room_id)
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	let canonical_alias = self
+		.services
+		.state_accessor
+		.room_state_get(room_id, &StateEventType::RoomCanonicalAlias, "")
+		.await
+		.is_ok();
+
+	let pinned_events = self
+		.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomPinnedEvents, "")
+		.await
+		.map_or(0, |content: RoomPinnedEventsEventContent| content.pinned.len());
+
+	let members: Vec<OwnedUserId> = self
+		.services
+		.state_cache
+		.local_users_in_room(room_id)
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	let mut tags = Vec::new();
+	let mut push_rules = Vec::new();
+	for user_id in &members {
+		if self
+			.services
+			.account_data
+			.get_raw(Some(room_id), user_id, &RoomAccountDataEventType::Tag.to_string())
+			.await
+			.is_ok()
+		{
+			tags.push(user_id.clone());
+		}
+
+		if self
+			.room_push_rule(user_id, room_id)
+			.await
+			.is_some()
+		{
+			push_rules.push(user_id.clone());
+		}
+	}
+
+	Ok(Report {
+		room_id: room_id.to_owned(),
+		from_version: Some(from_version),
+		to_version: Some(new_version.clone()),
+		sender: Some(sender.clone()),
+		aliases,
+		canonical_alias,
+		published: self
+			.services
+			.directory
+			.is_public_room(room_id)
+			.await,
+		pinned_events,
+		space_parents: self
+			.state_room_ids(room_id, &StateEventType::SpaceParent)
+			.await,
+		space_children: self
+			.state_room_ids(room_id, &StateEventType::SpaceChild)
+			.await,
+		members: members
+			.into_iter()
+			.filter(|user_id| *user_id != sender)
+			.collect(),
+		tags,
+		push_rules,
+		..Default::default()
+	})
+}
+
+/// Upgrades `room_id` to `new_version` on behalf of its most privileged local
+/// member, then migrates the local state listed in the [`Report`].
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "info")]
+pub async fn migrate(&self, room_id: &RoomId, new_version: &RoomVersionId) -> Result<Report> {
+	let mut report = self.plan(room_id, new_version).await?;
+	let sender = report
+		.sender
+		.clone()
+		.expect("plan always selects a sender");
+
+	let replacement_room = self
+		.upgrade(room_id, new_version, &sender)
+		.boxed()
+		.await?;
+
+	report.replacement_room = Some(replacement_room.clone());
+	let new_room = &replacement_room;
+
+	for event_type in MIGRATED_STATE_EVENTS {
+		if let Err(e) = self
+			.transfer_state(room_id, new_room, event_type, &sender)
+			.await
+		{
+			report
+				.errors
+				.push(format!("Failed to transfer {event_type}: {e}"));
+		}
+	}
+
+	// The canonical alias now points at aliases owned by the replacement room.
+	if report.canonical_alias {
+		let state_lock = self.services.state.mutex.lock(room_id).await;
+		if let Err(e) = self
+			.services
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder {
+					event_type: StateEventType::RoomCanonicalAlias
+						.to_string()
+						.into(),
+					content: to_raw_value(&serde_json::json!({}))?,
+					state_key: Some(StateKey::new()),
+					..Default::default()
+				},
+				&sender,
+				room_id,
+				&state_lock,
+			)
+			.await
+		{
+			report
+				.errors
+				.push(format!("Failed to clear old canonical alias: {e}"));
+		}
+	}
+
+	if report.published {
+		self.services.directory.set_public(new_room).await;
+		self.services.directory.set_not_public(room_id);
+	}
+
+	for parent in &report.space_parents {
+		if let Err(e) = self
+			.relink_space_child(parent, room_id, new_room)
+			.await
+		{
+			report
+				.errors
+				.push(format!("Failed to update space {parent}: {e}"));
+		}
+	}
+
+	for user_id in &report.members {
+		if let Err(e) = self
+			.join_replacement(&sender, user_id, new_room)
+			.await
+		{
+			report
+				.errors
+				.push(format!("Failed to join {user_id}: {e}"));
+		}
+	}
+
+	for user_id in &report.tags {
+		if let Err(e) = self.copy_tags(user_id, room_id, new_room).await {
+			report
+				.errors
+				.push(format!("Failed to copy tags of {user_id}: {e}"));
+		}
+	}
+
+	for user_id in &report.push_rules {
+		if let Err(e) = self
+			.copy_push_rule(user_id, room_id, new_room)
+			.await
+		{
+			report
+				.errors
+				.push(format!("Failed to copy push rule of {user_id}: {e}"));
+		}
+	}
+
+	for error in &report.errors {
+		warn!(%room_id, %new_room, "{error}");
+	}
+
+	Ok(report)
+}
+
+/// Copies every state event of `event_type` from `room_id` into
+/// `new_room`, preserving state keys.
+#[implement(Service)]
+async fn transfer_state(
+	&self,
+	room_id: &RoomId,
+	new_room: &RoomId,
+	event_type: &StateEventType,
+	sender_user: &UserId,
+) -> Result {
+	let shortstatehash = self
+		.services
+		.state
+		.get_room_shortstatehash(room_id)
+		.await?;
+
+	let state_keys: Vec<StateKey> = self
+		.services
+		.state_accessor
+		.state_keys(shortstatehash, event_type)
+		.collect()
+		.await;
+
+	let state_lock = self.services.state.mutex.lock(new_room).await;
+	for state_key in state_keys {
+		let Ok(event) = self
+			.services
+			.state_accessor
+			.state_get(shortstatehash, event_type, &state_key)
+			.await
+		else {
+			continue;
+		};
+
+		self.services
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder {
+					event_type: event_type.to_string().into(),
+					content: event.content().to_owned(),
+					state_key: Some(state_key),
+					..Default::default()
+				},
+				sender_user,
+				new_room,
+				&state_lock,
+			)
+			.await?;
+	}
+
+	Ok(())
+}
+
+/// Points a parent space's `m.space.child` link at the replacement room and
+/// removes the link to the old room.
+#[implement(Service)]
+async fn relink_space_child(
+	&self,
+	parent: &RoomId,
+	room_id: &RoomId,
+	new_room: &RoomId,
+) -> Result {
+	let Ok(child) = self
+		.services
+		.state_accessor
+		.room_state_get(parent, &StateEventType::SpaceChild, room_id.as_str())
+		.await
+	else {
+		return Ok(());
+	};
+
+	let content = child.content().to_owned();
+	let sender_user = self
+		.local_sender(parent, StateEventType::SpaceChild)
+		.await?;
+
+	let state_lock = self.services.state.mutex.lock(parent).await;
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				event_type: StateEventType::SpaceChild.to_string().into(),
+				content,
+				state_key: Some(new_room.as_str().into()),
+				..Default::default()
+			},
+			&sender_user,
+			parent,
+			&state_lock,
+		)
+		.await?;
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				event_type: StateEventType::SpaceChild.to_string().into(),
+				content: to_raw_value(&serde_json::json!({}))?,
+				state_key: Some(room_id.as_str().into()),
+				..Default::default()
+			},
+			&sender_user,
+			parent,
+			&state_lock,
+		)
+		.await?;
+
+	Ok(())
+}
+
+/// Invites and joins a local member of the old room to the replacement.
+#[implement(Service)]
+async fn join_replacement(
+	&self,
+	sender_user: &UserId,
+	user_id: &UserId,
+	new_room: &RoomId,
+) -> Result {
+	if !self
+		.services
+		.state_cache
+		.is_invited(user_id, new_room)
+		.await
+	{
+		self.services
+			.membership
+			.invite(sender_user, user_id, new_room, None, false)
+			.boxed()
+			.await?;
+	}
+
+	let state_lock = self.services.state.mutex.lock(new_room).await;
+	self.services
+		.membership
+		.join(user_id, new_room, None, &[], &None, &state_lock)
+		.boxed()
+		.await
+}
+
+#[implement(Service)]
+async fn copy_tags(&self, user_id: &UserId, room_id: &RoomId, new_room: &RoomId) -> Result {
+	let tags: serde_json::Value = self
+		.services
+		.account_data
+		.get_room(room_id, user_id, RoomAccountDataEventType::Tag)
+		.await?;
+
+	self.services
+		.account_data
+		.update(Some(new_room), user_id, RoomAccountDataEventType::Tag, &tags)
+		.await
+}
+
+#[implement(Service)]
+async fn copy_push_rule(&self, user_id: &UserId, room_id: &RoomId, new_room: &RoomId) -> Result {
+	let Some((mut event, actions, enabled)) = self.room_push_rule(user_id, room_id).await else {
+		return Ok(());
+	};
+
+	let ruleset = &mut event.content.global;
+	let rule = NewSimplePushRule::new(new_room.to_owned(), actions);
+	if let Err(e) = ruleset.insert(NewPushRule::Room(rule), None, None) {
+		return Err!(Database("Could not insert push rule: {e}"));
+	}
+
+	if let Err(e) = ruleset.set_enabled(RuleKind::Room, new_room.as_str(), enabled) {
+		return Err!(Database("Could not enable push rule: {e}"));
+	}
+
+	self.services
+		.account_data
+		.update(
+			None,
+			user_id,
+			GlobalAccountDataEventType::PushRules
+				.to_string()
+				.into(),
+			&serde_json::to_value(event)?,
+		)
+		.await
+}
+
+/// Returns the user's push rules along with the actions and enabled state of
+/// their room-specific rule for `room_id`, if any.
+#[implement(Service)]
+async fn room_push_rule(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+) -> Option<(PushRulesEvent, Vec<ruma::push::Action>, bool)> {
+	let event: PushRulesEvent = self
+		.services
+		.account_data
+		.get_global(user_id, GlobalAccountDataEventType::PushRules)
+		.await
+		.ok()?;
+
+	let (actions, enabled) = event
+		.content
+		.global
+		.room
+		.iter()
+		.find(|rule| rule.rule_id == room_id)
+		.map(|rule| (rule.actions.clone(), rule.enabled))?;
+
+	Some((event, actions, enabled))
+}
+
+/// Room IDs used as state keys for `event_type` in the current state.
+#[implement(Service)]
+async fn state_room_ids(
+	&self,
+	room_id: &RoomId,
+	event_type: &StateEventType,
+) -> Vec<OwnedRoomId> {
+	let Ok(shortstatehash) = self
+		.services
+		.state
+		.get_room_shortstatehash(room_id)
+		.await
+	else {
+		return Vec::new();
+	};
+
+	self.services
+		.state_accessor
+		.state_keys(shortstatehash, event_type)
+		.ready_filter_map(|state_key| RoomId::parse(&state_key).ok())
+		.collect()
+		.await
+}
@@ -0,0 +1,382 @@
+mod migrate;
+
+use std::{cmp::max, sync::Arc};
+
+use futures::{StreamExt, TryFutureExt};
+use ruma::{
+	CanonicalJsonObject, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId, UserId,
+	events::{
+		StateEventType, TimelineEventType,
+		room::{
+			create::PreviousRoom,
+			member::{MembershipState, RoomMemberEventContent},
+			power_levels::RoomPowerLevelsEventContent,
+			tombstone::RoomTombstoneEventContent,
+		},
+	},
+	int,
+	room_version_rules::RoomIdFormatVersion,
+};
+use serde_json::{json, value::to_raw_value};
+use tuwunel_core::{
+	Err, Result, err, implement,
+	matrix::{Event, StateKey, pdu::PduBuilder, room_version},
+	utils::stream::ReadyExt,
+};
+
+pub use self::migrate::Report;
+
+pub struct Service {
+	services: Arc<crate::services::OnceServices>,
+}
+
+/// Recommended transferable state events list from the spec
+const TRANSFERABLE_STATE_EVENTS: &[StateEventType; 9] = &[
+	StateEventType::RoomAvatar,
+	StateEventType::RoomEncryption,
+	StateEventType::RoomGuestAccess,
+	StateEventType::RoomHistoryVisibility,
+	StateEventType::RoomJoinRules,
+	StateEventType::RoomName,
+	StateEventType::RoomPowerLevels,
+	StateEventType::RoomServerAcl,
+	StateEventType::RoomTopic,
+];
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self { services: args.services.clone() }))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Upgrades the room.
+///
+/// - Creates a replacement room
+/// - Sends a tombstone event into the current room
+/// - Sender user joins the room
+/// - Transfers some state events
+/// - Moves local aliases
+/// - Modifies old room power levels to prevent users from speaking
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn upgrade(
+	&self,
+	room_id: &RoomId,
+	new_version: &RoomVersionId,
+	sender_user: &UserId,
+) -> Result<OwnedRoomId> {
+	debug_assert!(
+		TRANSFERABLE_STATE_EVENTS.is_sorted(),
+		"TRANSFERABLE_STATE_EVENTS is not sorted"
+	);
+
+	if !self
+		.services
+		.server
+		.supported_room_version(new_version)
+	{
+		return Err!(Request(UnsupportedRoomVersion(
+			"This server does not support that room version.",
+		)));
+	}
+
+	if matches!(new_version, RoomVersionId::V12) {
+		return Err!(Request(UnsupportedRoomVersion(
+			"Upgrading to version 12 is still under development.",
+		)));
+	}
+
+	let room_version_rules = room_version::rules(new_version)?;
+	let room_id_format = &room_version_rules.room_id_format;
+	assert!(*room_id_format == RoomIdFormatVersion::V1, "TODO");
+
+	// Create a replacement room
+	let replacement_room = RoomId::new_v1(self.services.globals.server_name());
+
+	let _short_id = self
+		.services
+		.short
+		.get_or_create_shortroomid(&replacement_room)
+		.await;
+
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+
+	// Send a m.room.tombstone event to the old room to indicate that it is not
+	// intended to be used any further Fail if the sender does not have the required
+	// permissions
+	let tombstone_event_id = self
+		.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(StateKey::new(), &RoomTombstoneEventContent {
+				body: "This room has been replaced".to_owned(),
+				replacement_room: replacement_room.clone(),
+			}),
+			sender_user,
+			room_id,
+			&state_lock,
+		)
+		.await?;
+
+	// Change lock to replacement room
+	drop(state_lock);
+	let state_lock = self
+		.services
+		.state
+		.mutex
+		.lock(&replacement_room)
+		.await;
+
+	// Get the old room creation event
+	let mut create_event_content: CanonicalJsonObject = self
+		.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomCreate, "")
+		.await
+		.map_err(|_| err!(Database("Found room without m.room.create event.")))?;
+
+	// Use the m.room.tombstone event as the predecessor
+	let predecessor = Some(PreviousRoom::new(room_id.to_owned(), Some(tombstone_event_id)));
+
+	// Send a m.room.create event containing a predecessor field and the applicable
+	// room_version
+	{
+		use RoomVersionId::*;
+		match new_version {
+			| V1 | V2 | V3 | V4 | V5 | V6 | V7 | V8 | V9 | V10 => {
+				create_event_content.insert(
+					"creator".into(),
+					json!(&sender_user).try_into().map_err(|e| {
+						err!(Request(BadJson(error!("Error forming creation event: {e}"))))
+					})?,
+				);
+			},
+			| _ => {
+				// "creator" key no longer exists in V11+ rooms
+				create_event_content.remove("creator");
+			},
+		}
+	}
+
+	create_event_content.insert(
+		"room_version".into(),
+		json!(new_version)
+			.try_into()
+			.map_err(|_| err!(Request(BadJson("Error forming creation event"))))?,
+	);
+	create_event_content.insert(
+		"predecessor".into(),
+		json!(predecessor)
+			.try_into()
+			.map_err(|_| err!(Request(BadJson("Error forming creation event"))))?,
+	);
+
+	// Validate creation event content
+	if serde_json::from_str::<CanonicalJsonObject>(to_raw_value(&create_event_content)?.get())
+		.is_err()
+	{
+		return Err!(Request(BadJson("Error forming creation event")));
+	}
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				event_type: TimelineEventType::RoomCreate,
+				content: to_raw_value(&create_event_content)?,
+				unsigned: None,
+				state_key: Some(StateKey::new()),
+				redacts: None,
+				timestamp: None,
+			},
+			sender_user,
+			&replacement_room,
+			&state_lock,
+		)
+		.await?;
+
+	// Join the new room
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				event_type: TimelineEventType::RoomMember,
+				content: to_raw_value(&RoomMemberEventContent {
+					membership: MembershipState::Join,
+					displayname: self
+						.services
+						.users
+						.displayname(sender_user)
+						.await
+						.ok(),
+					avatar_url: self
+						.services
+						.users
+						.avatar_url(sender_user)
+						.await
+						.ok(),
+					is_direct: None,
+					third_party_invite: None,
+					blurhash: self
+						.services
+						.users
+						.blurhash(sender_user)
+						.await
+						.ok(),
+					reason: None,
+					join_authorized_via_users_server: None,
+				})?,
+				unsigned: None,
+				state_key: Some(sender_user.as_str().into()),
+				redacts: None,
+				timestamp: None,
+			},
+			sender_user,
+			&replacement_room,
+			&state_lock,
+		)
+		.await?;
+
+	// Replicate transferable state events to the new room
+	for event_type in TRANSFERABLE_STATE_EVENTS {
+		let event_content = match self
+			.services
+			.state_accessor
+			.room_state_get(room_id, event_type, "")
+			.await
+		{
+			| Ok(v) => v.content().to_owned(),
+			| Err(_) => continue, // Skipping missing events.
+		};
+
+		self.services
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder {
+					event_type: event_type.to_string().into(),
+					content: event_content,
+					state_key: Some(StateKey::new()),
+					..Default::default()
+				},
+				sender_user,
+				&replacement_room,
+				&state_lock,
+			)
+			.await?;
+	}
+
+	// Moves any local aliases to the new room
+	let mut local_aliases = self
+		.services
+		.alias
+		.local_aliases_for_room(room_id)
+		.boxed();
+
+	while let Some(alias) = local_aliases.next().await {
+		self.services
+			.alias
+			.remove_alias(alias, sender_user)
+			.await?;
+
+		self.services
+			.alias
+			.set_alias(alias, &replacement_room, sender_user)?;
+	}
+
+	// Change lock back to the old room
+	drop(state_lock);
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+
+	// Get the old room power levels
+	let power_levels_event_content: RoomPowerLevelsEventContent = self
+		.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomPowerLevels, "")
+		.await
+		.map_err(|_| err!(Database("Found room without m.room.power_levels event.")))?;
+
+	// Setting events_default and invite to the greater of 50 and users_default + 1
+	let new_level = max(
+		int!(50),
+		power_levels_event_content
+			.users_default
+			.checked_add(int!(1))
+			.ok_or_else(|| {
+				err!(Request(BadJson("users_default power levels event content is not valid")))
+			})?,
+	);
+
+	// Modify the power levels in the old room to prevent sending of events and
+	// inviting new users
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(StateKey::new(), &RoomPowerLevelsEventContent {
+				events_default: new_level,
+				invite: new_level,
+				..power_levels_event_content
+			}),
+			sender_user,
+			room_id,
+			&state_lock,
+		)
+		.await?;
+
+	Ok(replacement_room)
+}
+
+/// Selects a local user joined to the room who is allowed to send the given
+/// state event, preferring the server user, then the most powerful member.
+#[implement(Service)]
+pub async fn local_sender(
+	&self,
+	room_id: &RoomId,
+	event_type: StateEventType,
+) -> Result<OwnedUserId> {
+	let power_levels = self
+		.services
+		.state_accessor
+		.get_power_levels(room_id)
+		.await?;
+
+	let server_user = &self.services.globals.server_user;
+	if self
+		.services
+		.state_cache
+		.is_joined(server_user, room_id)
+		.await && power_levels.user_can_send_state(server_user, event_type.clone())
+	{
+		return Ok(server_user.clone());
+	}
+
+	let users: Vec<OwnedUserId> = self
+		.services
+		.state_cache
+		.local_users_in_room(room_id)
+		.ready_filter(|user_id| power_levels.user_can_send_state(user_id, event_type.clone()))
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	users
+		.into_iter()
+		.max_by_key(|user_id| power_levels.for_user(user_id))
+		.ok_or_else(|| {
+			err!(Request(Forbidden(
+				"No local user in {room_id} is allowed to send {event_type}."
+			)))
+		})
+}
+
+/// Whether the room already has a tombstone pointing elsewhere.
+#[implement(Service)]
+pub async fn is_tombstoned(&self, room_id: &RoomId) -> bool {
+	self.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomTombstone, "")
+		.map_ok(|content: RoomTombstoneEventContent| content.replacement_room)
+		.await
+		.is_ok()
+}
@@ -1,8 +1,12 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
-use ruma::{RoomId, UserId};
-use tuwunel_core::{Result, implement};
-use tuwunel_database::{Database, Deserialized, Map};
+use futures::StreamExt;
+use ruma::{EventId, OwnedEventId, RoomId, UserId, events::receipt::ReceiptThread};
+use tuwunel_core::{
+	Result, implement,
+	utils::{ReadyExt, stream::TryIgnore},
+};
+use tuwunel_database::{Database, Deserialized, Interfix, Map};
 
 use crate::rooms::short::ShortStateHash;
 
@@ -15,6 +19,8 @@ struct Data {
 	db: Arc<Database>,
 	userroomid_notificationcount: Arc<Map>,
 	userroomid_highlightcount: Arc<Map>,
+	userroomthreadid_notificationcount: Arc<Map>,
+	userroomthreadid_highlightcount: Arc<Map>,
 	roomuserid_lastnotificationread: Arc<Map>,
 	roomsynctoken_shortstatehash: Arc<Map>,
 }
@@ -26,6 +32,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				db: args.db.clone(),
 				userroomid_notificationcount: args.db["userroomid_notificationcount"].clone(),
 				userroomid_highlightcount: args.db["userroomid_highlightcount"].clone(),
+				userroomthreadid_notificationcount: args.db["userroomthreadid_notificationcount"]
+					.clone(),
+				userroomthreadid_highlightcount: args.db["userroomthreadid_highlightcount"]
+					.clone(),
 				roomuserid_lastnotificationread: args.db["userroomid_highlightcount"].clone(),
 				roomsynctoken_shortstatehash: args.db["roomsynctoken_shortstatehash"].clone(),
 			},
@@ -36,17 +46,99 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
+/// Unread counts of a single thread in a room.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadCounts {
+	pub notification_count: u64,
+	pub highlight_count: u64,
+}
+
 #[implement(Service)]
-pub fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
+pub async fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
+	self.set_notification_counts(user_id, room_id, ThreadCounts::default());
+
+	let prefix = (user_id, room_id, Interfix);
+	for map in [
+		&self.db.userroomthreadid_notificationcount,
+		&self.db.userroomthreadid_highlightcount,
+	] {
+		map.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| map.remove(key))
+			.await;
+	}
+}
+
+/// Resets the unread counts covered by a receipt in the given thread.
+///
+/// - Unthreaded receipts cover the whole room
+/// - Main timeline receipts leave only the threads unread
+/// - Thread receipts clear that thread alone
+#[implement(Service)]
+pub async fn reset_thread_notification_counts(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+	thread: &ReceiptThread,
+) {
+	match thread {
+		| ReceiptThread::Main => {
+			let remaining = self
+				.thread_notification_counts(user_id, room_id)
+				.await
+				.into_values()
+				.fold(ThreadCounts::default(), |total, counts| ThreadCounts {
+					notification_count: total
+						.notification_count
+						.saturating_add(counts.notification_count),
+					highlight_count: total
+						.highlight_count
+						.saturating_add(counts.highlight_count),
+				});
+
+			self.set_notification_counts(user_id, room_id, remaining);
+		},
+		| ReceiptThread::Thread(thread_id) => {
+			let read = self
+				.thread_counts(user_id, room_id, thread_id)
+				.await;
+
+			let key = (user_id, room_id, thread_id);
+			self.db
+				.userroomthreadid_notificationcount
+				.del(key);
+			self.db.userroomthreadid_highlightcount.del(key);
+
+			let remaining = ThreadCounts {
+				notification_count: self
+					.notification_count(user_id, room_id)
+					.await
+					.saturating_sub(read.notification_count),
+				highlight_count: self
+					.highlight_count(user_id, room_id)
+					.await
+					.saturating_sub(read.highlight_count),
+			};
+
+			self.set_notification_counts(user_id, room_id, remaining);
+		},
+		| _ =>
+			self.reset_notification_counts(user_id, room_id)
+				.await,
+	}
+}
+
+#[implement(Service)]
+fn set_notification_counts(&self, user_id: &UserId, room_id: &RoomId, counts: ThreadCounts) {
 	let count = self.services.globals.next_count();
 
 	let userroom_id = (user_id, room_id);
 	self.db
 		.userroomid_highlightcount
-		.put(userroom_id, 0_u64);
+		.put(userroom_id, counts.highlight_count);
 	self.db
 		.userroomid_notificationcount
-		.put(userroom_id, 0_u64);
+		.put(userroom_id, counts.notification_count);
 
 	let roomuser_id = (room_id, user_id);
 	self.db
@@ -54,6 +146,72 @@ pub fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
 		.put(roomuser_id, *count);
 }
 
+/// Unread counts of each thread in the room with unread events; these are
+/// also included in the room totals.
+#[implement(Service)]
+pub async fn thread_notification_counts(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+) -> BTreeMap<OwnedEventId, ThreadCounts> {
+	type KeyVal<'a> = ((&'a UserId, &'a RoomId, &'a EventId), u64);
+
+	let mut threads = BTreeMap::<OwnedEventId, ThreadCounts>::new();
+	let prefix = (user_id, room_id, Interfix);
+	self.db
+		.userroomthreadid_notificationcount
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.ready_for_each(|((_, _, thread_id), count): KeyVal<'_>| {
+			threads
+				.entry(thread_id.to_owned())
+				.or_default()
+				.notification_count = count;
+		})
+		.await;
+
+	self.db
+		.userroomthreadid_highlightcount
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.ready_for_each(|((_, _, thread_id), count): KeyVal<'_>| {
+			threads
+				.entry(thread_id.to_owned())
+				.or_default()
+				.highlight_count = count;
+		})
+		.await;
+
+	threads
+}
+
+#[implement(Service)]
+async fn thread_counts(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+	thread_id: &EventId,
+) -> ThreadCounts {
+	let key = (user_id, room_id, thread_id);
+	let notification_count = self
+		.db
+		.userroomthreadid_notificationcount
+		.qry(&key)
+		.await
+		.deserialized()
+		.unwrap_or(0);
+
+	let highlight_count = self
+		.db
+		.userroomthreadid_highlightcount
+		.qry(&key)
+		.await
+		.deserialized()
+		.unwrap_or(0);
+
+	ThreadCounts { notification_count, highlight_count }
+}
+
 #[implement(Service)]
 pub async fn notification_count(&self, user_id: &UserId, room_id: &RoomId) -> u64 {
 	let key = (user_id, room_id);
@@ -176,6 +176,23 @@ pub async fn get_latest_educount(&self, server_name: &ServerName) -> u64 {
 			.deserialized()
 			.unwrap_or(0)
 	}
+
+	/// Appservices share the map with servers; their keys start with a plus
+	/// as in the request queues.
+	pub fn set_latest_appservice_count(&self, appservice_id: &str, last_count: u64) {
+		let key = format!("+{appservice_id}");
+		self.servername_educount
+			.raw_put(key.as_str(), last_count);
+	}
+
+	pub async fn get_latest_appservice_count(&self, appservice_id: &str) -> u64 {
+		let key = format!("+{appservice_id}");
+		self.servername_educount
+			.get(key.as_str())
+			.await
+			.deserialized()
+			.unwrap_or(0)
+	}
 }
 
 fn parse_servercurrentevent(key: &[u8], value: &[u8]) -> Result<(Destination, SendingEvent)> {
@@ -152,6 +152,22 @@ pub fn send_pdu_appservice(&self, appservice_id: String, pdu_id: RawPduId) -> Re
 		})
 	}
 
+	#[tracing::instrument(skip(self, serialized), level = "debug")]
+	pub fn send_edu_appservice(&self, appservice_id: String, serialized: EduBuf) -> Result {
+		let dest = Destination::Appservice(appservice_id);
+		let event = SendingEvent::Edu(serialized);
+		let _cork = self.db.db.cork();
+		let keys = self.db.queue_requests(once((&event, &dest)));
+		self.dispatch(Msg {
+			dest,
+			event,
+			queue_id: keys
+				.into_iter()
+				.next()
+				.expect("request queue key"),
+		})
+	}
+
 	#[tracing::instrument(skip(self, room_id, pdu_id), level = "debug")]
 	pub async fn send_pdu_room(&self, room_id: &RoomId, pdu_id: &RawPduId) -> Result {
 		let servers = self
@@ -271,6 +287,15 @@ pub async fn flush_servers<'a, S>(&self, servers: S) -> Result
 			.await
 	}
 
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub fn flush_appservice(&self, appservice_id: String) -> Result {
+		self.dispatch(Msg {
+			dest: Destination::Appservice(appservice_id),
+			event: SendingEvent::Flush,
+			queue_id: Vec::<u8>::new(),
+		})
+	}
+
 	/// Sends a request to a federation server
 	#[inline]
 	pub async fn send_federation_request<T>(
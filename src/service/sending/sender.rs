@@ -11,15 +11,18 @@
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 use futures::{
 	FutureExt, StreamExt,
-	future::{BoxFuture, OptionFuture, join3},
+	future::{BoxFuture, OptionFuture, join, join3},
 	pin_mut,
 	stream::FuturesUnordered,
 };
 use ruma::{
-	MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedServerName, OwnedUserId, RoomId, ServerName,
-	UInt,
+	MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, OwnedDeviceId, OwnedRoomId, OwnedServerName,
+	OwnedUserId, RoomId, ServerName, UInt, UserId,
 	api::{
-		appservice::event::push_events::v1::EphemeralData,
+		appservice::event::push_events::{
+			self,
+			v1::{DeviceLists, EphemeralData},
+		},
 		federation::transactions::{
 			edu::{
 				DeviceListUpdateContent, Edu, PresenceContent, PresenceUpdate, ReceiptContent,
@@ -30,13 +33,14 @@
 	},
 	device_id,
 	events::{
-		AnySyncEphemeralRoomEvent, GlobalAccountDataEventType, push_rules::PushRulesEvent,
-		receipt::ReceiptType,
+		AnySyncEphemeralRoomEvent, AnyToDeviceEvent, GlobalAccountDataEventType,
+		push_rules::PushRulesEvent, receipt::ReceiptType,
 	},
 	push,
 	serde::Raw,
 	uint,
 };
+use serde_json::{Value as JsonValue, value::to_raw_value};
 use tuwunel_core::{
 	Error, Event, Result, debug, err, error,
 	result::LogErr,
@@ -52,6 +56,7 @@
 use super::{
 	Destination, EduBuf, EduVec, Msg, SendingEvent, Service, appservice, data::QueueItem,
 };
+use crate::appservice::RegistrationInfo;
 
 #[derive(Debug)]
 enum TransactionStatus {
@@ -66,6 +71,10 @@ enum TransactionStatus {
 type SendingFutures<'a> = FuturesUnordered<SendingFuture<'a>>;
 type CurTransactionStatus = HashMap<Destination, TransactionStatus>;
 
+type OneTimeKeyCounts =
+	BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, BTreeMap<OneTimeKeyAlgorithm, UInt>>>;
+type FallbackKeyTypes = BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, Vec<OneTimeKeyAlgorithm>>>;
+
 const SELECT_PRESENCE_LIMIT: usize = 256;
 const SELECT_RECEIPT_LIMIT: usize = 256;
 const SELECT_EDU_LIMIT: usize = EDU_LIMIT - 2;
@@ -74,6 +83,23 @@ enum TransactionStatus {
 pub const PDU_LIMIT: usize = 50;
 pub const EDU_LIMIT: usize = 100;
 
+/// Device data pushed to appservices (MSC3202).
+#[derive(Default)]
+struct AppserviceDevices {
+	device_lists: DeviceLists,
+	one_time_keys_count: OneTimeKeyCounts,
+	unused_fallback_key_types: FallbackKeyTypes,
+}
+
+impl AppserviceDevices {
+	fn is_empty(&self) -> bool {
+		self.device_lists.changed.is_empty()
+			&& self.device_lists.left.is_empty()
+			&& self.one_time_keys_count.is_empty()
+			&& self.unused_fallback_key_types.is_empty()
+	}
+}
+
 impl Service {
 	#[tracing::instrument(skip(self), level = "debug")]
 	pub async fn sender(self: Arc<Self>, id: usize) -> Result {
@@ -477,6 +503,10 @@ async fn select_edus_device_changes(
 	}
 
 	/// Look for read receipts in this room
+	///
+	/// A user may hold a receipt in several threads of a room while a receipt
+	/// EDU carries one receipt per user, so each further receipt of the same
+	/// user is layered into another EDU.
 	#[tracing::instrument(
 		name = "receipts",
 		level = "trace",
@@ -487,39 +517,50 @@ async fn select_edus_receipts(
 		server_name: &ServerName,
 		since: (u64, u64),
 		max_edu_count: &AtomicU64,
-	) -> Option<EduBuf> {
+	) -> EduVec {
 		let num = AtomicUsize::new(0);
-		let receipts: BTreeMap<OwnedRoomId, ReceiptMap> = self
+		let rooms: Vec<(OwnedRoomId, Vec<ReceiptMap>)> = self
 			.services
 			.state_cache
 			.server_rooms(server_name)
 			.map(ToOwned::to_owned)
 			.broad_filter_map(async |room_id| {
-				let receipt_map = self
+				let receipt_maps = self
 					.select_edus_receipts_room(&room_id, since, max_edu_count, &num)
 					.await;
 
-				receipt_map
-					.read
+				receipt_maps
 					.is_empty()
 					.eq(&false)
-					.then_some((room_id, receipt_map))
+					.then_some((room_id, receipt_maps))
 			})
 			.collect()
 			.boxed()
 			.await;
 
-		if receipts.is_empty() {
-			return None;
+		let mut layers = Vec::<BTreeMap<OwnedRoomId, ReceiptMap>>::new();
+		for (room_id, receipt_maps) in rooms {
+			for (i, receipt_map) in receipt_maps.into_iter().enumerate() {
+				if layers.len() <= i {
+					layers.push(BTreeMap::new());
+				}
+
+				layers[i].insert(room_id.clone(), receipt_map);
+			}
 		}
 
-		let receipt_content = Edu::Receipt(ReceiptContent { receipts });
+		layers
+			.into_iter()
+			.map(|receipts| {
+				let receipt_content = Edu::Receipt(ReceiptContent { receipts });
 
-		let mut buf = EduBuf::new();
-		serde_json::to_writer(&mut buf, &receipt_content)
-			.expect("Failed to serialize Receipt EDU to JSON vec");
+				let mut buf = EduBuf::new();
+				serde_json::to_writer(&mut buf, &receipt_content)
+					.expect("Failed to serialize Receipt EDU to JSON vec");
 
-		Some(buf)
+				buf
+			})
+			.collect()
 	}
 
 	/// Look for read receipts in this room
@@ -534,14 +575,14 @@ async fn select_edus_receipts_room(
 		since: (u64, u64),
 		max_edu_count: &AtomicU64,
 		num: &AtomicUsize,
-	) -> ReceiptMap {
+	) -> Vec<ReceiptMap> {
 		let receipts =
 			self.services
 				.read_receipt
 				.readreceipts_since(room_id, since.0, Some(since.1));
 
 		pin_mut!(receipts);
-		let mut read = BTreeMap::<OwnedUserId, ReceiptData>::new();
+		let mut layers = Vec::<BTreeMap<OwnedUserId, ReceiptData>>::new();
 		while let Some((user_id, count, read_receipt)) = receipts.next().await {
 			debug_assert!(count <= since.1, "exceeds upper-bound");
 
@@ -578,18 +619,26 @@ async fn select_edus_receipts_room(
 				event_ids: vec![event_id.clone()],
 			};
 
-			if read
-				.insert(user_id.to_owned(), receipt_data)
-				.is_none()
-			{
-				let num = num.fetch_add(1, Ordering::Relaxed);
-				if num >= SELECT_RECEIPT_LIMIT {
-					break;
-				}
+			let layer = layers
+				.iter()
+				.position(|read| !read.contains_key(user_id))
+				.unwrap_or_else(|| {
+					layers.push(BTreeMap::new());
+					layers.len().saturating_sub(1)
+				});
+
+			layers[layer].insert(user_id.to_owned(), receipt_data);
+
+			let num = num.fetch_add(1, Ordering::Relaxed);
+			if num >= SELECT_RECEIPT_LIMIT {
+				break;
 			}
 		}
 
-		ReceiptMap { read }
+		layers
+			.into_iter()
+			.map(|read| ReceiptMap { read })
+			.collect()
 	}
 
 	/// Look for presence
@@ -701,10 +750,10 @@ async fn send_events_dest_appservice(
 		id: String,
 		events: Vec<SendingEvent>,
 	) -> SendingResult {
-		let Some(appservice) = self
+		let Some(info) = self
 			.services
 			.appservice
-			.get_registration(&id)
+			.get_registration_info(&id)
 			.await
 		else {
 			return Err((
@@ -713,18 +762,15 @@ async fn send_events_dest_appservice(
 			));
 		};
 
+		let receive_ephemeral = info.registration.receive_ephemeral;
 		let mut pdu_jsons = Vec::with_capacity(
 			events
 				.iter()
 				.filter(|event| matches!(event, SendingEvent::Pdu(_)))
 				.count(),
 		);
-		let mut edu_jsons: Vec<Raw<EphemeralData>> = Vec::with_capacity(
-			events
-				.iter()
-				.filter(|event| matches!(event, SendingEvent::Edu(_)))
-				.count(),
-		);
+		let mut edu_jsons: Vec<Raw<EphemeralData>> = Vec::new();
+		let mut to_device_jsons: Vec<Raw<AnyToDeviceEvent>> = Vec::new();
 		for event in &events {
 			match event {
 				| SendingEvent::Pdu(pdu_id) => {
@@ -738,45 +784,187 @@ async fn send_events_dest_appservice(
 					}
 				},
 				| SendingEvent::Edu(edu) =>
-					if appservice.receive_ephemeral {
-						if let Ok(edu) =
-							serde_json::from_slice(edu).and_then(|edu| Raw::new(&edu))
-						{
-							edu_jsons.push(edu);
+					if receive_ephemeral {
+						let Ok(edu) = serde_json::from_slice::<JsonValue>(edu) else {
+							continue;
+						};
+
+						let Ok(json) = to_raw_value(&edu) else {
+							continue;
+						};
+
+						// To-device events (MSC4203) are addressed to a device
+						if edu.get("to_device_id").is_some() {
+							to_device_jsons.push(Raw::from_json(json));
+						} else {
+							edu_jsons.push(Raw::from_json(json));
 						}
 					},
 				| SendingEvent::Flush => {}, // flush only; no new content
 			}
 		}
 
-		let txn_hash = calculate_hash(events.iter().filter_map(|e| match e {
-			| SendingEvent::Edu(b) => Some(b.as_ref()),
-			| SendingEvent::Pdu(b) => Some(b.as_ref()),
-			| SendingEvent::Flush => None,
-		}));
+		// Device changes since the last successful transaction (MSC3202)
+		let since = self.db.get_latest_appservice_count(&id).await;
+		let since_upper = self.services.globals.current_count();
+		let devices = if info.msc3202 {
+			self.select_appservice_devices(&info, since, since_upper)
+				.await
+		} else {
+			AppserviceDevices::default()
+		};
+
+		if pdu_jsons.is_empty()
+			&& edu_jsons.is_empty()
+			&& to_device_jsons.is_empty()
+			&& devices.is_empty()
+		{
+			self.db
+				.set_latest_appservice_count(&id, since_upper);
+
+			self.services
+				.appservice
+				.remove_device_changes(&id, since_upper)
+				.await;
+
+			return Ok(Destination::Appservice(id));
+		}
+
+		// Flush-only transactions carry no events; the window start keeps their
+		// ids distinct while retries of the same transaction reuse theirs.
+		let since_bytes = since.to_be_bytes();
+		let txn_hash = calculate_hash(
+			events
+				.iter()
+				.filter_map(|e| match e {
+					| SendingEvent::Edu(b) => Some(b.as_ref()),
+					| SendingEvent::Pdu(b) => Some(b.as_ref()),
+					| SendingEvent::Flush => None,
+				})
+				.chain(info.msc3202.then_some(since_bytes.as_slice())),
+		);
 
 		let txn_id = &*URL_SAFE_NO_PAD.encode(txn_hash);
 
-		//debug_assert!(pdu_jsons.len() + edu_jsons.len() > 0, "sending empty
-		// transaction");
 		let client = &self.services.client.appservice;
-		match appservice::send_request(
-			client,
-			appservice,
-			ruma::api::appservice::event::push_events::v1::Request {
-				txn_id: txn_id.into(),
-				events: pdu_jsons,
-				ephemeral: edu_jsons,
-				to_device: Vec::new(), // TODO
-			},
-		)
+		match appservice::send_request(client, info.registration, push_events::v1::Request {
+			txn_id: txn_id.into(),
+			events: pdu_jsons,
+			ephemeral: edu_jsons,
+			to_device: to_device_jsons,
+			device_lists: devices.device_lists,
+			device_one_time_keys_count: devices.one_time_keys_count,
+			device_unused_fallback_key_types: devices.unused_fallback_key_types,
+		})
 		.await
 		{
-			| Ok(_) => Ok(Destination::Appservice(id)),
+			| Ok(_) => {
+				self.db
+					.set_latest_appservice_count(&id, since_upper);
+
+				self.services
+					.appservice
+					.remove_device_changes(&id, since_upper)
+					.await;
+
+				Ok(Destination::Appservice(id))
+			},
 			| Err(e) => Err((Destination::Appservice(id), e)),
 		}
 	}
 
+	/// Device list changes, one-time key counts and unused fallback key types
+	/// of the appservice's users within the window (MSC3202), from the changes
+	/// recorded for the appservice.
+	async fn select_appservice_devices(
+		&self,
+		info: &RegistrationInfo,
+		since: u64,
+		since_upper: u64,
+	) -> AppserviceDevices {
+		let users: HashSet<OwnedUserId> = self
+			.services
+			.appservice
+			.device_changes(&info.registration.id, since, since_upper)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		let mut devices = AppserviceDevices::default();
+		for user_id in users {
+			if !info.is_user_match(&user_id) {
+				// Users who no longer share a room with the appservice's users
+				let shares_room = self
+					.services
+					.state_cache
+					.rooms_joined(&user_id)
+					.any(|room_id| {
+						self.services
+							.state_cache
+							.appservice_in_room(room_id, info)
+					})
+					.await;
+
+				if shares_room {
+					devices.device_lists.changed.push(user_id);
+				} else {
+					devices.device_lists.left.push(user_id);
+				}
+
+				continue;
+			}
+
+			if self
+				.services
+				.users
+				.last_one_time_keys_update(&user_id)
+				.await > since
+			{
+				self.select_key_counts(&user_id, &mut devices)
+					.await;
+			}
+
+			devices.device_lists.changed.push(user_id);
+		}
+
+		devices
+	}
+
+	/// One-time key counts and unused fallback key types of the user's devices.
+	async fn select_key_counts(&self, user_id: &UserId, devices: &mut AppserviceDevices) {
+		let device_ids: Vec<OwnedDeviceId> = self
+			.services
+			.users
+			.all_device_ids(user_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		for device_id in device_ids {
+			let (counts, fallback_types) = join(
+				self.services
+					.users
+					.count_one_time_keys(user_id, &device_id),
+				self.services
+					.users
+					.unused_fallback_key_types(user_id, &device_id),
+			)
+			.await;
+
+			devices
+				.one_time_keys_count
+				.entry(user_id.to_owned())
+				.or_default()
+				.insert(device_id.clone(), counts);
+
+			devices
+				.unused_fallback_key_types
+				.entry(user_id.to_owned())
+				.or_default()
+				.insert(device_id, fallback_types);
+		}
+	}
+
 	#[tracing::instrument(
 		name = "push",
 		level = "info",
@@ -1,12 +1,34 @@
 use std::sync::Arc;
 
-use ruma::{api::federation::discovery::VerifyKey, serde::Base64, signatures::Ed25519KeyPair};
-use tuwunel_core::{Result, debug, debug_info, err, error, utils, utils::string_from_bytes};
-use tuwunel_database::Database;
+use ruma::{
+	MilliSecondsSinceUnixEpoch, ServerName,
+	api::federation::discovery::{OldVerifyKey, ServerSigningKeys, VerifyKey},
+	serde::{Base64, base64::Standard},
+	signatures::Ed25519KeyPair,
+};
+use tuwunel_core::{
+	Err, Result, debug, debug_info, err, error, info, utils, utils::string_from_bytes,
+};
+use tuwunel_database::{Database, Deserialized, Json};
 
 use super::VerifyKeys;
 
-pub fn init(db: &Arc<Database>) -> Result<(Box<Ed25519KeyPair>, VerifyKeys)> {
+/// Keypair staged by a rotation which replaces the active keypair at the next
+/// startup.
+pub(super) const NEXT_KEYPAIR: &[u8] = b"keypair_next";
+
+/// PKCS#8 v1 prefix of an Ed25519 private key document followed by its seed.
+const PKCS8_ED25519_PREFIX: [u8; 16] = [
+	0x30, 0x2E, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2B, 0x65, 0x70, 0x04, 0x22, 0x04,
+	0x20,
+];
+
+pub fn init(
+	db: &Arc<Database>,
+	server_name: &ServerName,
+) -> Result<(Box<Ed25519KeyPair>, VerifyKeys)> {
+	promote(db, server_name)?;
+
 	let keypair = load(db).inspect_err(|_e| {
 		error!("Keypair invalid. Deleting...");
 		remove(db);
@@ -22,15 +44,98 @@ pub fn init(db: &Arc<Database>) -> Result<(Box<Ed25519KeyPair>, VerifyKeys)> {
 	Ok((keypair, verify_keys))
 }
 
+/// Generates a new keypair in the stored form.
+pub(super) fn generate() -> Result<(String, Vec<u8>)> {
+	let keypair = Ed25519KeyPair::generate()
+		.map_err(|e| err!("Failed to generate new ed25519 keypair: {e:?}"))?;
+
+	let id = utils::rand::string(8);
+	debug_info!("Generated new Ed25519 keypair: {id:?}");
+
+	Ok((id, keypair.to_vec()))
+}
+
+/// Parses a signing key in the format of a Synapse signing key file:
+/// `ed25519 <version> <unpadded base64 seed>`.
+pub(super) fn parse_signing_key(key: &str) -> Result<(String, Vec<u8>)> {
+	let mut parts = key.split_whitespace();
+	let (Some("ed25519"), Some(version), Some(seed), None) =
+		(parts.next(), parts.next(), parts.next(), parts.next())
+	else {
+		return Err!("Expected a signing key in the format `ed25519 <version> <base64 seed>`.");
+	};
+
+	if !version
+		.chars()
+		.all(|c| c.is_ascii_alphanumeric() || c == '_')
+	{
+		return Err!("Key version {version:?} may only contain alphanumerics and underscores.");
+	}
+
+	let seed = Base64::<Standard>::parse(seed)
+		.map_err(|e| err!("Failed to decode signing key seed: {e}"))?;
+
+	if seed.as_bytes().len() != 32 {
+		return Err!("Signing key seed must be 32 bytes long.");
+	}
+
+	let der = [PKCS8_ED25519_PREFIX.as_slice(), seed.as_bytes()].concat();
+	Ed25519KeyPair::from_der(&der, version.to_owned())
+		.map_err(|e| err!("Failed to load ed25519 keypair from signing key: {e:?}"))?;
+
+	Ok((version.to_owned(), der))
+}
+
+/// Replaces the active keypair with the one staged by a rotation, moving the
+/// previous key into our `old_verify_keys` expired as of now.
+fn promote(db: &Arc<Database>, server_name: &ServerName) -> Result {
+	let global = &db["global"];
+	let Ok(next) = global.get_blocking(NEXT_KEYPAIR) else {
+		return Ok(());
+	};
+
+	if let Ok(current) = global.get_blocking(b"keypair") {
+		let (version, der) = parse(&current);
+		let keypair = Ed25519KeyPair::from_der(&der, version)
+			.map_err(|e| err!("Failed to load ed25519 keypair from der: {e:?}"))?;
+
+		retire(db, server_name, &keypair)?;
+	}
+
+	let (version, _) = parse(&next);
+	global.insert(b"keypair", &*next);
+	global.remove(NEXT_KEYPAIR);
+	info!("Rotated Ed25519 keypair; the active key is now ed25519:{version}");
+
+	Ok(())
+}
+
+fn retire(db: &Arc<Database>, server_name: &ServerName, keypair: &Ed25519KeyPair) -> Result {
+	let server_signingkeys = &db["server_signingkeys"];
+	let mut keys: ServerSigningKeys = server_signingkeys
+		.get_blocking(server_name)
+		.deserialized()
+		.unwrap_or_else(|_| {
+			ServerSigningKeys::new(server_name.to_owned(), MilliSecondsSinceUnixEpoch::now())
+		});
+
+	let key_id = format!("ed25519:{}", keypair.version()).try_into()?;
+	let key = Base64::new(keypair.public_key().to_vec());
+
+	keys.verify_keys.remove(&key_id);
+	keys.old_verify_keys
+		.insert(key_id, OldVerifyKey::new(MilliSecondsSinceUnixEpoch::now(), key));
+
+	server_signingkeys.raw_put(server_name, Json(&keys));
+
+	Ok(())
+}
+
 fn load(db: &Arc<Database>) -> Result<Box<Ed25519KeyPair>> {
 	let (version, key) = db["global"]
 		.get_blocking(b"keypair")
 		.map(|ref val| {
-			// database deserializer is having trouble with this so it's manual for now
-			let mut elems = val.split(|&b| b == b'\xFF');
-			let vlen = elems.next().expect("invalid keypair entry").len();
-			let ver = string_from_bytes(&val[..vlen]).expect("invalid keypair version");
-			let der = val[vlen.saturating_add(1)..].to_vec();
+			let (ver, der) = parse(val);
 			debug!("Found existing Ed25519 keypair: {ver:?}");
 			(ver, der)
 		})
@@ -45,14 +150,17 @@ fn load(db: &Arc<Database>) -> Result<Box<Ed25519KeyPair>> {
 	Ok(Box::new(key))
 }
 
-fn create(db: &Arc<Database>) -> Result<(String, Vec<u8>)> {
-	let keypair = Ed25519KeyPair::generate()
-		.map_err(|e| err!("Failed to generate new ed25519 keypair: {e:?}"))?;
-
-	let id = utils::rand::string(8);
-	debug_info!("Generated new Ed25519 keypair: {id:?}");
+pub(super) fn parse(val: &[u8]) -> (String, Vec<u8>) {
+	// database deserializer is having trouble with this so it's manual for now
+	let mut elems = val.split(|&b| b == b'\xFF');
+	let vlen = elems.next().expect("invalid keypair entry").len();
+	let ver = string_from_bytes(&val[..vlen]).expect("invalid keypair version");
+	let der = val[vlen.saturating_add(1)..].to_vec();
+	(ver, der)
+}
 
-	let value: (String, Vec<u8>) = (id, keypair.to_vec());
+fn create(db: &Arc<Database>) -> Result<(String, Vec<u8>)> {
+	let value = generate()?;
 	db["global"].raw_put(b"keypair", &value);
 
 	Ok(value)
@@ -63,3 +171,42 @@ fn remove(db: &Arc<Database>) {
 	let global = &db["global"];
 	global.remove(b"keypair");
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{PKCS8_ED25519_PREFIX, parse_signing_key};
+
+	/// Unpadded base64 of a seed of 32 zero bytes.
+	const SEED: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+	#[test]
+	fn parse_synapse_signing_key() {
+		let (version, der) = parse_signing_key(&format!("ed25519 a_1 {SEED}\n")).unwrap();
+
+		assert_eq!(version, "a_1");
+		assert_eq!(der.len(), 48);
+		assert!(der.starts_with(&PKCS8_ED25519_PREFIX));
+		assert!(der[16..].iter().all(|&b| b == 0));
+	}
+
+	#[test]
+	fn parse_rejects_malformed() {
+		assert!(parse_signing_key("").is_err());
+		assert!(parse_signing_key("ed25519 a_1").is_err());
+		assert!(parse_signing_key(&format!("curve25519 a_1 {SEED}")).is_err());
+		assert!(parse_signing_key(&format!("ed25519 a_1 {SEED} extra")).is_err());
+	}
+
+	#[test]
+	fn parse_rejects_bad_version() {
+		assert!(parse_signing_key(&format!("ed25519 a-1 {SEED}")).is_err());
+		assert!(parse_signing_key(&format!("ed25519 a:1 {SEED}")).is_err());
+	}
+
+	#[test]
+	fn parse_rejects_bad_seed() {
+		assert!(parse_signing_key("ed25519 a_1 AAAA").is_err());
+		assert!(parse_signing_key("ed25519 a_1 !!!!").is_err());
+		assert!(parse_signing_key(&format!("ed25519 a_1 {SEED}AAAA")).is_err());
+	}
+}
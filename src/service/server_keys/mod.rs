@@ -1,16 +1,22 @@
 mod acquire;
 mod get;
 mod keypair;
+mod notary;
 mod request;
+mod rotate;
 mod sign;
 mod verify;
 
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::{Arc, RwLock},
+	time::{Duration, Instant},
+};
 
 use futures::StreamExt;
 use ruma::{
-	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OwnedServerSigningKeyId, ServerName,
-	ServerSigningKeyId,
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OwnedServerName, OwnedServerSigningKeyId,
+	ServerName, ServerSigningKeyId,
 	api::federation::discovery::{ServerSigningKeys, VerifyKey},
 	room_version_rules::RoomVersionRules,
 	serde::Raw,
@@ -27,11 +33,16 @@ pub struct Service {
 	keypair: Box<Ed25519KeyPair>,
 	verify_keys: VerifyKeys,
 	minimum_valid: Duration,
+	notary_backoff: RwLock<HashMap<OwnedServerName, BackoffState>>,
 	services: Arc<crate::services::OnceServices>,
 	db: Data,
 }
 
+type BackoffState = (Instant, u32); // Time of the last failed fetch, number of failed fetches
+
 struct Data {
+	global: Arc<Map>,
+	server_signedkeys: Arc<Map>,
 	server_signingkeys: Arc<Map>,
 }
 
@@ -43,15 +54,18 @@ impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		let minimum_valid = Duration::from_secs(3600);
 
-		let (keypair, verify_keys) = keypair::init(args.db)?;
+		let (keypair, verify_keys) = keypair::init(args.db, &args.server.name)?;
 		debug_assert!(verify_keys.len() == 1, "only one active verify_key supported");
 
 		Ok(Arc::new(Self {
 			keypair,
 			verify_keys,
 			minimum_valid,
+			notary_backoff: RwLock::new(HashMap::new()),
 			services: args.services.clone(),
 			db: Data {
+				global: args.db["global"].clone(),
+				server_signedkeys: args.db["server_signedkeys"].clone(),
 				server_signingkeys: args.db["server_signingkeys"].clone(),
 			},
 		}))
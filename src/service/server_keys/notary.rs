@@ -0,0 +1,124 @@
+use std::{collections::hash_map, time::Instant};
+
+use ruma::{
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OwnedServerSigningKeyId, ServerName,
+	api::federation::discovery::ServerSigningKeys, serde::Raw,
+};
+use tuwunel_core::{
+	Result, debug, debug_warn, err, implement, utils::continue_exponential_backoff_secs,
+};
+use tuwunel_database::Deserialized;
+
+use super::key_exists;
+
+/// Keys of a remote server as served to other servers acting as a notary
+/// (`/_matrix/key/v2/query`), counter-signed with our own key.
+///
+/// - The cached keys as signed by the origin are used while they remain valid
+///   until `minimum_valid_until_ts` and contain the requested key ids
+/// - Otherwise they are fetched from the origin first, falling back to the
+///   cached keys when it cannot be reached
+/// - Origins which failed to answer are not asked again until their backoff
+///   expires
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn notary_keys(
+	&self,
+	origin: &ServerName,
+	key_ids: &[OwnedServerSigningKeyId],
+	minimum_valid_until_ts: Option<MilliSecondsSinceUnixEpoch>,
+) -> Result<CanonicalJsonObject> {
+	let cached = self.signed_keys_for(origin).await.ok();
+
+	let usable = cached.as_ref().is_some_and(|keys| {
+		keys.deserialize()
+			.is_ok_and(|keys: ServerSigningKeys| {
+				let minimum_valid_until_ts =
+					minimum_valid_until_ts.unwrap_or_else(MilliSecondsSinceUnixEpoch::now);
+
+				keys.valid_until_ts >= minimum_valid_until_ts
+					&& key_ids
+						.iter()
+						.all(|key_id| key_exists(&keys, key_id))
+			})
+	});
+
+	let keys = if usable {
+		cached
+	} else if self.is_backed_off(origin) {
+		debug!(%origin, "Backing off from refreshing keys, serving cached keys");
+		cached
+	} else {
+		match self.server_request(origin).await {
+			| Ok(server_keys) => {
+				self.notary_backoff
+					.write()
+					.expect("locked")
+					.remove(origin);
+
+				self.add_signing_keys(server_keys).await;
+				self.signed_keys_for(origin).await.ok()
+			},
+			| Err(e) => {
+				debug_warn!(%origin, "Failed to refresh keys, serving cached keys: {e}");
+				self.back_off(origin);
+				cached
+			},
+		}
+	};
+
+	let mut keys: CanonicalJsonObject = keys
+		.ok_or_else(|| err!(Request(NotFound("No keys found for {origin}."))))
+		.and_then(|keys| serde_json::from_str(keys.json().get()).map_err(Into::into))?;
+
+	self.sign_json(&mut keys)?;
+
+	Ok(keys)
+}
+
+#[implement(super::Service)]
+fn back_off(&self, origin: &ServerName) {
+	use hash_map::Entry::{Occupied, Vacant};
+
+	match self
+		.notary_backoff
+		.write()
+		.expect("locked")
+		.entry(origin.into())
+	{
+		| Vacant(e) => {
+			e.insert((Instant::now(), 1));
+		},
+		| Occupied(mut e) => {
+			*e.get_mut() = (Instant::now(), e.get().1.saturating_add(1));
+		},
+	}
+}
+
+#[implement(super::Service)]
+fn is_backed_off(&self, origin: &ServerName) -> bool {
+	const MIN_SECS: u64 = 30;
+	const MAX_SECS: u64 = 60 * 60 * 24;
+
+	let Some((time, tries)) = self
+		.notary_backoff
+		.read()
+		.expect("locked")
+		.get(origin)
+		.copied()
+	else {
+		return false;
+	};
+
+	continue_exponential_backoff_secs(MIN_SECS, MAX_SECS, time.elapsed(), tries)
+}
+
+/// The latest keys of the origin as it signed them.
+#[implement(super::Service)]
+async fn signed_keys_for(&self, origin: &ServerName) -> Result<Raw<ServerSigningKeys>> {
+	self.db
+		.server_signedkeys
+		.get(origin)
+		.await
+		.deserialized()
+}
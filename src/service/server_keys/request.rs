@@ -1,7 +1,8 @@
 use std::{collections::BTreeMap, fmt::Debug};
 
 use ruma::{
-	OwnedServerName, OwnedServerSigningKeyId, ServerName, ServerSigningKeyId,
+	CanonicalJsonObject, OwnedServerName, OwnedServerSigningKeyId, ServerName,
+	ServerSigningKeyId,
 	api::federation::discovery::{
 		ServerSigningKeys, get_remote_server_keys,
 		get_remote_server_keys_batch::{self, v2::QueryCriteria},
@@ -9,6 +10,9 @@
 	},
 };
 use tuwunel_core::{Err, Result, debug, implement};
+use tuwunel_database::Json;
+
+use super::{PubKeyMap, PubKeys};
 
 #[implement(super::Service)]
 pub async fn batch_notary_request<'a, S, K>(
@@ -109,13 +113,14 @@ pub async fn notary_request(
 pub async fn server_request(&self, target: &ServerName) -> Result<ServerSigningKeys> {
 	use get_server_keys::v2::Request;
 
-	let server_signing_key = self
+	let server_key = self
 		.services
 		.sending
 		.send_federation_request(target, Request::new())
 		.await
-		.map(|response| response.server_key)
-		.and_then(|key| key.deserialize().map_err(Into::into))?;
+		.map(|response| response.server_key)?;
+
+	let server_signing_key: ServerSigningKeys = server_key.deserialize()?;
 
 	if server_signing_key.server_name != target {
 		return Err!(BadServerResponse(debug_warn!(
@@ -125,5 +130,25 @@ pub async fn server_request(&self, target: &ServerName) -> Result<ServerSigningK
 		)));
 	}
 
+	// The keys must be signed by the origin with the keys themselves
+	let verify_keys: PubKeys = server_signing_key
+		.verify_keys
+		.iter()
+		.map(|(key_id, verify_key)| (key_id.to_string(), verify_key.key.clone()))
+		.collect();
+
+	let verify_keys: PubKeyMap = [(target.as_str().into(), verify_keys)].into();
+	let object: CanonicalJsonObject = serde_json::from_str(server_key.json().get())?;
+	if let Err(e) = ruma::signatures::verify_json(&verify_keys, &object) {
+		return Err!(BadServerResponse(debug_warn!(
+			"Keys of {target} are not signed by their verify keys: {e}"
+		)));
+	}
+
+	// Keep the keys as signed by the origin to serve them as a notary
+	self.db
+		.server_signedkeys
+		.raw_put(target, Json(&server_key));
+
 	Ok(server_signing_key)
 }
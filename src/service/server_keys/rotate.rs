@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+
+use ruma::{OwnedServerSigningKeyId, api::federation::discovery::OldVerifyKey};
+use tuwunel_core::{Err, Result, implement};
+
+use super::keypair::{self, NEXT_KEYPAIR};
+
+/// Stages a newly generated keypair which replaces the active keypair at the
+/// next startup. Returns the id of the new key.
+#[implement(super::Service)]
+pub async fn rotate_keypair(&self) -> Result<OwnedServerSigningKeyId> {
+	let (version, der) = keypair::generate()?;
+
+	self.stage_keypair(version, der).await
+}
+
+/// Stages the keypair of a signing key in the format of a Synapse signing key
+/// file (`ed25519 <version> <base64 seed>`) which replaces the active keypair
+/// at the next startup. Returns the id of the imported key.
+#[implement(super::Service)]
+pub async fn import_keypair(&self, key: &str) -> Result<OwnedServerSigningKeyId> {
+	let (version, der) = keypair::parse_signing_key(key)?;
+
+	self.stage_keypair(version, der).await
+}
+
+/// Id of the key staged to become active at the next startup.
+#[implement(super::Service)]
+pub async fn pending_key_id(&self) -> Option<OwnedServerSigningKeyId> {
+	let next = self.db.global.get(NEXT_KEYPAIR).await.ok()?;
+	let (version, _) = keypair::parse(&next);
+
+	format!("ed25519:{version}").try_into().ok()
+}
+
+/// Our keys retired by rotations with the time they expired.
+#[implement(super::Service)]
+pub async fn old_verify_keys(&self) -> BTreeMap<OwnedServerSigningKeyId, OldVerifyKey> {
+	self.signing_keys_for(self.services.globals.server_name())
+		.await
+		.map(|keys| keys.old_verify_keys)
+		.unwrap_or_default()
+}
+
+#[implement(super::Service)]
+async fn stage_keypair(&self, version: String, der: Vec<u8>) -> Result<OwnedServerSigningKeyId> {
+	let key_id: OwnedServerSigningKeyId = format!("ed25519:{version}").try_into()?;
+	if key_id == self.active_key_id() || self.old_verify_keys().await.contains_key(&key_id) {
+		return Err!("Key {key_id} has already been used by this server.");
+	}
+
+	self.db
+		.global
+		.raw_put(NEXT_KEYPAIR, &(version, der));
+
+	Ok(key_id)
+}
@@ -0,0 +1,387 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use futures::FutureExt;
+use ruma::{
+	EventId, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId, UserId,
+	events::{
+		RoomAccountDataEventType, StateEventType,
+		room::{
+			create::RoomCreateEventContent,
+			history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
+			join_rules::{JoinRule, RoomJoinRulesEventContent},
+			member::{MembershipState, RoomMemberEventContent},
+			message::{
+				LimitType, MessageType, RoomMessageEventContent, ServerNoticeMessageEventContent,
+				ServerNoticeType,
+			},
+			name::RoomNameEventContent,
+			pinned_events::RoomPinnedEventsEventContent,
+			power_levels::RoomPowerLevelsEventContent,
+		},
+		tag::{TagEvent, TagEventContent, TagInfo, TagName},
+	},
+};
+use tuwunel_core::{Err, Event, Result, debug_info, err, implement, pdu::PduBuilder};
+use tuwunel_database::{Deserialized, Map};
+
+pub struct Service {
+	services: Arc<crate::services::OnceServices>,
+	db: Data,
+
+	/// System user delivering notices; `None` when server notices are
+	/// disabled.
+	user: Option<OwnedUserId>,
+}
+
+struct Data {
+	userid_servernoticeroom: Arc<Map>,
+}
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		let user = args
+			.server
+			.config
+			.server_notices_localpart
+			.as_deref()
+			.map(|localpart| UserId::parse_with_server_name(localpart, &args.server.name))
+			.transpose()
+			.map_err(|e| err!(Config("server_notices_localpart", "Invalid localpart: {e}")))?;
+
+		Ok(Arc::new(Self {
+			services: args.services.clone(),
+			db: Data {
+				userid_servernoticeroom: args.db["userid_servernoticeroom"].clone(),
+			},
+			user,
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// The system user sending server notices, if enabled.
+#[implement(Service)]
+#[inline]
+pub fn user(&self) -> Option<&UserId> { self.user.as_deref() }
+
+/// Sends a plain server notice to a local user.
+#[implement(Service)]
+pub async fn send_text(&self, user_id: &UserId, body: &str) -> Result<OwnedEventId> {
+	self.send(user_id, RoomMessageEventContent::text_markdown(body))
+		.await
+}
+
+/// Sends an `m.server_notice.usage_limit_reached` notice and pins it so
+/// clients display a blocking banner until it is cleared with
+/// [`Service::clear_usage_limit`].
+#[implement(Service)]
+pub async fn usage_limit_reached(
+	&self,
+	user_id: &UserId,
+	body: &str,
+	admin_contact: Option<String>,
+) -> Result<OwnedEventId> {
+	let mut notice = ServerNoticeMessageEventContent::new(
+		body.to_owned(),
+		ServerNoticeType::UsageLimitReached,
+	);
+
+	notice.admin_contact = admin_contact;
+	notice.limit_type = Some(LimitType::MonthlyActiveUser);
+
+	let content = RoomMessageEventContent::new(MessageType::ServerNotice(notice));
+	let event_id = self.send(user_id, content).await?;
+
+	let (system_user, room_id) = self.room(user_id).await?;
+	let mut pinned = self.pinned_events(&room_id).await;
+	pinned.push(event_id.clone());
+	self.set_pinned_events(system_user, &room_id, pinned)
+		.await?;
+
+	Ok(event_id)
+}
+
+/// Unpins any usage limit notices in the user's server notices room, leaving
+/// other pinned events. Returns false if there was nothing to clear.
+#[implement(Service)]
+pub async fn clear_usage_limit(&self, user_id: &UserId) -> Result<bool> {
+	let (system_user, room_id) = self.room(user_id).await?;
+	let pinned = self.pinned_events(&room_id).await;
+
+	let mut retained = Vec::with_capacity(pinned.len());
+	for event_id in &pinned {
+		if !self.is_usage_limit_notice(event_id).await {
+			retained.push(event_id.clone());
+		}
+	}
+
+	if retained.len() == pinned.len() {
+		return Ok(false);
+	}
+
+	self.set_pinned_events(system_user, &room_id, retained)
+		.await?;
+
+	Ok(true)
+}
+
+/// Sends a notice to a local user, creating their server notices room if
+/// they are not already in one.
+#[implement(Service)]
+#[tracing::instrument(skip(self, content), level = "debug")]
+pub async fn send(
+	&self,
+	user_id: &UserId,
+	content: RoomMessageEventContent,
+) -> Result<OwnedEventId> {
+	let (system_user, room_id) = self.room(user_id).await?;
+	let state_lock = self.services.state.mutex.lock(&room_id).await;
+
+	self.services
+		.timeline
+		.build_and_append_pdu(PduBuilder::timeline(&content), system_user, &room_id, &state_lock)
+		.boxed()
+		.await
+}
+
+/// Finds or creates the server notices room of a local user.
+#[implement(Service)]
+async fn room(&self, user_id: &UserId) -> Result<(&UserId, OwnedRoomId)> {
+	let Some(system_user) = self.user() else {
+		return Err!(Config(
+			"server_notices_localpart",
+			"Server notices are not enabled on this server."
+		));
+	};
+
+	if !self.services.globals.user_is_local(user_id) {
+		return Err!(Request(InvalidParam("Server notices can only be sent to local users.")));
+	}
+
+	if !self.services.users.exists(user_id).await {
+		return Err!(Request(NotFound("User {user_id} does not exist.")));
+	}
+
+	if user_id == system_user || user_id == self.services.globals.server_user {
+		return Err!(Request(InvalidParam("Cannot send server notices to a system user.")));
+	}
+
+	if let Some(room_id) = self.find_room(system_user, user_id).await {
+		return Ok((system_user, room_id));
+	}
+
+	self.create_room(system_user, user_id)
+		.boxed()
+		.await
+		.map(|room_id| (system_user, room_id))
+}
+
+/// The user's server notices room while the system user is joined and the
+/// user is still joined or invited. Rooms the user has left are abandoned.
+#[implement(Service)]
+async fn find_room(&self, system_user: &UserId, user_id: &UserId) -> Option<OwnedRoomId> {
+	let room_id: OwnedRoomId = self
+		.db
+		.userid_servernoticeroom
+		.get(user_id)
+		.await
+		.deserialized()
+		.ok()?;
+
+	let state_cache = &self.services.state_cache;
+	let current = state_cache.is_joined(system_user, &room_id).await
+		&& (state_cache.is_joined(user_id, &room_id).await
+			|| state_cache.is_invited(user_id, &room_id).await);
+
+	if !current {
+		self.db.userid_servernoticeroom.remove(user_id);
+		return None;
+	}
+
+	Some(room_id)
+}
+
+#[implement(Service)]
+async fn create_room(&self, system_user: &UserId, user_id: &UserId) -> Result<OwnedRoomId> {
+	self.ensure_user(system_user).await?;
+
+	let config = &self.services.server.config;
+	let room_id = RoomId::new_v1(self.services.globals.server_name());
+	let room_version = config.default_room_version.clone();
+
+	let _short_id = self
+		.services
+		.short
+		.get_or_create_shortroomid(&room_id)
+		.await;
+
+	let state_lock = self.services.state.mutex.lock(&room_id).await;
+
+	let create_content = {
+		use RoomVersionId::*;
+		match room_version {
+			| V1 | V2 | V3 | V4 | V5 | V6 | V7 | V8 | V9 | V10 =>
+				RoomCreateEventContent::new_v1(system_user.into()),
+			| _ => RoomCreateEventContent::new_v11(),
+		}
+	};
+
+	let power_levels = RoomPowerLevelsEventContent {
+		users: BTreeMap::from_iter([(system_user.into(), 100.into())]),
+		events_default: 100.into(),
+		..Default::default()
+	};
+
+	let state: [PduBuilder; 6] = [
+		PduBuilder::state(String::new(), &RoomCreateEventContent {
+			room_version: room_version.clone(),
+			..create_content
+		}),
+		PduBuilder::state(String::from(system_user), &RoomMemberEventContent {
+			displayname: Some(config.server_notices_displayname.clone()),
+			avatar_url: config.server_notices_avatar_url.clone(),
+			..RoomMemberEventContent::new(MembershipState::Join)
+		}),
+		PduBuilder::state(String::new(), &power_levels),
+		PduBuilder::state(String::new(), &RoomJoinRulesEventContent::new(JoinRule::Invite)),
+		PduBuilder::state(
+			String::new(),
+			&RoomHistoryVisibilityEventContent::new(HistoryVisibility::Shared),
+		),
+		PduBuilder::state(
+			String::new(),
+			&RoomNameEventContent::new(config.server_notices_room_name.clone()),
+		),
+	];
+
+	for pdu in state {
+		self.services
+			.timeline
+			.build_and_append_pdu(pdu, system_user, &room_id, &state_lock)
+			.boxed()
+			.await?;
+	}
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(
+				String::from(user_id),
+				&RoomMemberEventContent::new(MembershipState::Invite),
+			),
+			system_user,
+			&room_id,
+			&state_lock,
+		)
+		.boxed()
+		.await?;
+
+	self.set_notice_tag(&room_id, user_id).await?;
+	self.db
+		.userid_servernoticeroom
+		.insert(user_id, &room_id);
+
+	debug_info!(%user_id, %room_id, "Created server notices room");
+
+	Ok(room_id)
+}
+
+/// Creates the system user on first use with the configured profile. The
+/// profile of an existing user is left alone.
+#[implement(Service)]
+async fn ensure_user(&self, system_user: &UserId) -> Result {
+	let config = &self.services.server.config;
+	if self.services.users.exists(system_user).await {
+		return Ok(());
+	}
+
+	self.services
+		.users
+		.create(system_user, None, None)
+		.await?;
+
+	self.services
+		.users
+		.set_displayname(system_user, Some(config.server_notices_displayname.clone()));
+
+	self.services
+		.users
+		.set_avatar_url(system_user, config.server_notices_avatar_url.clone());
+
+	Ok(())
+}
+
+#[implement(Service)]
+async fn set_notice_tag(&self, room_id: &RoomId, user_id: &UserId) -> Result {
+	let mut event = self
+		.services
+		.account_data
+		.get_room(room_id, user_id, RoomAccountDataEventType::Tag)
+		.await
+		.unwrap_or_else(|_| TagEvent {
+			content: TagEventContent { tags: BTreeMap::new() },
+		});
+
+	event
+		.content
+		.tags
+		.insert(TagName::ServerNotice, TagInfo::new());
+
+	self.services
+		.account_data
+		.update(
+			Some(room_id),
+			user_id,
+			RoomAccountDataEventType::Tag,
+			&serde_json::to_value(event)?,
+		)
+		.await
+}
+
+#[implement(Service)]
+async fn is_usage_limit_notice(&self, event_id: &EventId) -> bool {
+	self.services
+		.timeline
+		.get_pdu(event_id)
+		.await
+		.and_then(|pdu| pdu.get_content::<RoomMessageEventContent>())
+		.is_ok_and(|content| {
+			matches!(
+				content.msgtype,
+				MessageType::ServerNotice(notice)
+					if notice.server_notice_type == ServerNoticeType::UsageLimitReached
+			)
+		})
+}
+
+#[implement(Service)]
+async fn pinned_events(&self, room_id: &RoomId) -> Vec<OwnedEventId> {
+	self.services
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomPinnedEvents, "")
+		.await
+		.map(|content: RoomPinnedEventsEventContent| content.pinned)
+		.unwrap_or_default()
+}
+
+#[implement(Service)]
+async fn set_pinned_events(
+	&self,
+	system_user: &UserId,
+	room_id: &RoomId,
+	pinned: Vec<OwnedEventId>,
+) -> Result {
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(String::new(), &RoomPinnedEventsEventContent::new(pinned)),
+			system_user,
+			room_id,
+			&state_lock,
+		)
+		.await
+		.map(|_| ())
+}
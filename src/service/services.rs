@@ -9,10 +9,10 @@
 
 pub use crate::OnceServices;
 use crate::{
-	account_data, admin, appservice, client, config, deactivate, emergency, federation, globals,
-	key_backups,
+	account_data, admin, appservice, client, config, deactivate, delayed_events, emergency,
+	federation, globals, key_backups,
 	manager::Manager,
-	media, membership, presence, pusher, resolver, rooms, sending, server_keys,
+	media, membership, presence, pusher, resolver, rooms, sending, server_keys, server_notices,
 	service::{Args, Service},
 	sync, transaction_ids, uiaa, users,
 };
@@ -49,16 +49,19 @@ pub struct Services {
 	pub threads: Arc<rooms::threads::Service>,
 	pub timeline: Arc<rooms::timeline::Service>,
 	pub typing: Arc<rooms::typing::Service>,
+	pub upgrade: Arc<rooms::upgrade::Service>,
 	pub user: Arc<rooms::user::Service>,
 	pub federation: Arc<federation::Service>,
 	pub sending: Arc<sending::Service>,
 	pub server_keys: Arc<server_keys::Service>,
+	pub server_notices: Arc<server_notices::Service>,
 	pub sync: Arc<sync::Service>,
 	pub transaction_ids: Arc<transaction_ids::Service>,
 	pub uiaa: Arc<uiaa::Service>,
 	pub users: Arc<users::Service>,
 	pub membership: Arc<membership::Service>,
 	pub deactivate: Arc<deactivate::Service>,
+	pub delayed_events: Arc<delayed_events::Service>,
 
 	manager: Mutex<Option<Arc<Manager>>>,
 	pub server: Arc<Server>,
@@ -111,16 +114,19 @@ macro_rules! build {
 		threads: build!(rooms::threads::Service),
 		timeline: build!(rooms::timeline::Service),
 		typing: build!(rooms::typing::Service),
+		upgrade: build!(rooms::upgrade::Service),
 		user: build!(rooms::user::Service),
 		federation: build!(federation::Service),
 		sending: build!(sending::Service),
 		server_keys: build!(server_keys::Service),
+		server_notices: build!(server_notices::Service),
 		sync: build!(sync::Service),
 		transaction_ids: build!(transaction_ids::Service),
 		uiaa: build!(uiaa::Service),
 		users: build!(users::Service),
 		membership: build!(membership::Service),
 		deactivate: build!(deactivate::Service),
+		delayed_events: build!(delayed_events::Service),
 
 		manager: Mutex::new(None),
 		server,
@@ -170,16 +176,19 @@ macro_rules! cast {
 		cast!(self.threads),
 		cast!(self.timeline),
 		cast!(self.typing),
+		cast!(self.upgrade),
 		cast!(self.user),
 		cast!(self.federation),
 		cast!(self.sending),
 		cast!(self.server_keys),
+		cast!(self.server_notices),
 		cast!(self.sync),
 		cast!(self.transaction_ids),
 		cast!(self.uiaa),
 		cast!(self.users),
 		cast!(self.membership),
 		cast!(self.deactivate),
+		cast!(self.delayed_events),
 	]
 	.into_iter()
 }
@@ -1,3 +1,4 @@
+mod stream;
 mod watch;
 
 use std::{
@@ -9,6 +10,8 @@
 use tuwunel_core::Result;
 use tuwunel_database::Map;
 
+pub use self::stream::stream_device_id;
+
 pub struct Service {
 	db: Data,
 	services: Arc<crate::services::OnceServices>,
@@ -17,6 +20,7 @@ pub struct Service {
 
 pub struct Data {
 	todeviceid_events: Arc<Map>,
+	userdevicestreamid_syncposition: Arc<Map>,
 	userroomid_joined: Arc<Map>,
 	userroomid_invitestate: Arc<Map>,
 	userroomid_leftstate: Arc<Map>,
@@ -48,6 +52,8 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			db: Data {
 				todeviceid_events: args.db["todeviceid_events"].clone(),
+				userdevicestreamid_syncposition: args.db["userdevicestreamid_syncposition"]
+					.clone(),
 				userroomid_joined: args.db["userroomid_joined"].clone(),
 				userroomid_invitestate: args.db["userroomid_invitestate"].clone(),
 				userroomid_leftstate: args.db["userroomid_leftstate"].clone(),
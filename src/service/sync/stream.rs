@@ -0,0 +1,174 @@
+//! Independent sync streams of a device.
+//!
+//! Several consumers (e.g. an E2EE proxy and a notification daemon) may run
+//! their own sync loop with the same access token. Each names its loop with a
+//! stream identifier; the default stream of a device has none. To-device
+//! events are only removed once every stream of the device acknowledged them.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use ruma::{DeviceId, OwnedDeviceId, UserId};
+use serde::{Deserialize, Serialize};
+use tuwunel_core::{
+	Err, Result, implement,
+	utils::{ReadyExt, millis_since_unix_epoch, stream::TryIgnore},
+};
+use tuwunel_database::{Interfix, Json};
+
+/// Streams not synced for this long stop holding back to-device events.
+const STREAM_EXPIRY: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+/// Name of the default stream in the database.
+const DEFAULT_STREAM: &str = "";
+
+/// Longest stream identifier accepted from a client.
+const STREAM_ID_MAX_LEN: usize = 64;
+
+/// Most streams a device may sync with besides the default stream.
+const STREAMS_MAX: usize = 16;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct StreamPosition {
+	/// The stream received everything up to this count.
+	pub since: u64,
+
+	/// Last time the stream synced, in milliseconds since the unix epoch.
+	pub last_seen: u64,
+}
+
+/// Rejects stream identifiers which are too long and new streams of a device
+/// which already syncs with the most streams allowed.
+#[implement(super::Service)]
+pub async fn check_stream(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	stream_id: Option<&str>,
+) -> Result {
+	let Some(stream_id) = stream_id.filter(|&stream_id| stream_id != DEFAULT_STREAM) else {
+		return Ok(());
+	};
+
+	if stream_id.len() > STREAM_ID_MAX_LEN {
+		return Err!(Request(InvalidParam(
+			"Stream identifier is longer than {STREAM_ID_MAX_LEN} bytes."
+		)));
+	}
+
+	let key = (user_id, device_id, stream_id);
+	if self
+		.db
+		.userdevicestreamid_syncposition
+		.qry(&key)
+		.await
+		.is_ok()
+	{
+		return Ok(());
+	}
+
+	let prefix = (user_id, device_id, Interfix);
+	let streams = self
+		.db
+		.userdevicestreamid_syncposition
+		.keys_prefix_raw(&prefix)
+		.ignore_err()
+		.count()
+		.await;
+
+	// The default stream is among the recorded streams once it has synced.
+	if streams >= STREAMS_MAX.saturating_add(1) {
+		return Err!(Request(Forbidden("Device already syncs with {STREAMS_MAX} streams.")));
+	}
+
+	Ok(())
+}
+
+/// Records the position of the stream and removes the to-device events every
+/// live stream of the device has received.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn ack_to_device(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	stream_id: Option<&str>,
+	since: u64,
+) {
+	let stream_id = stream_id.unwrap_or(DEFAULT_STREAM);
+	let now = millis_since_unix_epoch();
+	let position = StreamPosition { since, last_seen: now };
+
+	self.db
+		.userdevicestreamid_syncposition
+		.put((user_id, device_id, stream_id), Json(position));
+
+	let expired_before = now.saturating_sub(
+		STREAM_EXPIRY
+			.as_millis()
+			.try_into()
+			.unwrap_or(u64::MAX),
+	);
+
+	let mut acknowledged = since;
+	let prefix = (user_id, device_id, Interfix);
+	self.db
+		.userdevicestreamid_syncposition
+		.stream_prefix_raw(&prefix)
+		.ignore_err()
+		.ready_for_each(|(key, val)| match serde_json::from_slice::<StreamPosition>(val) {
+			| Ok(position) if position.last_seen >= expired_before => {
+				acknowledged = acknowledged.min(position.since);
+			},
+			| _ => self
+				.db
+				.userdevicestreamid_syncposition
+				.remove(key),
+		})
+		.await;
+
+	self.services
+		.users
+		.remove_to_device_events(user_id, device_id, acknowledged)
+		.await;
+}
+
+/// Whether the device has synced with any stream.
+#[implement(super::Service)]
+pub async fn device_syncs(&self, user_id: &UserId, device_id: &DeviceId) -> bool {
+	let prefix = (user_id, device_id, Interfix);
+	self.db
+		.userdevicestreamid_syncposition
+		.keys_prefix_raw(&prefix)
+		.ignore_err()
+		.next()
+		.await
+		.is_some()
+}
+
+/// Forgets all streams of a removed device.
+#[implement(super::Service)]
+pub async fn forget_streams(&self, user_id: &UserId, device_id: &DeviceId) {
+	let prefix = (user_id, device_id, Interfix);
+	self.db
+		.userdevicestreamid_syncposition
+		.keys_prefix_raw(&prefix)
+		.ignore_err()
+		.ready_for_each(|key| {
+			self.db
+				.userdevicestreamid_syncposition
+				.remove(key);
+		})
+		.await;
+}
+
+/// Device under which per-device sync state such as lazy-loaded members is
+/// kept for the stream, so streams do not consume each other's state. The
+/// default stream uses the device itself.
+#[must_use]
+pub fn stream_device_id(device_id: &DeviceId, stream_id: Option<&str>) -> OwnedDeviceId {
+	match stream_id {
+		| None | Some(DEFAULT_STREAM) => device_id.to_owned(),
+		| Some(stream_id) => format!("{device_id}/{stream_id}").into(),
+	}
+}
@@ -1,9 +1,13 @@
 use std::sync::Arc;
 
 use ruma::{DeviceId, TransactionId, UserId};
-use tuwunel_core::{Result, implement};
+use tuwunel_core::{Result, err, implement, utils::string_from_bytes};
 use tuwunel_database::{Handle, Map};
 
+/// Tag of the response of transactions which scheduled a delayed event
+/// (MSC4140), followed by the delay id. Event ids never contain NUL.
+const DELAYED_TAG: &[u8] = b"\0delayed\0";
+
 pub struct Service {
 	db: Data,
 }
@@ -12,6 +16,16 @@ struct Data {
 	userdevicetxnid_response: Arc<Map>,
 }
 
+/// What a transaction which was already used produced.
+#[derive(Debug)]
+pub enum TxnResponse {
+	/// Id of the event the transaction sent; empty for to-device messages.
+	Event(Vec<u8>),
+
+	/// Delay id of the delayed event the transaction scheduled (MSC4140).
+	Delayed(String),
+}
+
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
@@ -58,3 +72,36 @@ pub async fn existing_txnid(
 	let key = (user_id, device_id, txn_id);
 	self.db.userdevicetxnid_response.qry(&key).await
 }
+
+/// Records that the transaction scheduled the delayed event.
+#[implement(Service)]
+pub fn add_delayed_txnid(
+	&self,
+	user_id: &UserId,
+	device_id: Option<&DeviceId>,
+	txn_id: &TransactionId,
+	delay_id: &str,
+) {
+	let response = [DELAYED_TAG, delay_id.as_bytes()].concat();
+	self.add_txnid(user_id, device_id, txn_id, &response);
+}
+
+/// What the transaction produced when it was already used.
+#[implement(Service)]
+pub async fn existing_txn_response(
+	&self,
+	user_id: &UserId,
+	device_id: Option<&DeviceId>,
+	txn_id: &TransactionId,
+) -> Result<TxnResponse> {
+	let response = self
+		.existing_txnid(user_id, device_id, txn_id)
+		.await?;
+
+	match response.strip_prefix(DELAYED_TAG) {
+		| Some(delay_id) => string_from_bytes(delay_id)
+			.map(TxnResponse::Delayed)
+			.map_err(|e| err!(Database("Invalid delay_id in txnid data: {e:?}"))),
+		| None => Ok(TxnResponse::Event(response.to_vec())),
+	}
+}
@@ -0,0 +1,111 @@
+use ruma::{
+	DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, UserId,
+	api::client::{dehydrated_device::DehydratedDeviceData, device::Device},
+	serde::Raw,
+};
+use serde::{Deserialize, Serialize};
+use tuwunel_core::{Err, Result, err, implement};
+use tuwunel_database::{Deserialized, Json};
+
+/// Dehydrated device of a user (MSC3814). The device itself is an ordinary
+/// device without an access token so to-device events queue for it as usual;
+/// this record holds the opaque data needed to rehydrate it.
+#[derive(Deserialize, Serialize)]
+struct DehydratedDevice {
+	device_id: OwnedDeviceId,
+	device_data: Raw<DehydratedDeviceData>,
+}
+
+/// Stores a new dehydrated device for the user, removing the previous one
+/// along with its keys and queued to-device events.
+#[implement(super::Service)]
+#[tracing::instrument(level = "debug", skip(self, device_data))]
+pub async fn set_dehydrated_device(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	device_data: Raw<DehydratedDeviceData>,
+	display_name: Option<String>,
+) -> Result {
+	let previous = self.get_dehydrated_device_id(user_id).await.ok();
+	if previous.as_deref() != Some(device_id)
+		&& self
+			.get_device_metadata(user_id, device_id)
+			.await
+			.is_ok()
+	{
+		return Err!(Request(InvalidParam("Device {device_id} already exists.")));
+	}
+
+	if let Some(previous) = previous {
+		self.remove_device(user_id, &previous).await;
+	}
+
+	let device = Device {
+		device_id: device_id.into(),
+		display_name,
+		last_seen_ip: None,
+		last_seen_ts: Some(MilliSecondsSinceUnixEpoch::now()),
+	};
+
+	let dehydrated = DehydratedDevice { device_id: device_id.into(), device_data };
+
+	self.update_device_metadata(user_id, device_id, &device)
+		.await?;
+
+	self.db
+		.userid_dehydrateddevice
+		.raw_put(user_id, Json(dehydrated));
+
+	Ok(())
+}
+
+/// Returns the dehydrated device of the user and its data.
+#[implement(super::Service)]
+pub async fn get_dehydrated_device(
+	&self,
+	user_id: &UserId,
+) -> Result<(OwnedDeviceId, Raw<DehydratedDeviceData>)> {
+	self.db
+		.userid_dehydrateddevice
+		.get(user_id)
+		.await
+		.deserialized::<DehydratedDevice>()
+		.map(|dehydrated| (dehydrated.device_id, dehydrated.device_data))
+		.map_err(|_| err!(Request(NotFound("No dehydrated device found."))))
+}
+
+#[implement(super::Service)]
+pub async fn get_dehydrated_device_id(&self, user_id: &UserId) -> Result<OwnedDeviceId> {
+	self.get_dehydrated_device(user_id)
+		.await
+		.map(|(device_id, _)| device_id)
+}
+
+#[implement(super::Service)]
+pub async fn is_dehydrated_device(&self, user_id: &UserId, device_id: &DeviceId) -> bool {
+	self.get_dehydrated_device_id(user_id)
+		.await
+		.is_ok_and(|dehydrated| dehydrated == device_id)
+}
+
+/// Removes the dehydrated device of the user, returning its id.
+#[implement(super::Service)]
+#[tracing::instrument(level = "debug", skip(self))]
+pub async fn remove_dehydrated_device(&self, user_id: &UserId) -> Result<OwnedDeviceId> {
+	let device_id = self.get_dehydrated_device_id(user_id).await?;
+	self.remove_device(user_id, &device_id).await;
+
+	Ok(device_id)
+}
+
+/// Forgets the dehydrated device record when its device is removed.
+#[implement(super::Service)]
+pub(super) async fn unset_dehydrated_device(&self, user_id: &UserId, device_id: &DeviceId) {
+	if self
+		.is_dehydrated_device(user_id, device_id)
+		.await
+	{
+		self.db.userid_dehydrateddevice.remove(user_id);
+	}
+}
@@ -11,6 +11,7 @@
 use serde_json::json;
 use tuwunel_core::{
 	Err, Result, at, implement,
+	result::LogErr,
 	utils::{
 		self, ReadyExt,
 		stream::{IterStream, TryIgnore},
@@ -19,6 +20,8 @@
 };
 use tuwunel_database::{Deserialized, Ignore, Interfix, Json, Map};
 
+use crate::sending::EduBuf;
+
 /// generated user access token length
 pub const TOKEN_LENGTH: usize = 32;
 
@@ -86,6 +89,16 @@ pub async fn remove_device(&self, user_id: &UserId, device_id: &DeviceId) {
 		.await;
 
 	// TODO: Remove onetimekeys
+	self.remove_fallback_keys(user_id, device_id)
+		.await;
+
+	self.unset_dehydrated_device(user_id, device_id)
+		.await;
+
+	self.services
+		.sync
+		.forget_streams(user_id, device_id)
+		.await;
 
 	increment(&self.db.userid_devicelistversion, user_id.as_bytes());
 
@@ -289,17 +302,56 @@ pub async fn add_to_device_event(
 	event_type: &str,
 	content: serde_json::Value,
 ) {
-	let count = self.services.globals.next_count();
+	let event = json!({
+		"type": event_type,
+		"sender": sender,
+		"content": content,
+	});
+
+	let appservices: Vec<_> = self
+		.services
+		.appservice
+		.find_user_registrations(target_user_id)
+		.await
+		.into_iter()
+		.filter(|info| {
+			info.registration.receive_ephemeral && info.is_exclusive_user_match(target_user_id)
+		})
+		.collect();
+
+	// Appservices exclusively owning the user and opted into ephemeral events
+	// (MSC2409) receive the event addressed to the device (MSC4203). It is
+	// still queued for devices which sync themselves.
+	if !appservices.is_empty() {
+		let mut to_device = event.clone();
+		to_device["to_user_id"] = json!(target_user_id);
+		to_device["to_device_id"] = json!(target_device_id);
+
+		for info in appservices {
+			let mut buf = EduBuf::new();
+			serde_json::to_writer(&mut buf, &to_device)
+				.expect("to-device event can be serialized");
 
+			self.services
+				.sending
+				.send_edu_appservice(info.registration.id, buf)
+				.log_err()
+				.ok();
+		}
+
+		if !self
+			.services
+			.sync
+			.device_syncs(target_user_id, target_device_id)
+			.await
+		{
+			return;
+		}
+	}
+
+	let count = self.services.globals.next_count();
 	let key = (target_user_id, target_device_id, *count);
-	self.db.todeviceid_events.put(
-		key,
-		Json(json!({
-			"type": event_type,
-			"sender": sender,
-			"content": content,
-		})),
-	);
+	self.db.todeviceid_events.put(key, Json(event));
 }
 
 #[implement(super::Service)]
@@ -2,17 +2,18 @@
 
 use futures::{Stream, StreamExt, TryFutureExt};
 use ruma::{
-	DeviceId, KeyId, OneTimeKeyAlgorithm, OneTimeKeyId, OneTimeKeyName, OwnedKeyId, RoomId, UInt,
-	UserId,
+	DeviceId, KeyId, OneTimeKeyAlgorithm, OneTimeKeyId, OneTimeKeyName, OwnedKeyId,
+	OwnedOneTimeKeyId, RoomId, UInt, UserId,
 	api::client::error::ErrorKind,
 	encryption::{CrossSigningKey, DeviceKeys, OneTimeKey},
 	serde::Raw,
 };
+use serde::{Deserialize, Serialize};
 use tuwunel_core::{
 	Err, Error, Result, err, implement,
 	utils::{ReadyExt, stream::TryIgnore, string::Unquoted},
 };
-use tuwunel_database::{Deserialized, Ignore, Json};
+use tuwunel_database::{Deserialized, Ignore, Interfix, Json};
 
 #[implement(super::Service)]
 pub async fn add_one_time_key(
@@ -121,6 +122,11 @@ pub async fn take_one_time_key(
 		.next()
 		.await;
 
+	self.services
+		.appservice
+		.notify_device_changes(user_id)
+		.await;
+
 	one_time_key.ok_or_else(|| err!(Request(NotFound("No one-time-key found"))))
 }
 
@@ -155,6 +161,136 @@ pub async fn count_one_time_keys(
 	algorithm_counts
 }
 
+/// Fallback key (MSC2732) of a device for one algorithm. It is handed out
+/// when the one-time keys are exhausted and remains valid until replaced.
+#[derive(Deserialize, Serialize)]
+struct FallbackKey {
+	key_id: OwnedOneTimeKeyId,
+	key: Raw<OneTimeKey>,
+	used: bool,
+}
+
+/// Stores a fallback key, replacing any previous fallback key of the same
+/// algorithm.
+#[implement(super::Service)]
+pub async fn add_fallback_key(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	key_id: &OneTimeKeyId,
+	key: &Raw<OneTimeKey>,
+) -> Result {
+	let algorithm = key_id.algorithm();
+	let dbkey = (user_id, device_id, algorithm.as_str());
+
+	// Clients re-upload their current fallback key; that must not reset it to
+	// unused.
+	if let Ok(existing) = self
+		.db
+		.userdevicealgorithm_fallbackkey
+		.qry(&dbkey)
+		.await
+		.deserialized::<FallbackKey>()
+	{
+		if existing.key_id.as_str() == key_id.as_str() {
+			return Ok(());
+		}
+	}
+
+	let fallback = FallbackKey {
+		key_id: key_id.to_owned(),
+		key: key.clone(),
+		used: false,
+	};
+
+	self.db
+		.userdevicealgorithm_fallbackkey
+		.put(dbkey, Json(fallback));
+
+	let count = self.services.globals.next_count();
+	self.db
+		.userid_lastonetimekeyupdate
+		.raw_put(user_id, *count);
+
+	Ok(())
+}
+
+/// Returns the fallback key of the device for the algorithm, marking it used.
+/// Unlike one-time keys it is not removed.
+#[implement(super::Service)]
+pub async fn take_fallback_key(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	key_algorithm: &OneTimeKeyAlgorithm,
+) -> Result<(OwnedOneTimeKeyId, Raw<OneTimeKey>)> {
+	let dbkey = (user_id, device_id, key_algorithm.as_str());
+	let mut fallback: FallbackKey = self
+		.db
+		.userdevicealgorithm_fallbackkey
+		.qry(&dbkey)
+		.await
+		.deserialized()
+		.map_err(|_| err!(Request(NotFound("No fallback key found"))))?;
+
+	if !fallback.used {
+		fallback.used = true;
+		self.db
+			.userdevicealgorithm_fallbackkey
+			.put(dbkey, Json(&fallback));
+
+		let count = self.services.globals.next_count();
+		self.db
+			.userid_lastonetimekeyupdate
+			.raw_put(user_id, *count);
+
+		self.services
+			.appservice
+			.notify_device_changes(user_id)
+			.await;
+	}
+
+	Ok((fallback.key_id, fallback.key))
+}
+
+/// Algorithms for which the device has a fallback key which has not been
+/// handed out yet.
+#[implement(super::Service)]
+pub async fn unused_fallback_key_types(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+) -> Vec<OneTimeKeyAlgorithm> {
+	type KeyVal<'a> = ((Ignore, Ignore, &'a str), FallbackKey);
+
+	let query = (user_id, device_id);
+	self.db
+		.userdevicealgorithm_fallbackkey
+		.stream_prefix(&query)
+		.ignore_err()
+		.ready_filter_map(|((Ignore, Ignore, algorithm), fallback): KeyVal<'_>| {
+			(!fallback.used).then(|| algorithm.into())
+		})
+		.collect()
+		.await
+}
+
+/// Removes the fallback keys of a device.
+#[implement(super::Service)]
+pub(super) async fn remove_fallback_keys(&self, user_id: &UserId, device_id: &DeviceId) {
+	let prefix = (user_id, device_id, Interfix);
+	self.db
+		.userdevicealgorithm_fallbackkey
+		.keys_prefix_raw(&prefix)
+		.ignore_err()
+		.ready_for_each(|key| {
+			self.db
+				.userdevicealgorithm_fallbackkey
+				.remove(key)
+		})
+		.await;
+}
+
 #[implement(super::Service)]
 pub async fn add_device_keys(
 	&self,
@@ -355,6 +491,11 @@ pub async fn mark_device_key_update(&self, user_id: &UserId) {
 
 	let key = (user_id, *count);
 	self.db.keychangeid_userid.put_raw(key, user_id);
+
+	self.services
+		.appservice
+		.notify_device_changes(user_id)
+		.await;
 }
 
 #[implement(super::Service)]